@@ -0,0 +1,71 @@
+use crate::error::Error;
+
+/// Command arguments split between options and positional values
+#[derive(Debug, Default)]
+pub struct Args {
+    positional: Vec<String>,
+    options: Vec<(String, Option<String>)>,
+    /// Arguments given after `--`
+    rest: Vec<String>,
+}
+
+impl Args {
+    /// Parses raw arguments, `value_options` lists the options which take the next argument as value
+    pub fn parse(args: &[String], value_options: &[&str]) -> Self {
+        let mut parsed = Args::default();
+        let mut iter = args.iter();
+        while let Some(arg) = iter.next() {
+            if arg == "--" {
+                parsed.rest.extend(iter.by_ref().cloned());
+            } else if let Some((name, value)) =
+                arg.split_once('=').filter(|_| arg.starts_with("--"))
+            {
+                parsed
+                    .options
+                    .push((name.to_owned(), Some(value.to_owned())));
+            } else if arg.starts_with('-') && arg.len() > 1 {
+                let value = if value_options.contains(&arg.as_str()) {
+                    iter.next().cloned()
+                } else {
+                    None
+                };
+                parsed.options.push((arg.clone(), value));
+            } else {
+                parsed.positional.push(arg.clone());
+            }
+        }
+        parsed
+    }
+
+    /// Whether one of the given flags was passed
+    pub fn flag(&self, names: &[&str]) -> bool {
+        self.options
+            .iter()
+            .any(|(name, _)| names.contains(&name.as_str()))
+    }
+
+    /// The last value given to one of the options
+    pub fn value(&self, names: &[&str]) -> Option<&str> {
+        self.options
+            .iter()
+            .rev()
+            .find(|(name, _)| names.contains(&name.as_str()))
+            .and_then(|(_, value)| value.as_deref())
+    }
+
+    pub fn positional(&self) -> &[String] {
+        &self.positional
+    }
+
+    pub fn rest(&self) -> &[String] {
+        &self.rest
+    }
+
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.positional.get(index).map(String::as_str)
+    }
+
+    pub fn required(&self, index: usize, name: &'static str) -> Result<&str, Error> {
+        self.get(index).ok_or(Error::MissingArgument(name))
+    }
+}
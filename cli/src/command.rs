@@ -1,23 +1,262 @@
+use fursion::{
+    remote::{Remote, DEFAULT_REMOTE_NAME},
+    repo::Repo,
+};
+
 use crate::error::Error;
 
+mod access;
+mod bisect;
+mod blame;
+mod branch;
+mod bundle;
+mod checkout;
+mod cherry_pick;
+mod clone;
+mod commit;
+mod config;
+mod credential;
+mod diff;
+mod fetch;
+mod grep;
+mod init;
+mod log;
+mod merge;
+mod operation;
+mod pull;
+mod push;
+mod rebase;
+mod reflog;
+mod remote;
+mod reset;
+mod rev_parse;
+mod serve;
+mod stage;
+mod stash;
+mod tag;
+mod user;
+
 #[derive(Debug)]
 pub struct Command {
-    pub function: for<'a> fn(args: &'a [String]) -> Result<&'a str, Error>,
+    pub function: fn(args: &[String]) -> Result<String, Error>,
     pub name: &'static str,
     pub alias: Option<&'static str>,
 }
 
-pub const COMMANDS: &[Command] = &[Command {
-    name: "commit",
-    alias: None,
-    function: |arg| Ok("coucou"),
-}];
+pub const COMMANDS: &[Command] = &[
+    Command {
+        name: "init",
+        alias: None,
+        function: init::run,
+    },
+    Command {
+        name: "config",
+        alias: None,
+        function: config::run,
+    },
+    Command {
+        name: "add",
+        alias: Some("stage"),
+        function: stage::add,
+    },
+    Command {
+        name: "unstage",
+        alias: None,
+        function: stage::unstage,
+    },
+    Command {
+        name: "commit",
+        alias: None,
+        function: commit::run,
+    },
+    Command {
+        name: "branch",
+        alias: None,
+        function: branch::run,
+    },
+    Command {
+        name: "checkout",
+        alias: Some("switch"),
+        function: checkout::run,
+    },
+    Command {
+        name: "clone",
+        alias: None,
+        function: clone::run,
+    },
+    Command {
+        name: "cherry-pick",
+        alias: None,
+        function: cherry_pick::cherry_pick,
+    },
+    Command {
+        name: "revert",
+        alias: None,
+        function: cherry_pick::revert,
+    },
+    Command {
+        name: "diff",
+        alias: None,
+        function: diff::run,
+    },
+    Command {
+        name: "bisect",
+        alias: None,
+        function: bisect::run,
+    },
+    Command {
+        name: "blame",
+        alias: Some("annotate"),
+        function: blame::run,
+    },
+    Command {
+        name: "grep",
+        alias: None,
+        function: grep::run,
+    },
+    Command {
+        name: "log",
+        alias: None,
+        function: log::run,
+    },
+    Command {
+        name: "op",
+        alias: None,
+        function: operation::run,
+    },
+    Command {
+        name: "undo",
+        alias: None,
+        function: operation::undo,
+    },
+    Command {
+        name: "redo",
+        alias: None,
+        function: operation::redo,
+    },
+    Command {
+        name: "bundle",
+        alias: None,
+        function: bundle::run,
+    },
+    Command {
+        name: "credential",
+        alias: None,
+        function: credential::run,
+    },
+    Command {
+        name: "fetch",
+        alias: None,
+        function: fetch::run,
+    },
+    Command {
+        name: "pull",
+        alias: None,
+        function: pull::run,
+    },
+    Command {
+        name: "remote",
+        alias: None,
+        function: remote::run,
+    },
+    Command {
+        name: "push",
+        alias: None,
+        function: push::run,
+    },
+    Command {
+        name: "merge",
+        alias: None,
+        function: merge::run,
+    },
+    Command {
+        name: "rebase",
+        alias: None,
+        function: rebase::run,
+    },
+    Command {
+        name: "reflog",
+        alias: None,
+        function: reflog::run,
+    },
+    Command {
+        name: "reset",
+        alias: None,
+        function: reset::run,
+    },
+    Command {
+        name: "rev-parse",
+        alias: None,
+        function: rev_parse::run,
+    },
+    Command {
+        name: "stash",
+        alias: None,
+        function: stash::run,
+    },
+    Command {
+        name: "tag",
+        alias: None,
+        function: tag::run,
+    },
+    Command {
+        name: "access",
+        alias: None,
+        function: access::run,
+    },
+    Command {
+        name: "serve",
+        alias: None,
+        function: serve::run,
+    },
+    Command {
+        name: "user",
+        alias: None,
+        function: user::run,
+    },
+];
 
 impl Command {
-    pub fn from_str<'a>(value: &'a str) -> Result<&Self, Error> {
+    pub fn from_str(value: &str) -> Result<&Self, Error> {
         COMMANDS
             .iter()
             .find(|c| c.name == value || c.alias == Some(value))
             .ok_or(Error::CommandNotFound(value.to_string()))
     }
 }
+
+/// Reads the repo containing the current directory
+pub fn open_repo() -> Result<Repo, Error> {
+    Ok(Repo::discover(&std::env::current_dir()?)?)
+}
+
+/// The remote of the given name, or a remote at the given url, the first remote of the repo if [`None`]
+pub fn remote_arg(repo: &Repo, name_or_url: Option<&str>) -> Result<Remote, Error> {
+    match name_or_url {
+        Some(name) if repo.remote(name).is_ok() => Ok(repo.remote(name)?.clone()),
+        Some(url) if url.contains('/') => Ok(Remote::new(DEFAULT_REMOTE_NAME, &url_arg(url)?)),
+        Some(name) => Ok(repo.remote(name)?.clone()),
+        None => repo
+            .remotes
+            .first()
+            .cloned()
+            .ok_or(Error::MissingArgument("remote")),
+    }
+}
+
+/// The url of a remote, the path of a repo on this machine being made absolute so that
+/// it can be used from any directory
+pub fn url_arg(url: &str) -> Result<String, Error> {
+    match Remote::local_path(url) {
+        Some(path) => Ok(std::fs::canonicalize(path)?.to_string_lossy().into_owned()),
+        None => Ok(url.to_owned()),
+    }
+}
+
+/// Runs the network calls of the library to completion
+pub fn block_on<F: std::future::Future>(future: F) -> Result<F::Output, Error> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    Ok(runtime.block_on(future))
+}
@@ -0,0 +1,96 @@
+use fursion::access::{AccessControl, Permission, ProtectedBranch, Visibility};
+
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// Manages who may do what with a hosted repo: `access` shows the rules,
+/// `access visibility (public | private)`, `access grant [--team] <name> <read | write | admin>`,
+/// `access revoke [--team] <name>`,
+/// `access protect <pattern> [--allow-force-push] [--allow-direct-push]`
+/// and `access unprotect <pattern>`
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let repo = open_repo()?;
+    let mut access = repo.access_control()?;
+    let team = args.flag(&["--team"]);
+
+    match args.get(0) {
+        None => return Ok(render(&access)),
+        Some("visibility") => {
+            access.visibility = match args.required(1, "visibility")? {
+                "public" => Visibility::Public,
+                "private" => Visibility::Private,
+                other => return Err(Error::InvalidArgument(other.to_owned())),
+            }
+        }
+        Some("grant") => {
+            let name = args.required(1, "name")?.to_owned();
+            let permission = args.required(2, "permission")?;
+            let permission = Permission::from_name(permission)
+                .ok_or(Error::InvalidArgument(permission.to_owned()))?;
+            if team {
+                access.teams.insert(name, permission);
+            } else {
+                access.users.insert(name, permission);
+            }
+        }
+        Some("revoke") => {
+            let name = args.required(1, "name")?;
+            let granted = if team {
+                &mut access.teams
+            } else {
+                &mut access.users
+            };
+            granted
+                .remove(name)
+                .ok_or(Error::InvalidArgument(name.to_owned()))?;
+        }
+        Some("protect") => {
+            let pattern = args.required(1, "pattern")?;
+            access
+                .protected_branches
+                .retain(|rule| rule.pattern != pattern);
+            access.protected_branches.push(ProtectedBranch {
+                pattern: pattern.to_owned(),
+                allow_force_push: args.flag(&["--allow-force-push"]),
+                allow_direct_push: args.flag(&["--allow-direct-push"]),
+            });
+        }
+        Some("unprotect") => {
+            let pattern = args.required(1, "pattern")?;
+            let count = access.protected_branches.len();
+            access
+                .protected_branches
+                .retain(|rule| rule.pattern != pattern);
+            if access.protected_branches.len() == count {
+                return Err(Error::InvalidArgument(pattern.to_owned()));
+            }
+        }
+        Some(other) => return Err(Error::InvalidArgument(other.to_owned())),
+    }
+    repo.save_access_control(&access)?;
+    Ok(String::new())
+}
+
+fn render(access: &AccessControl) -> String {
+    let mut out = match access.visibility {
+        Visibility::Public => "public\n".to_owned(),
+        Visibility::Private => "private\n".to_owned(),
+    };
+    for (user, permission) in &access.users {
+        out.push_str(&format!("user {} {}\n", user, permission.name()));
+    }
+    for (team, permission) in &access.teams {
+        out.push_str(&format!("team {} {}\n", team, permission.name()));
+    }
+    for rule in &access.protected_branches {
+        out.push_str(&format!("protected {}", rule.pattern));
+        if rule.allow_force_push {
+            out.push_str(" --allow-force-push");
+        }
+        if rule.allow_direct_push {
+            out.push_str(" --allow-direct-push");
+        }
+        out.push('\n');
+    }
+    out
+}
@@ -0,0 +1,89 @@
+use fursion::{
+    bisect::{BisectMark, BisectStatus},
+    repo::Repo,
+};
+
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `bisect start [<bad> [<good>...]]`, `bisect good|bad|skip [<revision>...]`,
+/// `bisect run <command> [<arg>...]` to answer with the exit status of a command,
+/// and `bisect reset` to end the session
+pub fn run(raw_args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(raw_args, &[]);
+    let mut repo = open_repo()?;
+    let positional = args.positional();
+    let revisions = |repo: &Repo| {
+        positional
+            .iter()
+            .skip(1)
+            .map(|rev| repo.resolve(rev))
+            .collect::<Result<Vec<_>, _>>()
+    };
+
+    let status = match args.required(0, "subcommand")? {
+        "start" => {
+            let revisions = revisions(&repo)?;
+            repo.bisect_start(
+                revisions.first().copied(),
+                revisions.get(1..).unwrap_or(&[]),
+            )?
+        }
+        "good" | "bad" | "skip" => {
+            let mark = match positional[0].as_str() {
+                "good" => BisectMark::Good,
+                "bad" => BisectMark::Bad,
+                _ => BisectMark::Skip,
+            };
+            let revisions = revisions(&repo)?;
+            if revisions.is_empty() {
+                repo.bisect_mark(mark, None)?
+            } else {
+                let mut status = None;
+                for id in revisions {
+                    status = Some(repo.bisect_mark(mark, Some(id))?);
+                }
+                status.expect("at least one revision")
+            }
+        }
+        "run" => {
+            // Everything after `run` belongs to the command, including its options
+            let start = raw_args.iter().position(|arg| arg == "run").unwrap_or(0) + 1;
+            let command = &raw_args[start..];
+            let program = command.first().ok_or(Error::MissingArgument("command"))?;
+            repo.bisect_run(program, &command[1..])?
+        }
+        "reset" => {
+            repo.bisect_reset()?;
+            return Ok(String::new());
+        }
+        other => return Err(Error::InvalidArgument(other.to_owned())),
+    };
+
+    Ok(match status {
+        BisectStatus::NeedBad => "Waiting for a bad commit\n".to_owned(),
+        BisectStatus::NeedGood => "Waiting for a good commit\n".to_owned(),
+        BisectStatus::Testing { commit, remaining } => format!(
+            "Bisecting: {} commits left (roughly {} steps)\n[{}] {}\n",
+            remaining,
+            usize::BITS - remaining.leading_zeros(),
+            commit.to_hex(),
+            repo.history.find(&commit)?.summary()
+        ),
+        BisectStatus::Found(commit) => {
+            let found = repo.history.find(&commit)?;
+            format!(
+                "{} is the first bad commit\nAuthor: {}\n\n    {}\n",
+                commit.to_hex(),
+                found.author,
+                found.summary()
+            )
+        }
+        BisectStatus::OnlySkipped(commits) => format!(
+            "Only skipped commits are left to test, the first bad commit is one of:\n{}",
+            commits
+                .iter()
+                .map(|id| format!("{}\n", id.to_hex()))
+                .collect::<String>()
+        ),
+    })
+}
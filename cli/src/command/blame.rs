@@ -0,0 +1,50 @@
+use fursion::{blame::BlameOptions, date};
+
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `blame [--follow] <path> [<revision>]`
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let path = args.required(0, "path")?;
+    let repo = open_repo()?;
+
+    let at = args.get(1).map(|rev| repo.resolve(rev)).transpose()?;
+    let options = BlameOptions {
+        follow_renames: args.flag(&["--follow", "-C"]),
+    };
+    let blame = repo.blame(path, at, &options)?;
+
+    let author_width = blame
+        .lines
+        .iter()
+        .map(|line| line.origin.author.chars().count())
+        .max()
+        .unwrap_or(0);
+    let number_width = blame.lines.len().to_string().len();
+    let show_paths = blame
+        .lines
+        .iter()
+        .any(|line| line.origin.path != blame.path);
+
+    Ok(blame
+        .lines
+        .iter()
+        .enumerate()
+        .map(|(i, line)| {
+            let path = if show_paths {
+                format!(" {}", line.origin.path)
+            } else {
+                String::new()
+            };
+            format!(
+                "{}{} ({:<author_width$} {} {:>number_width$}) {}\n",
+                line.origin.commit.to_hex(),
+                path,
+                line.origin.author,
+                date::format_timestamp(line.origin.timestamp),
+                i + 1,
+                line.content,
+            )
+        })
+        .collect())
+}
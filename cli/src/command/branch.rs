@@ -0,0 +1,33 @@
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `branch` lists branches, `branch <name> [<start>]` creates one, `branch -d <name>` deletes one
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &["-d", "--delete"]);
+    let mut repo = open_repo()?;
+
+    if let Some(name) = args.value(&["-d", "--delete"]) {
+        repo.delete_branch(name)?;
+        return Ok(format!("Deleted branch {}\n", name));
+    }
+
+    match args.get(0) {
+        Some(name) => {
+            let start = args.get(1).map(|rev| repo.resolve(rev)).transpose()?;
+            repo.create_branch(name, start)?;
+            Ok(String::new())
+        }
+        None => Ok(repo
+            .refs
+            .branches
+            .keys()
+            .map(|name| {
+                let marker = if repo.refs.current_branch() == Some(name) {
+                    '*'
+                } else {
+                    ' '
+                };
+                format!("{} {}\n", marker, name)
+            })
+            .collect()),
+    }
+}
@@ -0,0 +1,75 @@
+use std::path::Path;
+
+use fursion::bundle::Bundle;
+
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `bundle create <file> (--all | <revision>...)` writes branches and tags with their commits
+/// to a file, `bundle verify <file>` checks the repo can fetch from it and
+/// `bundle list-heads <file>` shows its refs
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let subcommand = args.required(0, "subcommand")?;
+    let path = Path::new(args.required(1, "file")?);
+
+    match subcommand {
+        "create" => {
+            let repo = open_repo()?;
+            let revs = if args.flag(&["--all"]) {
+                repo.refs
+                    .branches
+                    .keys()
+                    .chain(repo.refs.tags.keys())
+                    .cloned()
+                    .collect()
+            } else {
+                args.positional()[2..].to_vec()
+            };
+            if revs.is_empty() {
+                return Err(Error::MissingArgument("revision"));
+            }
+            let revs = revs.iter().map(String::as_str).collect::<Vec<_>>();
+            let bundle = repo.create_bundle(&revs)?;
+            bundle.write(path)?;
+            Ok(format!(
+                "Bundled {} commits, {} refs\n",
+                bundle.pack.commits.len(),
+                bundle.refs.branches.len() + bundle.refs.tags.len()
+            ))
+        }
+        "verify" => {
+            let bundle = Bundle::read(path)?;
+            open_repo()?.verify_bundle(&bundle)?;
+            let mut out = list_heads(&bundle);
+            if bundle.prerequisites.is_empty() {
+                out.push_str("The bundle records a complete history.\n");
+            } else {
+                out.push_str(&format!(
+                    "The bundle requires these {} commits:\n",
+                    bundle.prerequisites.len()
+                ));
+                for id in &bundle.prerequisites {
+                    out.push_str(&format!("{}\n", id.to_hex()));
+                }
+            }
+            out.push_str(&format!("{} is okay\n", path.display()));
+            Ok(out)
+        }
+        "list-heads" => Ok(list_heads(&Bundle::read(path)?)),
+        other => Err(Error::InvalidArgument(other.to_owned())),
+    }
+}
+
+fn list_heads(bundle: &Bundle) -> String {
+    let branches = bundle
+        .refs
+        .branches
+        .iter()
+        .map(|(name, id)| format!("{} {}\n", id.to_hex(), name));
+    let tags = bundle
+        .refs
+        .tags
+        .iter()
+        .map(|(name, tag)| format!("{} tags/{}\n", tag.target().to_hex(), name));
+    branches.chain(tags).collect()
+}
@@ -0,0 +1,18 @@
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `checkout [-b] <branch | revision>`
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let target = args.required(0, "target")?;
+    let mut repo = open_repo()?;
+
+    if args.flag(&["-b"]) {
+        repo.create_branch(target, None)?;
+    }
+    repo.checkout(target)?;
+
+    Ok(match repo.refs.current_branch() {
+        Some(branch) => format!("Switched to branch '{}'\n", branch),
+        None => format!("HEAD is now at {}\n", repo.head_or_err()?.to_hex()),
+    })
+}
@@ -0,0 +1,55 @@
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `cherry-pick <revision>...` applies the changes of commits on top of HEAD,
+/// `cherry-pick --continue` or `--abort` ends one stopped on conflicts
+pub fn cherry_pick(args: &[String]) -> Result<String, Error> {
+    run(args, false)
+}
+
+/// `revert <revision>...` makes commits undoing the changes of commits,
+/// `revert --continue` or `--abort` ends one stopped on conflicts
+pub fn revert(args: &[String]) -> Result<String, Error> {
+    run(args, true)
+}
+
+fn run(args: &[String], revert: bool) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let mut repo = open_repo()?;
+
+    if args.flag(&["--abort"]) {
+        repo.abort_pick()?;
+        return Ok(String::new());
+    }
+    if args.flag(&["--continue"]) {
+        let id = repo.continue_pick()?;
+        return Ok(format!(
+            "[{}] {}\n",
+            id.to_hex(),
+            repo.history.find(&id)?.summary()
+        ));
+    }
+
+    let ids = args
+        .positional()
+        .iter()
+        .map(|rev| repo.resolve(rev))
+        .collect::<Result<Vec<_>, _>>()?;
+    if ids.is_empty() {
+        return Err(Error::MissingArgument("revision"));
+    }
+
+    let mut out = String::new();
+    for id in ids {
+        let new = if revert {
+            repo.revert(id)?
+        } else {
+            repo.cherry_pick(id)?
+        };
+        out.push_str(&format!(
+            "[{}] {}\n",
+            new.to_hex(),
+            repo.history.find(&new)?.summary()
+        ));
+    }
+    Ok(out)
+}
@@ -0,0 +1,45 @@
+use fursion::{
+    remote::{Remote, DEFAULT_REMOTE_NAME},
+    repo::Repo,
+};
+
+use crate::{
+    args::Args,
+    command::{block_on, url_arg},
+    error::Error,
+};
+
+/// `clone [--author <name>] [-o <remote name>] <url, path or bundle> [<path>]`, the path
+/// defaults to the last part of the url
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &["--author", "-o", "--origin"]);
+    let url = args.required(0, "url")?;
+    let name = url
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()
+        .map(|name| {
+            name.trim_end_matches(".fursion")
+                .trim_end_matches(".bundle")
+        })
+        .filter(|name| !name.is_empty())
+        .ok_or(Error::InvalidArgument(url.to_owned()))?;
+    let path = std::env::current_dir()?.join(args.get(1).unwrap_or(name));
+
+    let remote_name = args
+        .value(&["-o", "--origin"])
+        .unwrap_or(DEFAULT_REMOTE_NAME);
+    let remote = Remote::new(remote_name, &url_arg(url)?);
+    let mut repo = block_on(Repo::clone_from(&remote, &path))??;
+    if let Some(author) = args.value(&["--author"]) {
+        repo.metadata.author = author.to_owned();
+        repo.save_metadata()?;
+    }
+
+    Ok(format!(
+        "Cloned {} into {}, {} commits\n",
+        url,
+        path.display(),
+        repo.history.len()
+    ))
+}
@@ -0,0 +1,18 @@
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `commit -m <message>`, or `commit --amend [-m <message>]` to replace the last commit
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &["-m", "--message"]);
+    let message = args.value(&["-m", "--message"]);
+
+    let mut repo = open_repo()?;
+    let id = if args.flag(&["--amend"]) {
+        repo.commit_amend(message)?
+    } else {
+        repo.commit(message.ok_or(Error::MissingArgument("message"))?)?
+    };
+
+    let branch = repo.refs.current_branch().unwrap_or("detached HEAD");
+    let summary = repo.history.find(&id)?.summary();
+    Ok(format!("[{} {}] {}\n", branch, id.to_hex(), summary))
+}
@@ -0,0 +1,41 @@
+use fursion::config::{PullMode, Upstream};
+
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `config pull.mode [ff-only | merge | rebase]` and
+/// `config branch.<name>.upstream [<remote>/<branch>]` show or set a setting
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let repo = open_repo()?;
+    let key = args.required(0, "key")?;
+    let value = args.get(1);
+
+    if key == "pull.mode" {
+        let Some(value) = value else {
+            return Ok(format!("{}\n", repo.config()?.pull.name()));
+        };
+        let mut config = repo.config()?;
+        config.pull = PullMode::from_name(value).ok_or(Error::InvalidArgument(value.to_owned()))?;
+        repo.save_config(&config)?;
+        return Ok(String::new());
+    }
+
+    let branch = key
+        .strip_prefix("branch.")
+        .and_then(|key| key.strip_suffix(".upstream"))
+        .ok_or(Error::InvalidArgument(key.to_owned()))?;
+    let Some(value) = value else {
+        return Ok(format!("{}\n", repo.upstream(branch)?.tracking_name()));
+    };
+    let (remote, upstream_branch) = value
+        .split_once('/')
+        .ok_or(Error::InvalidArgument(value.to_owned()))?;
+    repo.set_upstream(
+        branch,
+        Upstream {
+            remote: remote.to_owned(),
+            branch: upstream_branch.to_owned(),
+        },
+    )?;
+    Ok(String::new())
+}
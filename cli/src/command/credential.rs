@@ -0,0 +1,48 @@
+use fursion::credential::{credentials_for, CredentialFile, Credentials};
+
+use crate::{args::Args, error::Error};
+
+/// `credential store <url> (--token <token> | --user <name> --password <password>)` saves the
+/// credentials sent to the urls starting with `<url>`, `credential erase <url>` forgets them
+/// and `credential get <url>` shows the ones which would be sent
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &["--token", "--user", "--password"]);
+    let subcommand = args.required(0, "subcommand")?;
+    let url = args.required(1, "url")?;
+    let path = CredentialFile::default_path().ok_or(Error::MissingArgument("home dir"))?;
+
+    match subcommand {
+        "store" => {
+            let credentials = match (
+                args.value(&["--token"]),
+                args.value(&["--user"]),
+                args.value(&["--password"]),
+            ) {
+                (Some(token), _, _) => Credentials::Token(token.to_owned()),
+                (None, Some(user), Some(password)) => Credentials::Basic {
+                    user: user.to_owned(),
+                    password: password.to_owned(),
+                },
+                _ => return Err(Error::MissingArgument("token")),
+            };
+            let mut file = CredentialFile::read(&path)?;
+            file.set(url, credentials)?;
+            file.save(&path)?;
+            Ok(String::new())
+        }
+        "erase" => {
+            let mut file = CredentialFile::read(&path)?;
+            if !file.remove(url) {
+                return Err(Error::InvalidArgument(url.to_owned()));
+            }
+            file.save(&path)?;
+            Ok(String::new())
+        }
+        "get" => Ok(match credentials_for(url)? {
+            Some(Credentials::Token(_)) => "token\n".to_owned(),
+            Some(Credentials::Basic { user, .. }) => format!("basic {}\n", user),
+            None => String::new(),
+        }),
+        other => Err(Error::InvalidArgument(other.to_owned())),
+    }
+}
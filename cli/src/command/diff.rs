@@ -0,0 +1,83 @@
+use std::fs;
+
+use fursion::{
+    commit::FileChanges,
+    diff::{self, DiffMode, DiffOptions},
+    tree::{self, Tree},
+};
+
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `diff [--staged] [--word-diff | --char-diff] [--color] [-U <n>] [<path>...]`
+/// or `diff --no-index [options] <old> <new>` to compare two files
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &["-U", "--unified"]);
+    let options = diff_options(&args)?;
+
+    if args.flag(&["--no-index"]) {
+        let old = fs::read_to_string(args.required(0, "old")?)?;
+        let new = fs::read_to_string(args.required(1, "new")?)?;
+        return Ok(diff::render(&old, &new, &options));
+    }
+
+    let repo = open_repo()?;
+    let (old_tree, changes) = if args.flag(&["--staged", "--cached"]) {
+        (repo.head_tree()?, repo.stated_changes.clone())
+    } else {
+        (repo.index_tree()?, repo.get_unstated_diff()?)
+    };
+
+    let paths = args.positional();
+    let changes = changes
+        .into_iter()
+        .filter(|c| paths.is_empty() || paths.iter().any(|p| tree::path_matches(c.path(), p)))
+        .collect::<Vec<_>>();
+
+    Ok(render_changes(&old_tree, &changes, &options))
+}
+
+/// Renders a list of changes along with the name of the files
+pub fn render_changes(old_tree: &Tree, changes: &[FileChanges], options: &DiffOptions) -> String {
+    let mut out = String::new();
+    for file_changes in changes {
+        let path = file_changes.path();
+        let old = old_tree.get(path).map(String::as_str).unwrap_or("");
+        let new = if file_changes.is_removal() {
+            String::new()
+        } else {
+            file_changes.apply(old)
+        };
+
+        out.push_str(&format!("diff a/{} b/{}\n", path, path));
+        if file_changes.is_removal() {
+            out.push_str("deleted file\n");
+        } else if !old_tree.contains_key(path) {
+            out.push_str("new file\n");
+        }
+        out.push_str(&diff::render(old, &new, options));
+    }
+    out
+}
+
+pub fn diff_options(args: &Args) -> Result<DiffOptions, Error> {
+    let mode = if args.flag(&["--word-diff", "-w"]) {
+        DiffMode::Word
+    } else if args.flag(&["--char-diff"]) {
+        DiffMode::Char
+    } else {
+        DiffMode::Line
+    };
+
+    let context = match args.value(&["-U", "--unified"]) {
+        Some(n) => n
+            .parse()
+            .map_err(|_| Error::InvalidArgument(n.to_owned()))?,
+        None => DiffOptions::default().context,
+    };
+
+    Ok(DiffOptions {
+        mode,
+        context,
+        color: args.flag(&["--color"]),
+    })
+}
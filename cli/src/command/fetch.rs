@@ -0,0 +1,61 @@
+use fursion::{commit::CommitId, fetch::FetchReport, push::RefUpdateStatus};
+
+use crate::{
+    args::Args,
+    command::{block_on, open_repo},
+    error::Error,
+};
+
+/// `fetch [<remote>]`, every remote by default
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let mut repo = open_repo()?;
+
+    let reports = match args.get(0) {
+        Some(name) => {
+            let remote = repo.remote(name)?.clone();
+            vec![block_on(repo.fetch_from(&remote))??]
+        }
+        None => block_on(repo.fetch())??,
+    };
+
+    Ok(reports.iter().map(render).collect())
+}
+
+pub(crate) fn render(report: &FetchReport) -> String {
+    if report.updates.is_empty() && report.tags.is_empty() {
+        return String::new();
+    }
+    let mut out = format!("From {}\n", report.remote);
+    for update in &report.updates {
+        let line = match (update.old, update.new, update.status) {
+            (None, _, _) => format!(" * [new branch]      {}", update.name),
+            (_, None, _) => format!(" - [deleted]         {}", update.name),
+            (_, _, RefUpdateStatus::RejectedNonFastForward) => {
+                format!(" ! [rejected]        {} (non-fast-forward)", update.name)
+            }
+            (_, _, RefUpdateStatus::ForcedUpdate) => format!(
+                " + {}...{}  {} (forced update)",
+                hex(update.old),
+                hex(update.new),
+                update.name
+            ),
+            _ => format!(
+                "   {}..{}   {}",
+                hex(update.old),
+                hex(update.new),
+                update.name
+            ),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    for tag in &report.tags {
+        out.push_str(&format!(" * [new tag]         {}\n", tag));
+    }
+    out
+}
+
+fn hex(id: Option<CommitId>) -> String {
+    id.map(|id| id.to_hex()).unwrap_or_default()
+}
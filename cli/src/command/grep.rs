@@ -0,0 +1,43 @@
+use fursion::search::Pattern;
+
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `grep [-E] [-n] [-l] [-i] <pattern> [<revision>] [-- <path>...]`, searches the working
+/// directory, or the files at a revision, `-E` for a regular expression
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let expression = args.required(0, "pattern")?;
+    let repo = open_repo()?;
+
+    let ignore_case = args.flag(&["-i", "--ignore-case"]);
+    let pattern = match (args.flag(&["-E", "--extended-regexp"]), ignore_case) {
+        (true, true) => Pattern::regex(&format!("(?i){}", expression))?,
+        (true, false) => Pattern::regex(expression)?,
+        (false, true) => Pattern::text_ignore_case(expression),
+        (false, false) => Pattern::text(expression),
+    };
+
+    let revision = args.get(1);
+    let at = revision.map(|rev| repo.resolve(rev)).transpose()?;
+    let matches = repo.grep(at, &pattern, args.rest())?;
+
+    let prefix = revision.map(|rev| format!("{}:", rev)).unwrap_or_default();
+    let mut out = String::new();
+    let mut last_path = None;
+    for found in &matches {
+        if args.flag(&["-l", "--files-with-matches"]) {
+            if last_path != Some(&found.path) {
+                out.push_str(&format!("{}{}\n", prefix, found.path));
+                last_path = Some(&found.path);
+            }
+        } else if args.flag(&["-n", "--line-number"]) {
+            out.push_str(&format!(
+                "{}{}:{}:{}\n",
+                prefix, found.path, found.line_number, found.line
+            ));
+        } else {
+            out.push_str(&format!("{}{}:{}\n", prefix, found.path, found.line));
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,21 @@
+use fursion::repo::Repo;
+
+use crate::{args::Args, error::Error};
+
+/// `init [--author <name>] [<path>]`
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &["--author"]);
+    let current_dir = std::env::current_dir()?;
+    let path = match args.get(0) {
+        Some(path) => current_dir.join(path),
+        None => current_dir,
+    };
+
+    let mut repo = Repo::init(&path)?;
+    if let Some(author) = args.value(&["--author"]) {
+        repo.metadata.author = author.to_owned();
+        repo.save_metadata()?;
+    }
+
+    Ok(format!("Initialized empty repo in {}\n", path.display()))
+}
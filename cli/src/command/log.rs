@@ -0,0 +1,90 @@
+use fursion::{
+    date,
+    log::{self, LogFormat, LogQuery},
+    search::Pattern,
+};
+
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `log [--oneline] [--graph] [--all] [--author <name>] [--since <date>] [--until <date>]
+/// [--grep <text>] [-S <string> [--pickaxe-regex]] [-n <count>] [<revision>...] [-- <path>...]`,
+/// `-S` only keeps the commits adding or removing occurrences of the string, or regex
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(
+        args,
+        &[
+            "--author",
+            "--since",
+            "--until",
+            "--grep",
+            "-S",
+            "-n",
+            "--max-count",
+        ],
+    );
+    let repo = open_repo()?;
+
+    let mut query = LogQuery::new();
+    if let Some(author) = args.value(&["--author"]) {
+        query = query.author(author);
+    }
+    if let Some(since) = args.value(&["--since"]) {
+        query = query.since(parse_date(since)?);
+    }
+    if let Some(until) = args.value(&["--until"]) {
+        query = query.until(parse_date(until)?);
+    }
+    if let Some(text) = args.value(&["--grep"]) {
+        query = query.grep(text);
+    }
+    if let Some(count) = args.value(&["-n", "--max-count"]) {
+        query = query.max_count(
+            count
+                .parse()
+                .map_err(|_| Error::InvalidArgument(count.to_owned()))?,
+        );
+    }
+    for path in args.rest() {
+        query = query.path(path);
+    }
+
+    let mut revs = args
+        .positional()
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+    if args.flag(&["--all"]) {
+        revs.extend(repo.refs.branches.keys().map(String::as_str));
+        revs.extend(repo.head().is_some().then_some("HEAD"));
+    }
+
+    let pickaxe = match args.value(&["-S"]) {
+        Some(expression) if args.flag(&["--pickaxe-regex"]) => Some(Pattern::regex(expression)?),
+        Some(text) => Some(Pattern::text(text)),
+        None => None,
+    };
+    let commits = match pickaxe {
+        Some(pattern) => repo
+            .pickaxe(&revs, &query, &pattern)?
+            .into_iter()
+            .map(|found| found.commit)
+            .collect(),
+        None => repo.log(&revs, &query)?,
+    };
+    let format = if args.flag(&["--oneline"]) {
+        LogFormat::OneLine
+    } else {
+        LogFormat::Full
+    };
+
+    Ok(log::render(
+        &repo,
+        &commits,
+        format,
+        args.flag(&["--graph"]),
+    ))
+}
+
+fn parse_date(s: &str) -> Result<u64, Error> {
+    date::parse_date(s).ok_or(Error::InvalidArgument(s.to_owned()))
+}
@@ -0,0 +1,35 @@
+use fursion::merge::MergeOutcome;
+
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `merge [-m <message>] <revision>` brings a commit into HEAD,
+/// `merge --continue` or `--abort` ends one stopped on conflicts
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &["-m", "--message"]);
+    let mut repo = open_repo()?;
+
+    if args.flag(&["--abort"]) {
+        repo.merge_abort()?;
+        return Ok(String::new());
+    }
+    if args.flag(&["--continue"]) {
+        let id = repo.merge_continue()?;
+        return Ok(format!(
+            "[{}] {}\n",
+            id.to_hex(),
+            repo.history.find(&id)?.summary()
+        ));
+    }
+
+    let rev = args.required(0, "revision")?;
+    let theirs = repo.resolve(rev)?;
+    let message = args
+        .value(&["-m", "--message"])
+        .map(str::to_owned)
+        .unwrap_or_else(|| format!("Merge '{}'", rev));
+    Ok(match repo.merge(theirs, &message)? {
+        MergeOutcome::UpToDate => "Already up to date.\n".to_owned(),
+        MergeOutcome::FastForward(id) => format!("Fast-forward to {}\n", id.to_hex()),
+        MergeOutcome::Merged(id) => format!("[{}] {}\n", id.to_hex(), message),
+    })
+}
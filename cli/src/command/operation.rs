@@ -0,0 +1,56 @@
+use fursion::{date, repo::Repo};
+
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `op log` lists the operations done on the repo, newest first,
+/// `op restore <id>` puts the repo back in the state it was after an operation
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let mut repo = open_repo()?;
+
+    match args.get(0).unwrap_or("log") {
+        "log" => Ok(repo
+            .operations()?
+            .iter()
+            .map(|op| {
+                format!(
+                    "{:<4} {} {} {}\n",
+                    op.id,
+                    date::format_timestamp(op.timestamp),
+                    op.who,
+                    op.description
+                )
+            })
+            .collect()),
+        "restore" => {
+            let id = args.required(1, "operation")?;
+            let id = id
+                .parse()
+                .map_err(|_| Error::InvalidArgument(id.to_owned()))?;
+            repo.restore_operation(id)?;
+            Ok(format!("Restored operation {}\n", id))
+        }
+        other => Err(Error::InvalidArgument(other.to_owned())),
+    }
+}
+
+/// `undo` goes back to the state before the last operation
+pub fn undo(_args: &[String]) -> Result<String, Error> {
+    let mut repo = open_repo()?;
+    repo.undo()?;
+    last_description(&repo)
+}
+
+/// `redo` goes back to the state undone by the last undo
+pub fn redo(_args: &[String]) -> Result<String, Error> {
+    let mut repo = open_repo()?;
+    repo.redo()?;
+    last_description(&repo)
+}
+
+fn last_description(repo: &Repo) -> Result<String, Error> {
+    let operations = repo.operations()?;
+    Ok(operations
+        .first()
+        .map_or(String::new(), |op| format!("{}\n", op.description)))
+}
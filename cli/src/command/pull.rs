@@ -0,0 +1,58 @@
+use fursion::{config::PullMode, pull::PullOutcome};
+
+use crate::{
+    args::Args,
+    command::{block_on, fetch, open_repo},
+    error::Error,
+};
+
+/// `pull [--ff-only | --no-rebase | --rebase]` fetches the upstream of the checked out branch
+/// and brings it in, as set by `config pull.mode` unless an option is given
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let mut repo = open_repo()?;
+
+    let mode = if args.flag(&["--ff-only"]) {
+        PullMode::FastForwardOnly
+    } else if args.flag(&["--no-rebase"]) {
+        PullMode::Merge
+    } else if args.flag(&["-r", "--rebase"]) {
+        PullMode::Rebase
+    } else {
+        repo.config()?.pull
+    };
+    let report = block_on(repo.pull_with(mode))??;
+
+    let mut out = fetch::render(&report.fetch);
+    match report.outcome {
+        PullOutcome::UpToDate => out.push_str("Already up to date.\n"),
+        PullOutcome::FastForward { from, to } => out.push_str(&format!(
+            "Updating {}..{}\nFast-forward\n",
+            from.map(|id| id.to_hex()).unwrap_or_default(),
+            to.to_hex()
+        )),
+        PullOutcome::Merged(id) => out.push_str(&format!(
+            "Merge made by {}: [{}]\n",
+            report.upstream,
+            id.to_hex()
+        )),
+        PullOutcome::Rebased(_) => {
+            out.push_str(&format!("Successfully rebased onto {}.\n", report.upstream))
+        }
+        PullOutcome::Conflicts(paths) => {
+            for path in paths {
+                out.push_str(&format!("CONFLICT (content): Merge conflict in {}\n", path));
+            }
+            let command = match report.mode {
+                PullMode::Rebase => "rebase",
+                _ => "merge",
+            };
+            out.push_str(&format!(
+                "Fix the conflicts then run `{0} --continue`, or `{0} --abort`\n",
+                command
+            ));
+            return Err(Error::Stopped(out));
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,79 @@
+use fursion::{
+    commit::CommitId,
+    push::{PushOptions, RefUpdateStatus},
+};
+
+use crate::{
+    args::Args,
+    command::{block_on, open_repo, remote_arg},
+    error::Error,
+};
+
+/// `push [-f] [--tags] [-d <branch>] [<remote> [<branch>...]]`, the remote being a name or
+/// a url, the checked out branch to the first remote by default
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &["-d", "--delete"]);
+    let repo = open_repo()?;
+    let remote = remote_arg(&repo, args.get(0))?;
+
+    let options = PushOptions {
+        branches: args.positional().iter().skip(1).cloned().collect(),
+        delete: args
+            .value(&["-d", "--delete"])
+            .map(str::to_owned)
+            .into_iter()
+            .collect(),
+        tags: args.flag(&["--tags"]),
+        force: args.flag(&["-f", "--force"]),
+    };
+    let report = block_on(repo.push(&remote, &options))??;
+
+    let mut out = format!("To {}\n", remote.push_url());
+    for update in &report.updates {
+        let line = match update.status {
+            RefUpdateStatus::Updated if update.new.is_none() => {
+                format!(" - [deleted]         {}", update.name)
+            }
+            RefUpdateStatus::Updated if update.old.is_none() => {
+                format!(" * [new]             {}", update.name)
+            }
+            RefUpdateStatus::Updated => format!(
+                "   {}..{}   {}",
+                hex(update.old),
+                hex(update.new),
+                update.name
+            ),
+            RefUpdateStatus::ForcedUpdate => format!(
+                " + {}...{}  {} (forced update)",
+                hex(update.old),
+                hex(update.new),
+                update.name
+            ),
+            RefUpdateStatus::UpToDate => format!(" = [up to date]      {}", update.name),
+            status => format!(" ! [rejected]        {} ({})", update.name, reason(status)),
+        };
+        out.push_str(&line);
+        out.push('\n');
+    }
+    if report.is_rejected() {
+        return Err(Error::Rejected(out));
+    }
+    Ok(out)
+}
+
+fn hex(id: Option<CommitId>) -> String {
+    id.map_or("(none)".to_owned(), |id| id.to_hex())
+}
+
+fn reason(status: RefUpdateStatus) -> &'static str {
+    match status {
+        RefUpdateStatus::RejectedNonFastForward => "non-fast-forward",
+        RefUpdateStatus::RejectedStale => "stale info",
+        RefUpdateStatus::RejectedAlreadyExists => "already exists",
+        RefUpdateStatus::RejectedDirtyCheckout => "branch is checked out with changes",
+        RefUpdateStatus::RejectedCurrentBranch => "branch is checked out",
+        RefUpdateStatus::RejectedProtected => "protected branch",
+        RefUpdateStatus::RejectedInvalidName => "invalid name",
+        _ => "",
+    }
+}
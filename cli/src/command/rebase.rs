@@ -0,0 +1,91 @@
+use std::{env, fs, process};
+
+use fursion::{
+    rebase::{RebaseOptions, RebaseStatus, TodoItem},
+    repo::{Repo, FURSION_DIR},
+};
+
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// Name of the file the todo plan is written to for the editor
+const TODO_EDIT_FILE_NAME: &str = "REBASE_TODO";
+
+const TODO_HELP: &str = "\n\
+# Commands:
+# p, pick <commit> = use commit
+# r, reword <commit> <message> = use commit, with the message written after it
+# e, edit <commit> = use commit, but stop for amending
+# s, squash <commit> = use commit, but meld into previous commit
+# f, fixup <commit> = like \"squash\", but discard this commit's message
+# d, drop <commit> = remove commit
+#
+# Lines are replayed from top to bottom, removing a line drops its commit.
+";
+
+/// `rebase [--onto <revision>] <upstream>` replays the commits of HEAD on top of another commit,
+/// `-i` edits the todo plan with `$FURSION_EDITOR` or `$EDITOR` first, `--todo <file>` reads it
+/// from a file, `--continue`, `--skip` and `--abort` handle a stopped rebase
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &["--onto", "--todo"]);
+    let mut repo = open_repo()?;
+
+    let status = if args.flag(&["--abort"]) {
+        repo.rebase_abort()?;
+        return Ok(String::new());
+    } else if args.flag(&["--continue"]) {
+        repo.rebase_continue()?
+    } else if args.flag(&["--skip"]) {
+        repo.rebase_skip()?
+    } else {
+        let upstream = repo.resolve(args.required(0, "upstream")?)?;
+        let onto = args
+            .value(&["--onto"])
+            .map(|rev| repo.resolve(rev))
+            .transpose()?;
+        let todo = if let Some(path) = args.value(&["--todo"]) {
+            Some(TodoItem::list_from_str(&fs::read_to_string(path)?)?)
+        } else if args.flag(&["-i", "--interactive"]) {
+            Some(edit_todo(&repo, &repo.rebase_todo(upstream)?)?)
+        } else {
+            None
+        };
+        repo.rebase(upstream, &RebaseOptions { onto, todo })?
+    };
+
+    Ok(match status {
+        RebaseStatus::Finished(_) => match repo.refs.current_branch() {
+            Some(branch) => format!("Successfully rebased and updated {}.\n", branch),
+            None => "Successfully rebased.\n".to_owned(),
+        },
+        RebaseStatus::StoppedAtEdit(id) => format!(
+            "Stopped at {}, change it then run `rebase --continue`\n",
+            id.to_hex()
+        ),
+    })
+}
+
+/// Lets the user change the todo plan in their editor
+fn edit_todo(repo: &Repo, todo: &[TodoItem]) -> Result<Vec<TodoItem>, Error> {
+    let editor = env::var("FURSION_EDITOR")
+        .or_else(|_| env::var("EDITOR"))
+        .map_err(|_| Error::MissingArgument("editor"))?;
+    let path = repo.path.join(FURSION_DIR).join(TODO_EDIT_FILE_NAME);
+    fs::write(
+        &path,
+        format!("{}{}", TodoItem::list_to_string(todo), TODO_HELP),
+    )?;
+
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().ok_or(Error::MissingArgument("editor"))?;
+    let status = process::Command::new(program)
+        .args(parts)
+        .arg(&path)
+        .status()?;
+    if !status.success() {
+        return Err(Error::Io(format!("editor exited with {}", status)));
+    }
+
+    let edited = fs::read_to_string(&path)?;
+    fs::remove_file(&path)?;
+    Ok(TodoItem::list_from_str(&edited)?)
+}
@@ -0,0 +1,18 @@
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `reflog [<ref>]` prints the movements of a ref, HEAD by default, newest first
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let repo = open_repo()?;
+    let name = args
+        .positional()
+        .first()
+        .map_or("HEAD", |name| name.as_str());
+
+    let mut out = String::new();
+    for (i, entry) in repo.reflog(name)?.iter().enumerate() {
+        let id = entry.new.map_or("-".to_owned(), |id| id.to_hex());
+        out.push_str(&format!("{} {}@{{{}}}: {}\n", id, name, i, entry.message));
+    }
+    Ok(out)
+}
@@ -0,0 +1,79 @@
+use fursion::remote::Refspec;
+
+use crate::{
+    args::Args,
+    command::{open_repo, url_arg},
+    error::Error,
+};
+
+/// `remote [-v]` lists the remotes, `remote add <name> <url>`, `remote remove <name>`,
+/// `remote rename <old> <new>`, `remote set-url [--push] <name> <url>` and
+/// `remote set-refspecs <name> [<refspec>...]` change them
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let mut repo = open_repo()?;
+
+    match args.get(0) {
+        None => {
+            let verbose = args.flag(&["-v", "--verbose"]);
+            Ok(repo
+                .remotes
+                .iter()
+                .map(|remote| match verbose {
+                    true => format!(
+                        "{}\t{} (fetch)\n{}\t{} (push)\n",
+                        remote.name,
+                        remote.url,
+                        remote.name,
+                        remote.push_url()
+                    ),
+                    false => format!("{}\n", remote.name),
+                })
+                .collect())
+        }
+        Some("add") => {
+            let url = url_arg(args.required(2, "url")?)?;
+            repo.add_remote(args.required(1, "name")?, &url)?;
+            Ok(String::new())
+        }
+        Some("remove" | "rm") => {
+            repo.remove_remote(args.required(1, "name")?)?;
+            Ok(String::new())
+        }
+        Some("rename") => {
+            repo.rename_remote(args.required(1, "old")?, args.required(2, "new")?)?;
+            Ok(String::new())
+        }
+        Some("set-url") => {
+            let push = args.flag(&["--push"]);
+            let url = url_arg(args.required(2, "url")?)?;
+            repo.set_remote_url(args.required(1, "name")?, &url, push)?;
+            Ok(String::new())
+        }
+        Some("set-refspecs") => {
+            let name = args.required(1, "name")?;
+            let refspecs = args
+                .positional()
+                .iter()
+                .skip(2)
+                .map(|spec| spec.parse::<Refspec>())
+                .collect::<Result<Vec<_>, _>>()?;
+            repo.set_remote_refspecs(name, refspecs)?;
+            Ok(String::new())
+        }
+        Some("show") => {
+            let remote = repo.remote(args.required(1, "name")?)?;
+            let mut out = format!(
+                "* remote {}\n  Fetch URL: {}\n  Push  URL: {}\n",
+                remote.name,
+                remote.url,
+                remote.push_url()
+            );
+            for spec in remote.refspecs() {
+                out.push_str(&format!("  Refspec: {}\n", spec));
+            }
+            Ok(out)
+        }
+        Some(other) => Err(Error::InvalidArgument(other.to_owned())),
+    }
+}
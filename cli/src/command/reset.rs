@@ -0,0 +1,22 @@
+use fursion::reset::ResetMode;
+
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `reset [--soft | --mixed | --hard] [<revision>]` moves HEAD to a commit, HEAD by default
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let mut repo = open_repo()?;
+
+    let mode = if args.flag(&["--soft"]) {
+        ResetMode::Soft
+    } else if args.flag(&["--hard"]) {
+        ResetMode::Hard
+    } else {
+        ResetMode::Mixed
+    };
+    let target = repo.resolve(args.get(0).unwrap_or("HEAD"))?;
+    repo.reset(target, mode)?;
+
+    let summary = repo.history.find(&target)?.summary();
+    Ok(format!("HEAD is now at {} {}\n", target.to_hex(), summary))
+}
@@ -0,0 +1,24 @@
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `rev-parse <revision>...` prints the commit of each revision,
+/// ranges print the included commits then the excluded ones prefixed by `^`
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let repo = open_repo()?;
+
+    let mut out = String::new();
+    for rev in args.positional() {
+        if rev.contains("..") || rev.starts_with('^') {
+            let set = repo.resolve_set(&[rev])?;
+            for id in set.include {
+                out.push_str(&format!("{}\n", id.to_hex()));
+            }
+            for id in set.exclude {
+                out.push_str(&format!("^{}\n", id.to_hex()));
+            }
+        } else {
+            out.push_str(&format!("{}\n", repo.resolve(rev)?.to_hex()));
+        }
+    }
+    Ok(out)
+}
@@ -0,0 +1,22 @@
+use std::path::PathBuf;
+
+use fursion::server::Server;
+
+use crate::{args::Args, command::block_on, error::Error};
+
+/// Hosts the repos found under the root dir given by `--root`, the current dir by default,
+/// laid out as `<author>/<repo>`, to the users managed by the `user` command, on the
+/// `<host>:<port>` given by `--address`, `127.0.0.1:54510` by default
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &["--root", "--address"]);
+    let root = match args.value(&["--root"]) {
+        Some(root) => PathBuf::from(root),
+        None => std::env::current_dir()?,
+    };
+    let address = args
+        .value(&["--address"])
+        .unwrap_or(Server::DEFAULT_ADDRESS);
+    let server = Server::new(&root)?;
+    block_on(server.run(address))??;
+    Ok(String::new())
+}
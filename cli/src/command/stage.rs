@@ -0,0 +1,31 @@
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `add [<path>...]`, every change if no path is given
+pub fn add(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let paths = args
+        .positional()
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+
+    let mut repo = open_repo()?;
+    repo.stage(&paths)?;
+
+    Ok(String::new())
+}
+
+/// `unstage [<path>...]`, every change if no path is given
+pub fn unstage(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &[]);
+    let paths = args
+        .positional()
+        .iter()
+        .map(String::as_str)
+        .collect::<Vec<_>>();
+
+    let mut repo = open_repo()?;
+    repo.unstage(&paths)?;
+
+    Ok(String::new())
+}
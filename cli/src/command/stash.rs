@@ -0,0 +1,66 @@
+use fursion::tree;
+
+use crate::{
+    args::Args,
+    command::{diff, open_repo},
+    error::Error,
+};
+
+/// `stash [push] [-m <message>]` sets the uncommitted work aside, `stash list`,
+/// `stash show [<stash>]`, `stash apply [<stash>]`, `stash pop [<stash>]` and `stash drop [<stash>]`
+/// handle the stashed entries, `<stash>` being `stash@{n}` or `n` and defaulting to the newest
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &["-m", "--message", "-U", "--unified"]);
+    let mut repo = open_repo()?;
+    let index = || args.get(1).map_or(Ok(0), parse_index);
+
+    match args.get(0).unwrap_or("push") {
+        "push" | "save" => {
+            repo.stash_save(args.value(&["-m", "--message"]))?;
+            Ok(format!(
+                "Saved working directory and index state {}\n",
+                repo.stash_get(0)?.message
+            ))
+        }
+        "list" => Ok(repo
+            .stash_list()?
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| format!("stash@{{{}}}: {}\n", i, entry.message))
+            .collect()),
+        "show" => {
+            let entry = repo.stash_get(index()?)?;
+            let base_tree = repo.stash_base_tree(&entry)?;
+            let changes = tree::diff_trees(&base_tree, &entry.working_tree(&base_tree));
+            Ok(diff::render_changes(
+                &base_tree,
+                &changes,
+                &diff::diff_options(&args)?,
+            ))
+        }
+        "apply" => {
+            repo.stash_apply(index()?)?;
+            Ok(String::new())
+        }
+        "pop" => {
+            let index = index()?;
+            repo.stash_pop(index)?;
+            Ok(format!("Dropped stash@{{{}}}\n", index))
+        }
+        "drop" => {
+            let index = index()?;
+            repo.stash_drop(index)?;
+            Ok(format!("Dropped stash@{{{}}}\n", index))
+        }
+        other => Err(Error::InvalidArgument(other.to_owned())),
+    }
+}
+
+/// Parses `stash@{n}` or `n`
+fn parse_index(s: &str) -> Result<usize, Error> {
+    s.strip_prefix("stash@{")
+        .and_then(|s| s.strip_suffix('}'))
+        .unwrap_or(s)
+        .parse()
+        .map_err(|_| Error::InvalidArgument(s.to_owned()))
+}
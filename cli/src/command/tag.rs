@@ -0,0 +1,56 @@
+use fursion::{date, tag::Tag};
+
+use crate::{args::Args, command::open_repo, error::Error};
+
+/// `tag [-n]` lists tags, `tag [-a] [-m <message>] [-f] <name> [<revision>]` creates one,
+/// `tag -d <name>` deletes one and `tag --show <name>` shows one
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &["-m", "--message", "-d", "--delete", "--show"]);
+    let mut repo = open_repo()?;
+
+    if let Some(name) = args.value(&["-d", "--delete"]) {
+        let tag = repo.delete_tag(name)?;
+        return Ok(format!(
+            "Deleted tag '{}' (was {})\n",
+            name,
+            tag.target().to_hex()
+        ));
+    }
+
+    if let Some(name) = args.value(&["--show"]) {
+        return Ok(match repo.tag(name)? {
+            Tag::Lightweight(id) => format!("tag {}\ncommit {}\n", name, id.to_hex()),
+            Tag::Annotated(tag) => format!(
+                "tag {}\nTagger: {}\nDate:   {}\n\n{}\n\ncommit {}\n",
+                name,
+                tag.tagger,
+                date::format_timestamp(tag.timestamp),
+                tag.message,
+                tag.target.to_hex()
+            ),
+        });
+    }
+
+    let Some(name) = args.get(0) else {
+        let show_message = args.flag(&["-n"]);
+        return Ok(repo
+            .list_tags()
+            .map(|(name, tag)| match tag {
+                Tag::Annotated(tag) if show_message => format!(
+                    "{:<16} {}\n",
+                    name,
+                    tag.message.lines().next().unwrap_or("")
+                ),
+                _ => format!("{}\n", name),
+            })
+            .collect());
+    };
+
+    let target = args.get(1).map(|rev| repo.resolve(rev)).transpose()?;
+    let message = args.value(&["-m", "--message"]);
+    if args.flag(&["-a", "--annotate"]) && message.is_none() {
+        return Err(Error::MissingArgument("message"));
+    }
+    repo.create_tag(name, target, message, args.flag(&["-f", "--force"]))?;
+    Ok(String::new())
+}
@@ -0,0 +1,77 @@
+use std::path::PathBuf;
+
+use fursion::auth::UserStore;
+
+use crate::{args::Args, error::Error};
+
+/// Manages the accounts of a server, in the root dir given by `--root`, the current dir
+/// by default: `user list`, `user add <name> [--password <password>]`, `user remove <name>`,
+/// `user passwd <name> [<password>]`, `user token create <name> <token name>`,
+/// `user token revoke <name> <token name>`, `user team add <team> <name>`,
+/// `user team remove <team> <name>` and `user team list`
+pub fn run(args: &[String]) -> Result<String, Error> {
+    let args = Args::parse(args, &["--root", "--password"]);
+    let root = match args.value(&["--root"]) {
+        Some(root) => PathBuf::from(root),
+        None => std::env::current_dir()?,
+    };
+    let mut store = UserStore::read(&root)?;
+
+    let out = match args.required(0, "subcommand")? {
+        "list" => {
+            return Ok(store
+                .users
+                .iter()
+                .map(|(name, user)| format!("{} ({} tokens)\n", name, user.tokens.len()))
+                .collect())
+        }
+        "add" => {
+            store.add_user(args.required(1, "name")?, args.value(&["--password"]))?;
+            String::new()
+        }
+        "remove" | "rm" => {
+            store.remove_user(args.required(1, "name")?)?;
+            String::new()
+        }
+        "passwd" => {
+            store.set_password(args.required(1, "name")?, args.get(2))?;
+            String::new()
+        }
+        "token" => {
+            let user = args.required(2, "name")?;
+            let token_name = args.required(3, "token name")?;
+            match args.required(1, "token subcommand")? {
+                "create" => format!("{}\n", store.create_token(user, token_name)?),
+                "revoke" => {
+                    store.revoke_token(user, token_name)?;
+                    String::new()
+                }
+                other => return Err(Error::InvalidArgument(other.to_owned())),
+            }
+        }
+        "team" => match args.required(1, "team subcommand")? {
+            "list" => {
+                return Ok(store
+                    .teams
+                    .iter()
+                    .map(|(team, members)| {
+                        let members = members.iter().cloned().collect::<Vec<_>>();
+                        format!("{}: {}\n", team, members.join(" "))
+                    })
+                    .collect())
+            }
+            "add" => {
+                store.add_team_member(args.required(2, "team")?, args.required(3, "name")?)?;
+                String::new()
+            }
+            "remove" | "rm" => {
+                store.remove_team_member(args.required(2, "team")?, args.required(3, "name")?)?;
+                String::new()
+            }
+            other => return Err(Error::InvalidArgument(other.to_owned())),
+        },
+        other => return Err(Error::InvalidArgument(other.to_owned())),
+    };
+    store.save(&root)?;
+    Ok(out)
+}
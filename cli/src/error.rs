@@ -1,4 +1,42 @@
+use std::fmt::Display;
+
 #[derive(Debug)]
 pub enum Error {
     CommandNotFound(String),
+    MissingCommand,
+    MissingArgument(&'static str),
+    InvalidArgument(String),
+    Io(String),
+    /// Output of a push of which some refs were rejected
+    Rejected(String),
+    /// Output of a command which stopped on conflicts
+    Stopped(String),
+    Fursion(fursion::error::Error),
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::CommandNotFound(name) => write!(f, "unknown command `{}`", name),
+            Error::MissingCommand => write!(f, "no command given"),
+            Error::MissingArgument(name) => write!(f, "missing argument <{}>", name),
+            Error::InvalidArgument(arg) => write!(f, "invalid argument `{}`", arg),
+            Error::Io(e) => write!(f, "{}", e),
+            Error::Rejected(out) => write!(f, "failed to push some refs\n{}", out),
+            Error::Stopped(out) => write!(f, "{}", out.trim_end()),
+            Error::Fursion(e) => write!(f, "{:?}", e),
+        }
+    }
+}
+
+impl From<fursion::error::Error> for Error {
+    fn from(value: fursion::error::Error) -> Self {
+        Error::Fursion(value)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(value: std::io::Error) -> Self {
+        Error::Io(value.to_string())
+    }
 }
@@ -1,11 +1,21 @@
+mod args;
 mod command;
 mod error;
 
 fn main() {
     let args: Vec<String> = std::env::args().collect();
 
-    let command = command::Command::from_str(&args[1]).unwrap();
-    let res = (command.function)(&args[1..]).unwrap();
+    let res = args
+        .get(1)
+        .ok_or(error::Error::MissingCommand)
+        .and_then(|name| command::Command::from_str(name))
+        .and_then(|command| (command.function)(&args[2..]));
 
-    println!("{:?}", res);
+    match res {
+        Ok(output) => print!("{}", output),
+        Err(e) => {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,152 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    auth::UserStore,
+    error::Error,
+    repo::{self, Repo, FURSION_DIR},
+};
+
+/// What a user may do with a hosted repo, each level allowing the ones before it
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Permission {
+    /// Fetch and clone
+    Read,
+    /// Push
+    Write,
+    /// Push to the protected branches which only admins may update
+    Admin,
+}
+
+impl Permission {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Permission::Read => "read",
+            Permission::Write => "write",
+            Permission::Admin => "admin",
+        }
+    }
+
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s {
+            "read" => Some(Permission::Read),
+            "write" => Some(Permission::Write),
+            "admin" => Some(Permission::Admin),
+            _ => None,
+        }
+    }
+}
+
+/// Who may read a hosted repo without being granted it
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Visibility {
+    /// Anyone, even without credentials
+    Public,
+    /// Only the owner and the users and teams granted a permission
+    #[default]
+    Private,
+}
+
+/// Rules restricting the updates of the branches matching a pattern, the pattern being a
+/// branch name or a prefix followed by `*`, like `release/*`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct ProtectedBranch {
+    pub pattern: String,
+    /// Whether the branch may be forced over commits or deleted
+    #[serde(default)]
+    pub allow_force_push: bool,
+    /// Whether users with [`Permission::Write`] may push to it, only admins may otherwise
+    #[serde(default)]
+    pub allow_direct_push: bool,
+}
+
+impl ProtectedBranch {
+    pub fn matches(&self, branch: &str) -> bool {
+        match self.pattern.strip_suffix('*') {
+            Some(prefix) => branch.starts_with(prefix),
+            None => branch == self.pattern,
+        }
+    }
+}
+
+/// Who may do what with a hosted repo, saved in its `.fursion` dir, the author of the
+/// repo being its admin
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct AccessControl {
+    #[serde(default)]
+    pub visibility: Visibility,
+    #[serde(default)]
+    pub users: BTreeMap<String, Permission>,
+    /// Permissions of the members of the teams of the [`UserStore`]
+    #[serde(default)]
+    pub teams: BTreeMap<String, Permission>,
+    #[serde(default)]
+    pub protected_branches: Vec<ProtectedBranch>,
+}
+
+impl AccessControl {
+    pub const FILE_NAME: &'static str = "access";
+
+    pub fn read(fursion_dir_path: &Path) -> Result<Self, Error> {
+        repo::read_json_or_default(&fursion_dir_path.join(Self::FILE_NAME))
+    }
+
+    pub fn save(&self, fursion_dir_path: &Path) -> Result<(), Error> {
+        repo::save_json(&fursion_dir_path.join(Self::FILE_NAME), self)
+    }
+
+    /// The highest permission of a user, [`None`] for anonymous requests, on a repo
+    /// owned by `owner`, [`None`] if they can't even read it
+    pub fn permission(
+        &self,
+        user: Option<&str>,
+        owner: &str,
+        users: &UserStore,
+    ) -> Option<Permission> {
+        let public = (self.visibility == Visibility::Public).then_some(Permission::Read);
+        let Some(user) = user else {
+            return public;
+        };
+        if user == owner {
+            return Some(Permission::Admin);
+        }
+        let teams = users
+            .teams_of(user)
+            .filter_map(|team| self.teams.get(team).copied());
+        self.users
+            .get(user)
+            .copied()
+            .into_iter()
+            .chain(teams)
+            .chain(public)
+            .max()
+    }
+
+    /// Whether a user with `permission` may update a branch, `rewrites` telling whether
+    /// the update drops commits from it, as forced updates and deletions do
+    pub fn allows_branch_update(
+        &self,
+        branch: &str,
+        rewrites: bool,
+        permission: Permission,
+    ) -> bool {
+        self.protected_branches
+            .iter()
+            .filter(|rule| rule.matches(branch))
+            .all(|rule| {
+                (rule.allow_force_push || !rewrites)
+                    && (rule.allow_direct_push || permission == Permission::Admin)
+            })
+    }
+}
+
+impl Repo {
+    pub fn access_control(&self) -> Result<AccessControl, Error> {
+        AccessControl::read(&self.path.join(FURSION_DIR))
+    }
+
+    pub fn save_access_control(&self, access: &AccessControl) -> Result<(), Error> {
+        access.save(&self.path.join(FURSION_DIR))
+    }
+}
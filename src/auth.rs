@@ -0,0 +1,209 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::Path,
+};
+
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::{
+    commit,
+    credential::Credentials,
+    error::{AuthErrorReason, Error},
+    repo,
+};
+
+/// An API token of a user, only its hash is kept
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct ApiToken {
+    pub name: String,
+    /// Hex of the SHA-256 of the token
+    pub hash: String,
+    /// Seconds since the unix epoch at which the token was made
+    pub created: u64,
+}
+
+/// An account of the server
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct User {
+    /// Argon2 hash of the password in PHC format, [`None`] if the user only uses tokens
+    #[serde(default)]
+    pub password_hash: Option<String>,
+    #[serde(default)]
+    pub tokens: Vec<ApiToken>,
+}
+
+/// The accounts of a server, saved as JSON in its root dir
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct UserStore {
+    pub users: BTreeMap<String, User>,
+    /// Members of each team, see [`AccessControl`](crate::access::AccessControl)
+    #[serde(default)]
+    pub teams: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl UserStore {
+    pub const FILE_NAME: &'static str = "users.json";
+
+    pub fn read(root: &Path) -> Result<Self, Error> {
+        repo::read_json_or_default(&root.join(Self::FILE_NAME))
+    }
+
+    pub fn save(&self, root: &Path) -> Result<(), Error> {
+        repo::save_json(&root.join(Self::FILE_NAME), self)
+    }
+
+    pub fn add_user(&mut self, name: &str, password: Option<&str>) -> Result<(), Error> {
+        if self.users.contains_key(name) {
+            return Err(Error::AuthError(AuthErrorReason::UserAlreadyExists(
+                name.to_owned(),
+            )));
+        }
+        let password_hash = password.map(hash_password).transpose()?;
+        self.users.insert(
+            name.to_owned(),
+            User {
+                password_hash,
+                tokens: Vec::new(),
+            },
+        );
+        Ok(())
+    }
+
+    /// Removes a user from the store and from their teams
+    pub fn remove_user(&mut self, name: &str) -> Result<User, Error> {
+        let user = self
+            .users
+            .remove(name)
+            .ok_or_else(|| Error::AuthError(AuthErrorReason::UserNotFound(name.to_owned())))?;
+        for members in self.teams.values_mut() {
+            members.remove(name);
+        }
+        self.teams.retain(|_, members| !members.is_empty());
+        Ok(user)
+    }
+
+    /// Adds a user to a team, the team being made if it doesn't exist
+    pub fn add_team_member(&mut self, team: &str, user: &str) -> Result<(), Error> {
+        self.user_mut(user)?;
+        self.teams
+            .entry(team.to_owned())
+            .or_default()
+            .insert(user.to_owned());
+        Ok(())
+    }
+
+    /// Removes a user from a team, the team being removed once empty
+    pub fn remove_team_member(&mut self, team: &str, user: &str) -> Result<(), Error> {
+        let members = self
+            .teams
+            .get_mut(team)
+            .ok_or_else(|| Error::AuthError(AuthErrorReason::TeamNotFound(team.to_owned())))?;
+        if !members.remove(user) {
+            return Err(Error::AuthError(AuthErrorReason::UserNotFound(
+                user.to_owned(),
+            )));
+        }
+        self.teams.retain(|_, members| !members.is_empty());
+        Ok(())
+    }
+
+    /// Names of the teams a user is a member of
+    pub fn teams_of<'a>(&'a self, user: &'a str) -> impl Iterator<Item = &'a String> {
+        self.teams
+            .iter()
+            .filter(move |(_, members)| members.contains(user))
+            .map(|(team, _)| team)
+    }
+
+    /// Changes the password of a user, [`None`] disables password logins
+    pub fn set_password(&mut self, name: &str, password: Option<&str>) -> Result<(), Error> {
+        let password_hash = password.map(hash_password).transpose()?;
+        self.user_mut(name)?.password_hash = password_hash;
+        Ok(())
+    }
+
+    /// Makes a new API token for a user and gives it back, it can't be read again later
+    pub fn create_token(&mut self, user: &str, token_name: &str) -> Result<String, Error> {
+        let user = self.user_mut(user)?;
+        if user.tokens.iter().any(|token| token.name == token_name) {
+            return Err(Error::AuthError(AuthErrorReason::TokenAlreadyExists(
+                token_name.to_owned(),
+            )));
+        }
+        let token = format!("fur_{}", hex(&rand::random::<[u8; 24]>()));
+        user.tokens.push(ApiToken {
+            name: token_name.to_owned(),
+            hash: hash_token(&token),
+            created: commit::now(),
+        });
+        Ok(token)
+    }
+
+    pub fn revoke_token(&mut self, user: &str, token_name: &str) -> Result<(), Error> {
+        let user = self.user_mut(user)?;
+        let len = user.tokens.len();
+        user.tokens.retain(|token| token.name != token_name);
+        if user.tokens.len() == len {
+            return Err(Error::AuthError(AuthErrorReason::TokenNotFound(
+                token_name.to_owned(),
+            )));
+        }
+        Ok(())
+    }
+
+    /// The name of the user the credentials belong to
+    pub fn authenticate(&self, credentials: &Credentials) -> Result<String, Error> {
+        let invalid = || Error::AuthError(AuthErrorReason::InvalidCredentials);
+        match credentials {
+            Credentials::Token(token) => {
+                let hash = hash_token(token);
+                self.users
+                    .iter()
+                    .find(|(_, user)| user.tokens.iter().any(|token| token.hash == hash))
+                    .map(|(name, _)| name.clone())
+                    .ok_or_else(invalid)
+            }
+            Credentials::Basic { user, password } => {
+                let stored = self
+                    .users
+                    .get(user)
+                    .and_then(|user| user.password_hash.as_deref())
+                    .ok_or_else(invalid)?;
+                let hash = PasswordHash::new(stored).map_err(|_| invalid())?;
+                Argon2::default()
+                    .verify_password(password.as_bytes(), &hash)
+                    .map_err(|_| invalid())?;
+                Ok(user.clone())
+            }
+        }
+    }
+
+    fn user_mut(&mut self, name: &str) -> Result<&mut User, Error> {
+        self.users
+            .get_mut(name)
+            .ok_or_else(|| Error::AuthError(AuthErrorReason::UserNotFound(name.to_owned())))
+    }
+}
+
+fn hash_password(password: &str) -> Result<String, Error> {
+    let salt = SaltString::encode_b64(&rand::random::<[u8; 16]>())
+        .map_err(|e| Error::Unknown(e.to_string()))?;
+    Ok(Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| Error::Unknown(e.to_string()))?
+        .to_string())
+}
+
+/// Tokens are random enough for a plain hash
+fn hash_token(token: &str) -> String {
+    hex(&Sha256::digest(token.as_bytes()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
@@ -0,0 +1,241 @@
+use std::{collections::HashSet, fs, path::Path, process};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commit::CommitId,
+    error::{BisectErrorReason, Error},
+    refs::Head,
+    repo::{Repo, FURSION_DIR},
+};
+
+/// Exit status of a bisect run command meaning the commit can't be tested
+pub const SKIP_EXIT_CODE: i32 = 125;
+
+/// An answer about a commit
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum BisectMark {
+    Good,
+    Bad,
+    Skip,
+}
+
+/// Where a bisect session is at
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BisectStatus {
+    /// A bad commit is needed to start
+    NeedBad,
+    /// A good commit is needed to start
+    NeedGood,
+    /// The commit checked out to be tested, along with the number of commits which
+    /// may still be the first bad one
+    Testing { commit: CommitId, remaining: usize },
+    /// The first bad commit
+    Found(CommitId),
+    /// Only skipped commits are left, the first bad commit is one of them
+    OnlySkipped(Vec<CommitId>),
+}
+
+/// A bisect session, saved in the `.fursion` dir so it spans several runs
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct BisectState {
+    /// What was checked out before the session started
+    pub orig_head: Head,
+    pub bad: Option<CommitId>,
+    pub good: Vec<CommitId>,
+    pub skipped: Vec<CommitId>,
+}
+
+impl BisectState {
+    pub const FILE_NAME: &'static str = "bisect";
+
+    pub fn read(fursion_dir_path: &Path) -> Result<Option<Self>, Error> {
+        let path = fursion_dir_path.join(Self::FILE_NAME);
+        if !Path::exists(&path) {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+    }
+
+    pub fn save(&self, fursion_dir_path: &Path) -> Result<(), Error> {
+        fs::write(
+            fursion_dir_path.join(Self::FILE_NAME),
+            serde_json::to_string(self)?,
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(fursion_dir_path: &Path) -> Result<(), Error> {
+        let path = fursion_dir_path.join(Self::FILE_NAME);
+        if Path::exists(&path) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Repo {
+    /// Starts a bisect session, optionally with a bad commit and some good ones
+    pub fn bisect_start(
+        &mut self,
+        bad: Option<CommitId>,
+        good: &[CommitId],
+    ) -> Result<BisectStatus, Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        if BisectState::read(&fursion_dir)?.is_some() {
+            return Err(Error::InProgress("bisect".to_owned()));
+        }
+        if self.has_uncommitted_changes()? {
+            return Err(Error::UncommittedChanges);
+        }
+        for id in bad.iter().chain(good) {
+            self.history.find(id)?;
+        }
+
+        let state = BisectState {
+            orig_head: self.refs.head.clone(),
+            bad,
+            good: good.to_vec(),
+            skipped: Vec::new(),
+        };
+        self.check_bisect_range(&state)?;
+        state.save(&fursion_dir)?;
+        self.bisect_next(&state)
+    }
+
+    /// Records an answer about a commit, HEAD if [`None`], and checks out the next one to test
+    pub fn bisect_mark(
+        &mut self,
+        mark: BisectMark,
+        id: Option<CommitId>,
+    ) -> Result<BisectStatus, Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        let mut state = BisectState::read(&fursion_dir)?.ok_or(Error::NothingInProgress)?;
+        let id = match id {
+            Some(id) => self.history.find(&id)?.id,
+            None => self.head_or_err()?,
+        };
+
+        match mark {
+            BisectMark::Good => state.good.push(id),
+            BisectMark::Bad => state.bad = Some(id),
+            BisectMark::Skip => state.skipped.push(id),
+        }
+        self.check_bisect_range(&state)?;
+        state.save(&fursion_dir)?;
+        self.bisect_next(&state)
+    }
+
+    /// The bisect session in progress, if any
+    pub fn bisect_state(&self) -> Result<Option<BisectState>, Error> {
+        BisectState::read(&self.path.join(FURSION_DIR))
+    }
+
+    /// Ends the bisect session and checks out what was checked out before it
+    pub fn bisect_reset(&mut self) -> Result<(), Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        let state = BisectState::read(&fursion_dir)?.ok_or(Error::NothingInProgress)?;
+        BisectState::delete(&fursion_dir)?;
+        let target = match &state.orig_head {
+            Head::Branch(name) => name.clone(),
+            Head::Detached(id) => id.to_hex(),
+        };
+        self.checkout(&target)
+    }
+
+    /// Answers automatically by running a command on every commit to test: an exit status
+    /// of 0 means good, [`SKIP_EXIT_CODE`] means skip, and any other one below 128 means bad
+    pub fn bisect_run(&mut self, program: &str, args: &[String]) -> Result<BisectStatus, Error> {
+        let state = self.bisect_state()?.ok_or(Error::NothingInProgress)?;
+        let mut status = self.bisect_next(&state)?;
+        while let BisectStatus::Testing { .. } = status {
+            let exit = process::Command::new(program)
+                .args(args)
+                .current_dir(&self.path)
+                .status()
+                .map_err(|e| Error::BisectError(BisectErrorReason::CommandFailed(e.to_string())))?;
+            let mark = match exit.code() {
+                Some(0) => BisectMark::Good,
+                Some(SKIP_EXIT_CODE) => BisectMark::Skip,
+                Some(code) if (1..128).contains(&code) => BisectMark::Bad,
+                _ => {
+                    return Err(Error::BisectError(BisectErrorReason::CommandFailed(
+                        exit.to_string(),
+                    )))
+                }
+            };
+            status = self.bisect_mark(mark, None)?;
+        }
+        Ok(status)
+    }
+
+    /// Fails if the bad commit is reachable from a good one, the answers then contradict
+    /// each other
+    fn check_bisect_range(&self, state: &BisectState) -> Result<(), Error> {
+        match state.bad {
+            Some(bad) if self.history.ancestors_of_all(&state.good).contains(&bad) => Err(
+                Error::BisectError(BisectErrorReason::BadIsAncestorOfGood(bad.to_hex())),
+            ),
+            _ => Ok(()),
+        }
+    }
+
+    /// Picks the commit splitting the remaining candidates in the most even halves and checks it out
+    fn bisect_next(&mut self, state: &BisectState) -> Result<BisectStatus, Error> {
+        let Some(bad) = state.bad else {
+            return Ok(BisectStatus::NeedBad);
+        };
+        if state.good.is_empty() {
+            return Ok(BisectStatus::NeedGood);
+        }
+
+        // The commits which may be the first bad one
+        let good_ancestors = self.history.ancestors_of_all(&state.good);
+        let candidates: HashSet<CommitId> = self
+            .history
+            .ancestors(&bad)
+            .into_iter()
+            .filter(|id| !good_ancestors.contains(id))
+            .collect();
+        let skipped: HashSet<&CommitId> = state.skipped.iter().collect();
+        let mut testable = candidates
+            .iter()
+            .filter(|id| **id != bad && !skipped.contains(id))
+            .copied()
+            .collect::<Vec<_>>();
+        testable.sort();
+
+        if testable.is_empty() {
+            let mut left = candidates
+                .into_iter()
+                .filter(|id| *id == bad || skipped.contains(id))
+                .collect::<Vec<_>>();
+            left.sort();
+            return Ok(match left.as_slice() {
+                [id] => BisectStatus::Found(*id),
+                _ => BisectStatus::OnlySkipped(left),
+            });
+        }
+
+        let total = candidates.len();
+        let commit = testable
+            .into_iter()
+            .max_by_key(|id| {
+                let below = self
+                    .history
+                    .ancestors(id)
+                    .iter()
+                    .filter(|id| candidates.contains(id))
+                    .count();
+                below.min(total - below)
+            })
+            .unwrap_or(bad);
+        if self.head() != Some(commit) {
+            self.checkout(&commit.to_hex())?;
+        }
+        Ok(BisectStatus::Testing {
+            commit,
+            remaining: total,
+        })
+    }
+}
@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+
+use crate::{
+    commit::{Commit, CommitId, FileChanges},
+    diff::{self, DiffOp},
+    error::{Error, RefErrorReason},
+    repo::{Repo, RepoHistory},
+    tree::Tree,
+};
+
+/// Minimum share of common lines for a removed file and an added one to be seen as a rename
+pub const RENAME_SIMILARITY: f64 = 0.5;
+
+/// Options of [`Repo::blame`]
+#[derive(Debug, Clone, Default)]
+pub struct BlameOptions {
+    /// Keeps going through the commits where the file was renamed
+    pub follow_renames: bool,
+}
+
+/// The commit which introduced a line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineOrigin {
+    pub commit: CommitId,
+    pub author: String,
+    pub timestamp: u64,
+    /// Path of the file in the commit which introduced the line
+    pub path: String,
+    /// Line number, starting at 1, in the file of the commit which introduced the line
+    pub line_number: usize,
+}
+
+/// A line of a blamed file
+#[derive(Debug, Clone)]
+pub struct BlameLine {
+    pub origin: LineOrigin,
+    pub content: String,
+}
+
+/// The origin of every line of a file at a commit
+#[derive(Debug, Clone)]
+pub struct Blame {
+    pub path: String,
+    pub commit: CommitId,
+    pub lines: Vec<BlameLine>,
+}
+
+impl Repo {
+    /// Finds the commit which last touched each line of a file at a commit, HEAD if [`None`]
+    pub fn blame(
+        &self,
+        path: &str,
+        at: Option<CommitId>,
+        options: &BlameOptions,
+    ) -> Result<Blame, Error> {
+        let commit = match at {
+            Some(id) => id,
+            None => self.head_or_err()?,
+        };
+        let mut blamer = Blamer {
+            history: &self.history,
+            options,
+            cache: HashMap::new(),
+        };
+
+        let (content, origins) = blamer.blame(commit, path)?.ok_or(Error::RefError(
+            RefErrorReason::PathNotInRevision(path.to_owned(), commit.to_hex()),
+        ))?;
+
+        let lines = diff::split_lines(&content)
+            .into_iter()
+            .zip(origins)
+            .map(|(line, origin)| BlameLine {
+                origin,
+                content: line.trim_end_matches(['\n', '\r']).to_owned(),
+            })
+            .collect();
+
+        Ok(Blame {
+            path: path.to_owned(),
+            commit,
+            lines,
+        })
+    }
+}
+
+/// Content of a file and the origin of each of its lines
+type Blamed = (String, Vec<LineOrigin>);
+
+struct Blamer<'a> {
+    history: &'a RepoHistory,
+    options: &'a BlameOptions,
+    cache: HashMap<(CommitId, String), Option<Blamed>>,
+}
+
+impl Blamer<'_> {
+    /// Blames a file at a commit, [`None`] if the file does not exist there
+    fn blame(&mut self, at: CommitId, path: &str) -> Result<Option<Blamed>, Error> {
+        let key = (at, path.to_owned());
+        if let Some(blamed) = self.cache.get(&key) {
+            return Ok(blamed.clone());
+        }
+
+        // Walks back the first parent chain to find under which name the file
+        // existed after each commit, and where it was created
+        let mut chain: Vec<(&Commit, String)> = Vec::new();
+        let mut name = path.to_owned();
+        let mut next = Some(at);
+        while let Some(id) = next {
+            let commit = self.history.find(&id)?;
+            let parent = commit.parents.first().copied();
+            chain.push((commit, name.clone()));
+
+            let Some(changes) = changes_of(commit, &name) else {
+                next = parent;
+                continue;
+            };
+            if changes.is_removal() {
+                // The file does not exist after this commit
+                chain.clear();
+                break;
+            }
+
+            let parent_tree = match parent {
+                Some(parent) => self.history.tree_at(&parent)?,
+                None => Tree::new(),
+            };
+            if parent_tree.contains_key(&name) {
+                next = parent;
+                continue;
+            }
+            match self.rename_source(commit, &parent_tree, &name)? {
+                Some(source) => {
+                    name = source;
+                    next = parent;
+                }
+                None => break,
+            }
+        }
+
+        // Replays the chain from the commit where the file appeared
+        let mut content = String::new();
+        let mut origins: Vec<LineOrigin> = Vec::new();
+        let mut current_name: Option<String> = None;
+        for (commit, name) in chain.iter().rev() {
+            let renamed = current_name.as_ref().is_some_and(|n| n != name);
+            let changes = changes_of(commit, name);
+            if changes.is_none() && !renamed {
+                continue;
+            }
+
+            let new_content = match changes {
+                Some(changes) if !renamed => changes.apply(&content),
+                _ => self
+                    .history
+                    .tree_at(&commit.id)?
+                    .remove(name)
+                    .unwrap_or_default(),
+            };
+            let mut new_origins = match changes {
+                Some(changes) if !renamed && current_name.is_some() => {
+                    carry_through_changes(&content, &origins, changes, &new_content)
+                }
+                _ => carry_through_diff(&content, &origins, &new_content),
+            };
+
+            for (line_number, origin) in new_origins.iter_mut().enumerate() {
+                if origin.is_none() {
+                    *origin = Some(LineOrigin {
+                        commit: commit.id,
+                        author: commit.author.clone(),
+                        timestamp: commit.timestamp,
+                        path: name.clone(),
+                        line_number: line_number + 1,
+                    });
+                }
+            }
+            let mut new_origins: Vec<LineOrigin> = new_origins.into_iter().flatten().collect();
+
+            // Lines of a merge coming from the other parents are blamed there
+            for parent in commit.parents.iter().skip(1) {
+                let Some((parent_content, parent_origins)) = self.blame(*parent, name)? else {
+                    continue;
+                };
+                let carried = carry_through_diff(&parent_content, &parent_origins, &new_content);
+                for (origin, carried) in new_origins.iter_mut().zip(carried) {
+                    if let Some(carried) = carried.filter(|_| origin.commit == commit.id) {
+                        *origin = carried;
+                    }
+                }
+            }
+
+            content = new_content;
+            origins = new_origins;
+            current_name = Some(name.clone());
+        }
+
+        let blamed = current_name.map(|_| (content, origins));
+        self.cache.insert(key, blamed.clone());
+        Ok(blamed)
+    }
+
+    /// The file removed by a commit which most looks like the file it creates at `path`
+    fn rename_source(
+        &self,
+        commit: &Commit,
+        parent_tree: &Tree,
+        path: &str,
+    ) -> Result<Option<String>, Error> {
+        if !self.options.follow_renames {
+            return Ok(None);
+        }
+        let Some(new_content) = self.history.tree_at(&commit.id)?.remove(path) else {
+            return Ok(None);
+        };
+
+        Ok(commit
+            .changes()
+            .iter()
+            .filter(|changes| changes.is_removal())
+            .filter_map(|changes| {
+                let old_content = parent_tree.get(changes.path())?;
+                let similarity = diff::similarity(old_content, &new_content);
+                (similarity >= RENAME_SIMILARITY).then_some((similarity, changes.path()))
+            })
+            .max_by(|a, b| a.0.total_cmp(&b.0))
+            .map(|(_, source)| source.to_owned()))
+    }
+}
+
+fn changes_of<'a>(commit: &'a Commit, path: &str) -> Option<&'a FileChanges> {
+    commit
+        .changes()
+        .iter()
+        .find(|changes| changes.path() == path)
+}
+
+/// Keeps the origin of the lines of `old` which are left untouched by the change ranges,
+/// the other lines of `new` get [`None`]
+fn carry_through_changes(
+    old: &str,
+    old_origins: &[LineOrigin],
+    changes: &FileChanges,
+    new: &str,
+) -> Vec<Option<LineOrigin>> {
+    // Untouched regions of the old content as (old start, old end, new start)
+    let mut regions = Vec::new();
+    let mut old_position = 0;
+    let mut shift: isize = 0;
+    for change in changes.changes() {
+        let start = change.range.start.min(old.len());
+        let end = change.range.end.min(old.len()).max(start);
+        if start > old_position {
+            regions.push((
+                old_position,
+                start,
+                (old_position as isize + shift) as usize,
+            ));
+        }
+        let inserted = change.text.as_deref().map_or(0, str::len);
+        shift += inserted as isize - (end - start) as isize;
+        old_position = old_position.max(end);
+    }
+    if old.len() > old_position {
+        regions.push((
+            old_position,
+            old.len(),
+            (old_position as isize + shift) as usize,
+        ));
+    }
+
+    let old_lines: HashMap<(usize, usize), usize> = line_spans(old)
+        .into_iter()
+        .enumerate()
+        .map(|(i, span)| (span, i))
+        .collect();
+
+    line_spans(new)
+        .into_iter()
+        .map(|(start, end)| {
+            let &(old_start, _, new_start) =
+                regions.iter().find(|(old_start, old_end, new_start)| {
+                    start >= *new_start && end <= new_start + (old_end - old_start)
+                })?;
+            let span = (start - new_start + old_start, end - new_start + old_start);
+            old_lines.get(&span).map(|i| old_origins[*i].clone())
+        })
+        .collect()
+}
+
+/// Keeps the origin of the lines of `old` which are equal in a line diff with `new`,
+/// the other lines of `new` get [`None`]
+fn carry_through_diff(old: &str, old_origins: &[LineOrigin], new: &str) -> Vec<Option<LineOrigin>> {
+    let old_lines = diff::split_lines(old);
+    let new_lines = diff::split_lines(new);
+    let mut carried = vec![None; new_lines.len()];
+    for op in diff::diff_slices(&old_lines, &new_lines) {
+        if let DiffOp::Equal { old, new, len } = op {
+            for i in 0..len {
+                carried[new + i] = old_origins.get(old + i).cloned();
+            }
+        }
+    }
+    carried
+}
+
+/// Byte span of every line, line ending included
+fn line_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut position = 0;
+    diff::split_lines(text)
+        .into_iter()
+        .map(|line| {
+            let span = (position, position + line.len());
+            position += line.len();
+            span
+        })
+        .collect()
+}
@@ -0,0 +1,187 @@
+use std::{collections::BTreeSet, fs, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commit::CommitId,
+    error::{BundleErrorReason, Error},
+    fetch::FetchRequest,
+    pack::Pack,
+    refs::{Head, Refs},
+    repo::Repo,
+};
+
+/// Refs along with the commits they lead to, in a single file to move history between
+/// repos which can't reach each other
+///
+/// A bundle is a header line, then a JSON line with the prerequisites and the refs,
+/// then a [`Pack`] of the commits
+#[derive(Debug, Clone, Default)]
+pub struct Bundle {
+    /// Commits the bundle doesn't hold and which the receiving repo must already have
+    pub prerequisites: Vec<CommitId>,
+    pub refs: Refs,
+    pub pack: Pack,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+struct BundleHeader {
+    prerequisites: Vec<CommitId>,
+    refs: Refs,
+}
+
+impl Bundle {
+    const HEADER: &'static str = "FURSION BUNDLE 1";
+
+    /// The text of the bundle, as written in bundle files
+    pub fn to_text(&self) -> Result<String, Error> {
+        let header = BundleHeader {
+            prerequisites: self.prerequisites.clone(),
+            refs: self.refs.clone(),
+        };
+        Ok(format!(
+            "{}\n{}\n{}",
+            Self::HEADER,
+            serde_json::to_string(&header)?,
+            self.pack
+        ))
+    }
+
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        Self::from_str(&fs::read_to_string(path)?)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        fs::write(path, self.to_text()?)?;
+        Ok(())
+    }
+
+    /// Whether a file starts like a bundle
+    pub fn is_bundle(path: &Path) -> bool {
+        path.is_file() && fs::read(path).is_ok_and(|data| data.starts_with(Self::HEADER.as_bytes()))
+    }
+}
+
+impl FromStr for Bundle {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let malformed =
+            |reason: &str| Error::BundleError(BundleErrorReason::Malformed(reason.to_owned()));
+        let rest = s
+            .strip_prefix(Self::HEADER)
+            .and_then(|rest| rest.strip_prefix('\n'))
+            .ok_or_else(|| malformed("invalid header"))?;
+        let (header, pack) = rest
+            .split_once('\n')
+            .ok_or_else(|| malformed("missing refs"))?;
+        let header: BundleHeader =
+            serde_json::from_str(header).map_err(|e| malformed(&e.to_string()))?;
+        let head = match &header.refs.head {
+            Head::Branch(name) => Some(name),
+            Head::Detached(_) => None,
+        };
+        if let Some(name) = header
+            .refs
+            .branches
+            .keys()
+            .chain(header.refs.tags.keys())
+            .chain(header.refs.remotes.keys())
+            .chain(head)
+            .find(|name| Refs::check_name(name).is_err())
+        {
+            return Err(malformed(&format!("invalid ref name `{}`", name)));
+        }
+        Ok(Bundle {
+            prerequisites: header.prerequisites,
+            refs: header.refs,
+            pack: Pack::from_str(pack)?,
+        })
+    }
+}
+
+impl Repo {
+    /// Bundles the branches and tags named in `revs` with the commits of the revision set
+    /// of `revs`, see [`Repo::resolve_set`], like `main` for the whole history of main
+    /// or `v1..main` for the commits made on main since v1
+    pub fn create_bundle(&self, revs: &[&str]) -> Result<Bundle, Error> {
+        let set = self.resolve_set(revs)?;
+        let mut refs = Refs::default();
+        for rev in revs {
+            let name = match rev.rsplit_once("..") {
+                Some((_, name)) => name,
+                None if rev.starts_with('^') => continue,
+                None => rev,
+            };
+            if let Some(id) = self.refs.branches.get(name) {
+                refs.branches.insert(name.to_owned(), *id);
+            } else if let Some(tag) = self.refs.tags.get(name) {
+                refs.tags.insert(name.to_owned(), tag.clone());
+            }
+        }
+        if refs.branches.is_empty() && refs.tags.is_empty() {
+            return Err(Error::BundleError(BundleErrorReason::Empty));
+        }
+        refs.head = match self.refs.current_branch() {
+            Some(branch) if refs.branches.contains_key(branch) => Head::Branch(branch.to_owned()),
+            _ => match refs.branches.keys().next() {
+                Some(branch) => Head::Branch(branch.clone()),
+                None => Head::Detached(set.include[0]),
+            },
+        };
+
+        let pack = self.pack_missing(&FetchRequest {
+            wants: set.include,
+            haves: set.exclude,
+        })?;
+        let bundled = pack
+            .commits
+            .iter()
+            .map(|commit| commit.id)
+            .collect::<BTreeSet<_>>();
+        let prerequisites = pack
+            .commits
+            .iter()
+            .flat_map(|commit| &commit.parents)
+            .filter(|parent| !bundled.contains(parent))
+            .copied()
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .collect();
+        Ok(Bundle {
+            prerequisites,
+            refs,
+            pack,
+        })
+    }
+
+    /// Checks that the repo has every prerequisite of a bundle and that its refs lead to
+    /// commits it holds
+    pub fn verify_bundle(&self, bundle: &Bundle) -> Result<(), Error> {
+        let missing = bundle
+            .prerequisites
+            .iter()
+            .filter(|id| !self.history.contains(id))
+            .map(CommitId::to_hex)
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(Error::BundleError(BundleErrorReason::MissingPrerequisites(
+                missing,
+            )));
+        }
+        let known = |id: &CommitId| {
+            bundle.pack.commits.iter().any(|commit| commit.id == *id) || self.history.contains(id)
+        };
+        if let Some(id) = FetchRequest::new(&bundle.refs, Vec::new())
+            .wants
+            .iter()
+            .find(|id| !known(id))
+        {
+            return Err(Error::BundleError(BundleErrorReason::Malformed(format!(
+                "ref to missing commit {}",
+                id.to_hex()
+            ))));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+use std::{fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commit::CommitId,
+    error::Error,
+    merge::{self, MergeLabels},
+    repo::{Repo, FURSION_DIR},
+    tree::{self, Tree},
+};
+
+/// What stopped on conflicts
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum PickKind {
+    CherryPick,
+    Revert,
+}
+
+impl PickKind {
+    /// Name used in the reflog and the operation log
+    pub fn name(&self) -> &'static str {
+        match self {
+            PickKind::CherryPick => "cherry-pick",
+            PickKind::Revert => "revert",
+        }
+    }
+}
+
+/// A cherry-pick or revert waiting for its conflicts to be resolved
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PendingPick {
+    pub kind: PickKind,
+    /// The picked or reverted commit
+    pub commit: CommitId,
+    pub author: String,
+    /// Message of the commit made once the conflicts are resolved
+    pub message: String,
+    /// Paths of the files written with conflict markers
+    pub conflicts: Vec<String>,
+}
+
+impl PendingPick {
+    pub const FILE_NAME: &'static str = "PICK_HEAD";
+
+    pub fn read(fursion_dir_path: &Path) -> Result<Option<Self>, Error> {
+        let path = fursion_dir_path.join(Self::FILE_NAME);
+        if !Path::exists(&path) {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+    }
+
+    pub fn save(&self, fursion_dir_path: &Path) -> Result<(), Error> {
+        fs::write(
+            fursion_dir_path.join(Self::FILE_NAME),
+            serde_json::to_string(self)?,
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(fursion_dir_path: &Path) -> Result<(), Error> {
+        let path = fursion_dir_path.join(Self::FILE_NAME);
+        if Path::exists(&path) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Repo {
+    /// Applies the changes of a commit on top of HEAD with a three-way merge and commits
+    /// them with the same message and author, stops with [`Error::MergeConflicts`]
+    /// leaving the conflicting files to be resolved before [`Repo::continue_pick`]
+    pub fn cherry_pick(&mut self, id: CommitId) -> Result<CommitId, Error> {
+        let commit = self.history.find(&id)?.clone();
+        let base = match commit.parents.first() {
+            Some(parent) => self.tree_at(parent)?,
+            None => Tree::new(),
+        };
+        let theirs = self.tree_at(&id)?;
+        let labels = MergeLabels {
+            ours: "HEAD",
+            theirs: &format!("{} ({})", id.to_hex(), commit.summary()),
+        };
+        self.pick(
+            PickKind::CherryPick,
+            id,
+            &commit.author,
+            &commit.message,
+            &base,
+            &theirs,
+            labels,
+        )
+    }
+
+    /// Makes a commit undoing the changes of a commit, by merging its inverse
+    /// change set on top of HEAD, stops with [`Error::MergeConflicts`] like [`Repo::cherry_pick`]
+    pub fn revert(&mut self, id: CommitId) -> Result<CommitId, Error> {
+        let commit = self.history.find(&id)?.clone();
+        let base = self.tree_at(&id)?;
+        let before = match commit.parents.first() {
+            Some(parent) => self.tree_at(parent)?,
+            None => Tree::new(),
+        };
+        let inverse = commit
+            .changes()
+            .iter()
+            .map(|changes| changes.invert(before.get(changes.path()).map(String::as_str)))
+            .collect::<Vec<_>>();
+        let mut theirs = base.clone();
+        tree::apply_changes(&mut theirs, &inverse);
+
+        let message = format!(
+            "Revert \"{}\"\n\nThis reverts commit {}.",
+            commit.summary(),
+            id.to_hex()
+        );
+        let labels = MergeLabels {
+            ours: "HEAD",
+            theirs: &format!("parent of {} ({})", id.to_hex(), commit.summary()),
+        };
+        let author = self.metadata.author.clone();
+        self.pick(
+            PickKind::Revert,
+            id,
+            &author,
+            &message,
+            &base,
+            &theirs,
+            labels,
+        )
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn pick(
+        &mut self,
+        kind: PickKind,
+        id: CommitId,
+        author: &str,
+        message: &str,
+        base: &Tree,
+        theirs: &Tree,
+        labels: MergeLabels,
+    ) -> Result<CommitId, Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        self.check_nothing_in_progress()?;
+        if self.has_uncommitted_changes()? {
+            return Err(Error::UncommittedChanges);
+        }
+
+        let head_tree = self.head_tree()?;
+        let merged = merge::merge_trees(base, &head_tree, theirs, labels);
+        tree::write_working_tree(&self.path, &head_tree, &merged.tree)?;
+        self.reread_files()?;
+
+        if !merged.is_clean() {
+            PendingPick {
+                kind,
+                commit: id,
+                author: author.to_owned(),
+                message: message.to_owned(),
+                conflicts: merged.conflicts.clone(),
+            }
+            .save(&fursion_dir)?;
+            self.record_operation(&format!("{} (conflicts): {}", kind.name(), id.to_hex()))?;
+            return Err(Error::MergeConflicts(merged.conflicts));
+        }
+        let changes = tree::diff_trees(&head_tree, &merged.tree);
+        let parents = self.head().into_iter().collect();
+        self.commit_changes(message, author, parents, changes, kind.name())
+    }
+
+    /// The cherry-pick or revert stopped on conflicts, if any
+    pub fn pending_pick(&self) -> Result<Option<PendingPick>, Error> {
+        PendingPick::read(&self.path.join(FURSION_DIR))
+    }
+
+    /// Commits a stopped cherry-pick or revert once the conflict markers are removed
+    pub fn continue_pick(&mut self) -> Result<CommitId, Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        let pending = PendingPick::read(&fursion_dir)?.ok_or(Error::NothingInProgress)?;
+
+        let unresolved = self.unresolved_conflicts(&pending.conflicts)?;
+        if !unresolved.is_empty() {
+            return Err(Error::MergeConflicts(unresolved));
+        }
+
+        let changes = tree::diff_trees(&self.head_tree()?, &self.working_tree()?);
+        self.stated_changes.clear();
+        let id = self.commit_changes(
+            &pending.message,
+            &pending.author,
+            self.head().into_iter().collect(),
+            changes,
+            pending.kind.name(),
+        )?;
+        PendingPick::delete(&fursion_dir)?;
+        Ok(id)
+    }
+
+    /// Gives up a stopped cherry-pick or revert, putting back the files as they are at HEAD
+    pub fn abort_pick(&mut self) -> Result<(), Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        let pending = PendingPick::read(&fursion_dir)?.ok_or(Error::NothingInProgress)?;
+
+        tree::write_working_tree(&self.path, &self.working_tree()?, &self.head_tree()?)?;
+        self.reread_files()?;
+        self.stated_changes.clear();
+        self.save_stated_changes()?;
+        PendingPick::delete(&fursion_dir)?;
+        self.record_operation(&format!("{} (abort)", pending.kind.name()))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,77 @@
+use std::{fs, path::Path};
+
+use crate::{
+    error::{Error, RepoErrorReason},
+    fetch::FetchRequest,
+    pack::Pack,
+    refs::{Head, Refs},
+    remote::Remote,
+    repo::Repo,
+    tree::{self, Tree},
+};
+
+impl Repo {
+    /// Makes a new repo in `dest` holding every commit, branch and tag of the remote,
+    /// with the remote registered and its checked out branch checked out
+    pub async fn clone_from(remote: &Remote, dest: &Path) -> Result<Repo, Error> {
+        // Checked before fetching so that nothing is downloaded for nothing
+        Self::check_clone_dest(dest)?;
+        let refs = remote.list_refs().await?;
+        let request = FetchRequest::new(&refs, Vec::new());
+        let pack = if request.wants.is_empty() {
+            Pack::default()
+        } else {
+            remote.fetch_pack(&request).await?
+        };
+        Self::clone_pack(remote, &refs, pack, dest)
+    }
+
+    /// Makes the clone of a repo read locally, every path of the source, which are the
+    /// ones of the machine it was read on, is left behind for `dest`
+    pub fn clone_fetched(source: &Repo, remote: &Remote, dest: &Path) -> Result<Repo, Error> {
+        let pack = source.pack_missing(&FetchRequest::new(&source.refs, Vec::new()))?;
+        Self::clone_pack(remote, &source.refs, pack, dest)
+    }
+
+    /// Makes the clone out of the refs of the remote and the commits they lead to
+    fn clone_pack(remote: &Remote, refs: &Refs, pack: Pack, dest: &Path) -> Result<Repo, Error> {
+        Self::check_clone_dest(dest)?;
+        // The branches are copied as they are, their names being joined to the refs dir
+        for name in refs.branches.keys() {
+            Refs::check_name(name)?;
+        }
+        if let Head::Branch(name) = &refs.head {
+            Refs::check_name(name)?;
+        }
+        let mut repo = Repo::init(dest)?;
+        repo.remotes = vec![remote.clone()];
+        repo.save_remotes()?;
+        repo.store_fetch(remote, refs, pack)?;
+
+        let message = format!("clone: from {}", remote.url);
+        repo.refs.branches = refs.branches.clone();
+        repo.refs.head = refs.head.clone();
+        for (name, id) in &refs.branches {
+            repo.log_ref_update(name, None, Some(*id), &message)?;
+        }
+        if let Some(id) = repo.head() {
+            repo.log_ref_update(Refs::HEAD_FILE_NAME, None, Some(id), &message)?;
+            tree::write_working_tree(dest, &Tree::new(), &repo.tree_at(&id)?)?;
+            repo.reread_files()?;
+            repo.ignored = Self::get_ignored(dest)?;
+        }
+        repo.save_refs()?;
+        repo.record_operation(&format!("clone from {}", remote.url))?;
+        Ok(repo)
+    }
+
+    /// A clone is only made in a missing or empty directory
+    fn check_clone_dest(dest: &Path) -> Result<(), Error> {
+        if Path::exists(dest) && fs::read_dir(dest)?.next().is_some() {
+            return Err(Error::RepoInitFailed(RepoErrorReason::DirIsNotEmpty(
+                dest.to_owned(),
+            )));
+        }
+        Ok(())
+    }
+}
@@ -1,10 +1,17 @@
 use rand;
 use serde::{Deserialize, Serialize};
-use std::{fs, ops::Range, panic::catch_unwind, path::Path};
+use std::{
+    fmt, fs,
+    ops::Range,
+    panic::catch_unwind,
+    path::Path,
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use crate::error::{CommitParseFailedReason, Error};
 
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
 pub struct CommitId([u8; 3]);
 
 impl CommitId {
@@ -16,76 +23,250 @@ impl CommitId {
     /// Gives back an hex value which equals to the commit id
     /// The hex value looks something like ```69FC64```
     pub fn to_hex(&self) -> String {
-        format!("{:X?}", self.0)
-            .replace(", ", "")
-            .strip_prefix("[")
-            .unwrap()
-            .strip_suffix("]")
-            .unwrap()
-            .to_owned()
+        self.0.iter().map(|byte| format!("{:02X}", byte)).collect()
     }
 
+    /// Number of hex digits of a full commit id
+    pub const HEX_LEN: usize = 6;
+
+    /// Parses a full commit id, like ```69FC64```, case insensitive
     pub fn from_hex(s: &str) -> Result<Self, Error> {
-        let vec = vec![&s[0..=1], &s[2..=3], &s[4..=5]];
+        if s.len() != Self::HEX_LEN || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(Error::CommitParseFailed(
+                CommitParseFailedReason::CommitIdParseFailed,
+            ));
+        }
 
-        let num_vec = vec
-            .iter()
-            .map(|s| u8::from_str_radix(s, 16))
-            .collect::<Result<Vec<_>, _>>()
-            .map_err(|_| Error::CommitParseFailed(CommitParseFailedReason::CommitIdParseFailed))?;
+        let mut bytes = [0; 3];
+        for (i, byte) in bytes.iter_mut().enumerate() {
+            *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| {
+                Error::CommitParseFailed(CommitParseFailedReason::CommitIdParseFailed)
+            })?;
+        }
 
-        let slice = num_vec
-            .try_into()
-            .map_err(|_| Error::CommitParseFailed(CommitParseFailedReason::CommitIdParseFailed))?;
+        Ok(CommitId(bytes))
+    }
 
-        Ok(CommitId(slice))
+    /// Whether the hex value of the id starts with `prefix`, case insensitive
+    pub fn starts_with(&self, prefix: &str) -> bool {
+        self.to_hex().starts_with(&prefix.to_ascii_uppercase())
     }
 }
 
 /// A commit with a message and an id
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct Commit {
     /// The message associated with the commit
     pub message: String,
     /// A random 3 bytes id can be displayed as 6 digits hex
     pub id: CommitId,
-    changes: FileChanges,
+    /// The commits this one is based on, the first one is the one `changes` are relative to,
+    /// there are two or more parents for merge commits and none for the first commit
+    pub parents: Vec<CommitId>,
+    /// The name of the author of the commit
+    pub author: String,
+    /// Seconds since the unix epoch at which the commit was made
+    pub timestamp: u64,
+    changes: Vec<FileChanges>,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+/// The changes made to a single file
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
 pub struct FileChanges {
     path: String,
     changes: Vec<FileChange>,
+    /// Whether the file is removed once the changes are applied
+    #[serde(default)]
+    removed: bool,
 }
 
 impl FileChanges {
+    pub const DELIMITER: &'static str = "\nEND_FURSION_FILE\n";
+    const REMOVED_MARKER: &'static str = "|removed";
+
     pub fn new(path: String, changes: Vec<FileChange>) -> Self {
-        FileChanges{
+        FileChanges {
+            path,
+            changes,
+            removed: false,
+        }
+    }
+
+    /// The changes removing a file which had `content`
+    pub fn removal(path: String, content: &str) -> Self {
+        let changes = if content.is_empty() {
+            Vec::new()
+        } else {
+            vec![FileChange {
+                range: 0..content.len(),
+                text: None,
+                operation: FileChangeOperation::Deletion,
+            }]
+        };
+        FileChanges {
             path,
             changes,
+            removed: true,
+        }
+    }
+
+    pub fn is_removal(&self) -> bool {
+        self.removed
+    }
+
+    /// The path of the changed file relative to the repo root
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// The list of changes, sorted by range and relative to the previous content of the file
+    pub fn changes(&self) -> &[FileChange] {
+        &self.changes
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty() && !self.removed
+    }
+
+    /// Applies the changes to the previous content of the file and gives back the new content
+    pub fn apply(&self, content: &str) -> String {
+        let mut result = content.to_owned();
+        for change in self.changes.iter().rev() {
+            let end = change.range.end.min(result.len());
+            let start = change.range.start.min(end);
+            let text = change.text.as_deref().unwrap_or("");
+            match change.operation {
+                FileChangeOperation::Insertion => result.insert_str(start, text),
+                FileChangeOperation::Deletion => result.replace_range(start..end, ""),
+                FileChangeOperation::Edit => result.replace_range(start..end, text),
+            }
+        }
+        result
+    }
+
+    /// The changes undoing these ones, `old` being the content of the file before them,
+    /// [`None`] if they created the file
+    pub fn invert(&self, old: Option<&str>) -> Self {
+        let Some(old) = old else {
+            return Self::removal(self.path.clone(), &self.apply(""));
+        };
+        if self.removed {
+            let changes = if old.is_empty() {
+                Vec::new()
+            } else {
+                vec![FileChange {
+                    range: 0..0,
+                    text: Some(old.to_owned()),
+                    operation: FileChangeOperation::Insertion,
+                }]
+            };
+            return Self::new(self.path.clone(), changes);
+        }
+
+        // Each change is replaced back, at its position in the new content
+        let mut shift: isize = 0;
+        let changes = self
+            .changes
+            .iter()
+            .map(|change| {
+                let end = change.range.end.min(old.len());
+                let start = change.range.start.min(end);
+                let (removed, inserted) = match change.operation {
+                    FileChangeOperation::Insertion => {
+                        (0, change.text.as_deref().map_or(0, str::len))
+                    }
+                    FileChangeOperation::Deletion => (end - start, 0),
+                    FileChangeOperation::Edit => {
+                        (end - start, change.text.as_deref().map_or(0, str::len))
+                    }
+                };
+                let new_start = (start as isize + shift) as usize;
+                shift += inserted as isize - removed as isize;
+
+                let operation = match (removed, inserted) {
+                    (_, 0) => FileChangeOperation::Insertion,
+                    (0, _) => FileChangeOperation::Deletion,
+                    _ => FileChangeOperation::Edit,
+                };
+                FileChange {
+                    range: new_start..new_start + inserted,
+                    text: (removed > 0).then(|| old[start..start + removed].to_owned()),
+                    operation,
+                }
+            })
+            .collect();
+        Self::new(self.path.clone(), changes)
+    }
+
+    /// Parses a list of file changes as written by [`FileChanges::list_to_string`]
+    pub fn list_from_str(s: &str) -> Result<Vec<Self>, Error> {
+        if s.is_empty() {
+            return Ok(Vec::new());
         }
+        s.split(Self::DELIMITER).map(Self::from_str).collect()
     }
 
-    pub fn from_str(s: &str) -> Result<Self, Error> {
-        let changes_iter = s.split(FileChange::DELIMITER);
-        let vec = changes_iter
-            .map(FileChange::from_str)
-            .collect::<Result<Vec<_>, _>>()?;
-        Ok(Self::new(vec))
+    pub fn list_to_string(list: &[Self]) -> String {
+        list.iter()
+            .map(Self::to_string)
+            .collect::<Vec<_>>()
+            .join(Self::DELIMITER)
     }
 
-    pub fn to_string(&self) -> String {
-        let changes = self.changes
+    /// Reads a list of file changes from a file, an inexistant file means no changes
+    pub fn list_from_file(path: &Path) -> Result<Vec<Self>, Error> {
+        if !Path::exists(path) {
+            return Ok(Vec::new());
+        }
+        let data = fs::read(path)?;
+        Self::list_from_str(std::str::from_utf8(&data)?)
+    }
+}
+
+impl fmt::Display for FileChanges {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let changes = self
+            .changes
             .iter()
             .map(FileChange::to_string)
             .collect::<Vec<_>>()
             .join(FileChange::DELIMITER);
 
-        format!("{}\n{}", self.path, changes)
+        let marker = if self.removed {
+            Self::REMOVED_MARKER
+        } else {
+            ""
+        };
+        write!(f, "{}{}\n{}", self.path, marker, changes)
+    }
+}
+
+impl FromStr for FileChanges {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let (header, changes_str) = s.split_once('\n').unwrap_or((s, ""));
+        let changes = if changes_str.is_empty() {
+            Vec::new()
+        } else {
+            changes_str
+                .split(FileChange::DELIMITER)
+                .map(FileChange::from_str)
+                .collect::<Result<Vec<_>, _>>()?
+        };
+        let (path, removed) = match header.strip_suffix(Self::REMOVED_MARKER) {
+            Some(path) => (path, true),
+            None => (header, false),
+        };
+        Ok(FileChanges {
+            path: path.to_owned(),
+            changes,
+            removed,
+        })
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
 pub struct FileChange {
     pub range: Range<usize>,
     pub text: Option<String>,
@@ -99,17 +280,18 @@ pub enum FileChangeOperation {
     Edit,
 }
 
-impl FileChangeOperation {
-    fn to_string(&self) -> String {
-        match self {
+impl fmt::Display for FileChangeOperation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
             Self::Deletion => "-",
             Self::Insertion => "+",
             Self::Edit => "e",
-        }
-        .to_string()
+        })
     }
+}
 
-    fn from_str(s: &str) -> Self {
+impl From<&str> for FileChangeOperation {
+    fn from(s: &str) -> Self {
         match s {
             "-" => Self::Deletion,
             "+" => Self::Insertion,
@@ -119,40 +301,46 @@ impl FileChangeOperation {
     }
 }
 
-impl From<&str> for FileChangeOperation {
-    fn from(s: &str) -> Self {
-        FileChangeOperation::from_str(s)
+impl From<FileChangeOperation> for String {
+    fn from(operation: FileChangeOperation) -> Self {
+        operation.to_string()
     }
 }
 
-impl Into<String> for FileChangeOperation {
-    fn into(self) -> String {
-        self.to_string()
+impl FileChange {
+    pub const DELIMITER: &'static str = "\nEND_FURSION_CHANGE\n";
+}
+
+impl fmt::Display for FileChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}..{}|{}\n{}",
+            self.range.start,
+            self.range.end,
+            self.operation,
+            self.text.as_deref().unwrap_or("")
+        )
     }
 }
 
-impl FileChange {
-    pub const DELIMITER: &'static str = "\nEND_FURSION_CHANGE";
+impl FromStr for FileChange {
+    type Err = Error;
 
-    pub fn from_str(str: &str) -> Result<FileChange, Error> {
+    fn from_str(str: &str) -> Result<Self, Error> {
         catch_unwind(|| {
-            let meta_str = str
-                .lines()
-                .collect::<Vec<_>>()
-                .first()
-                .and_then(|s| Some(*s))
-                .ok_or(Error::CommitParseFailed(
-                    CommitParseFailedReason::FileChangeDataNotFound,
-                ))?;
+            let meta_str = str.lines().collect::<Vec<_>>().first().copied().ok_or(
+                Error::CommitParseFailed(CommitParseFailedReason::FileChangeDataNotFound),
+            )?;
 
             let meta = meta_str.split("|").collect::<Vec<_>>();
 
-            let operation = FileChangeOperation::from_str(meta[1]);
+            let operation = FileChangeOperation::from(meta[1]);
 
             let text = if operation == FileChangeOperation::Deletion {
                 None
             } else {
-                Some(str[meta_str.len()..].to_string())
+                Some(str.get(meta_str.len() + 1..).unwrap_or("").to_string())
             };
 
             let range_nums: Vec<usize> = meta[0]
@@ -173,55 +361,106 @@ impl FileChange {
         })
         .map_err(|_| Error::CommitParseFailed(CommitParseFailedReason::FileChangeDataMalformed))?
     }
-
-    pub fn to_string(&self) -> String {
-        format!(
-            "{}..{}|{}",
-            self.range.start,
-            self.range.end,
-            self.operation.to_string()
-        )
-    }
 }
 
 impl Commit {
+    pub const MESSAGE_DELIMITER: &'static str = "\nEND_FURSION_MESSAGE\n";
+
     /// Makes a new commit object with a pseudo-random id
-    pub(crate) fn new(message: &str, changes: FileChanges) -> Self {
+    pub(crate) fn new(
+        message: &str,
+        author: &str,
+        parents: Vec<CommitId>,
+        changes: Vec<FileChanges>,
+    ) -> Self {
         Commit {
             changes,
+            parents,
+            author: author.to_owned(),
+            timestamp: now(),
             message: message.to_owned(),
             id: CommitId::new(),
         }
     }
 
-    pub fn to_string(&self) -> String {
-        let changes_str = self
-            .changes
-            .0
+    /// The changes of each file touched by the commit
+    pub fn changes(&self) -> &[FileChanges] {
+        &self.changes
+    }
+
+    /// The first line of the message
+    pub fn summary(&self) -> &str {
+        self.message.lines().next().unwrap_or("")
+    }
+
+    pub fn is_merge(&self) -> bool {
+        self.parents.len() > 1
+    }
+}
+
+impl fmt::Display for Commit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let parents = self
+            .parents
             .iter()
-            .map(|change| change.to_string())
+            .map(CommitId::to_hex)
             .collect::<Vec<_>>()
-            .join(FileChange::DELIMITER);
-
-        format!("{}|{}\n{}", self.id.to_hex(), self.message, changes_str)
+            .join(",");
+
+        write!(
+            f,
+            "{}|{}|{}|{}\n{}{}{}",
+            self.id.to_hex(),
+            parents,
+            self.timestamp,
+            self.author,
+            self.message,
+            Self::MESSAGE_DELIMITER,
+            FileChanges::list_to_string(&self.changes)
+        )
     }
+}
 
-    pub fn from_str(s: &str) -> Result<Self, Error> {
-        let data_str = s.lines().collect::<Vec<_>>()[0];
-        let data = data_str.split("|").collect::<Vec<_>>();
+impl FromStr for Commit {
+    type Err = Error;
 
-        let changes = s[data_str.len()..]
-            .split(FileChange::DELIMITER)
-            .map(FileChange::from_str)
-            .collect::<Result<_, _>>()?;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let malformed = || Error::CommitParseFailed(CommitParseFailedReason::CommitDataMalformed);
+
+        let (data_str, rest) = s.split_once('\n').ok_or_else(malformed)?;
+        let (message, changes_str) = rest
+            .split_once(Self::MESSAGE_DELIMITER)
+            .ok_or_else(malformed)?;
+
+        let data = data_str.splitn(4, '|').collect::<Vec<_>>();
+        if data.len() != 4 {
+            return Err(malformed());
+        }
 
-        let message = data[1].to_string();
         let id = CommitId::from_hex(data[0])?;
+        let parents = data[1]
+            .split(',')
+            .filter(|s| !s.is_empty())
+            .map(CommitId::from_hex)
+            .collect::<Result<_, _>>()?;
+        let timestamp = data[2].parse().map_err(|_| malformed())?;
+        let changes = FileChanges::list_from_str(changes_str)?;
 
         Ok(Self {
-            message,
+            message: message.to_string(),
             id,
-            changes: FileChanges::new(changes),
+            parents,
+            author: data[3].to_string(),
+            timestamp,
+            changes,
         })
     }
 }
+
+/// Seconds elapsed since the unix epoch
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
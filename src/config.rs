@@ -0,0 +1,118 @@
+use std::{collections::BTreeMap, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    error::Error,
+    remote::DEFAULT_REMOTE_NAME,
+    repo::{self, Repo, FURSION_DIR},
+};
+
+/// How a pull brings the fetched commits into the checked out branch
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PullMode {
+    /// Only moves the branch forward, fails if it has commits the upstream doesn't have
+    FastForwardOnly,
+    /// Fast-forwards when possible, makes a merge commit otherwise
+    #[default]
+    Merge,
+    /// Replays the commits of the branch on top of the upstream
+    Rebase,
+}
+
+impl PullMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            PullMode::FastForwardOnly => "ff-only",
+            PullMode::Merge => "merge",
+            PullMode::Rebase => "rebase",
+        }
+    }
+
+    pub fn from_name(s: &str) -> Option<Self> {
+        match s {
+            "ff-only" => Some(PullMode::FastForwardOnly),
+            "merge" => Some(PullMode::Merge),
+            "rebase" => Some(PullMode::Rebase),
+            _ => None,
+        }
+    }
+}
+
+/// The branch of a remote a local branch pulls from
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Upstream {
+    pub remote: String,
+    pub branch: String,
+}
+
+impl Upstream {
+    /// Name of the remote-tracking branch with the default refspec, like `origin/main`,
+    /// see [`Repo::upstream_tracking_name`]
+    pub fn tracking_name(&self) -> String {
+        format!("{}/{}", self.remote, self.branch)
+    }
+}
+
+/// Settings of a repo, saved in the `.fursion` dir
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct RepoConfig {
+    #[serde(default)]
+    pub pull: PullMode,
+    /// Upstreams of the local branches which don't pull from the branch of the same name
+    /// of [`DEFAULT_REMOTE_NAME`]
+    #[serde(default)]
+    pub upstreams: BTreeMap<String, Upstream>,
+}
+
+impl RepoConfig {
+    pub const FILE_NAME: &'static str = "config";
+
+    pub fn read(fursion_dir_path: &Path) -> Result<Self, Error> {
+        repo::read_json_or_default(&fursion_dir_path.join(Self::FILE_NAME))
+    }
+
+    pub fn save(&self, fursion_dir_path: &Path) -> Result<(), Error> {
+        repo::save_json(&fursion_dir_path.join(Self::FILE_NAME), self)
+    }
+}
+
+impl Repo {
+    pub fn config(&self) -> Result<RepoConfig, Error> {
+        RepoConfig::read(&self.path.join(FURSION_DIR))
+    }
+
+    pub fn save_config(&self, config: &RepoConfig) -> Result<(), Error> {
+        config.save(&self.path.join(FURSION_DIR))
+    }
+
+    /// The branch a local branch pulls from, the branch of the same name
+    /// of [`DEFAULT_REMOTE_NAME`] unless another one was set
+    pub fn upstream(&self, branch: &str) -> Result<Upstream, Error> {
+        Ok(self
+            .config()?
+            .upstreams
+            .get(branch)
+            .cloned()
+            .unwrap_or_else(|| Upstream {
+                remote: DEFAULT_REMOTE_NAME.to_owned(),
+                branch: branch.to_owned(),
+            }))
+    }
+
+    /// The remote-tracking branch an upstream is fetched into, as the refspecs of its
+    /// remote say
+    pub fn upstream_tracking_name(&self, upstream: &Upstream) -> String {
+        self.remote(&upstream.remote)
+            .ok()
+            .and_then(|remote| remote.tracking_name(&upstream.branch))
+            .map(|(tracking, _)| tracking)
+            .unwrap_or_else(|| upstream.tracking_name())
+    }
+
+    pub fn set_upstream(&self, branch: &str, upstream: Upstream) -> Result<(), Error> {
+        let mut config = self.config()?;
+        config.upstreams.insert(branch.to_owned(), upstream);
+        self.save_config(&config)
+    }
+}
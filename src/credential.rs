@@ -0,0 +1,223 @@
+use std::{
+    env, fmt, fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+    str::FromStr,
+};
+
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+
+use crate::error::{AuthErrorReason, Error};
+
+/// Env var holding the path of the credential file, see [`CredentialFile::default_path`]
+pub const CREDENTIAL_FILE_ENV: &str = "FURSION_CREDENTIALS";
+/// Env var holding a command giving the credentials of a url, see [`credentials_for`]
+pub const CREDENTIAL_HELPER_ENV: &str = "FURSION_CREDENTIAL_HELPER";
+
+/// What a client proves who it is with
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Credentials {
+    /// An API token made on the server, sent as a bearer token
+    Token(String),
+    /// A user name and password, sent with HTTP basic auth
+    Basic { user: String, password: String },
+}
+
+impl Credentials {
+    /// Value of the `Authorization` header carrying the credentials
+    pub fn to_header(&self) -> String {
+        match self {
+            Credentials::Token(token) => format!("Bearer {}", token),
+            Credentials::Basic { user, password } => {
+                format!("Basic {}", BASE64.encode(format!("{}:{}", user, password)))
+            }
+        }
+    }
+
+    /// Reads the value of an `Authorization` header
+    pub fn from_header(value: &str) -> Result<Self, Error> {
+        let invalid = || Error::AuthError(AuthErrorReason::InvalidCredentials);
+        let (scheme, value) = value.trim().split_once(' ').ok_or_else(invalid)?;
+        match scheme {
+            "Bearer" => Ok(Credentials::Token(value.trim().to_owned())),
+            "Basic" => {
+                let decoded = BASE64.decode(value.trim()).map_err(|_| invalid())?;
+                let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+                let (user, password) = decoded.split_once(':').ok_or_else(invalid)?;
+                Ok(Credentials::Basic {
+                    user: user.to_owned(),
+                    password: password.to_owned(),
+                })
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Reads the `key=value` lines written by a credential helper, either `token=` or
+    /// `username=` and `password=`
+    pub fn from_helper_output(s: &str) -> Option<Self> {
+        let value = |key: &str| {
+            s.lines()
+                .find_map(|line| line.trim().strip_prefix(key)?.strip_prefix('='))
+                .map(str::to_owned)
+        };
+        match (value("token"), value("username"), value("password")) {
+            (Some(token), _, _) => Some(Credentials::Token(token)),
+            (None, Some(user), Some(password)) => Some(Credentials::Basic { user, password }),
+            _ => None,
+        }
+    }
+}
+
+/// Credentials of the servers a client talks to, one per line as
+/// `<url prefix> token <token>` or `<url prefix> basic <user> <password>`
+#[derive(Debug, Clone, Default)]
+pub struct CredentialFile {
+    pub entries: Vec<(String, Credentials)>,
+}
+
+impl CredentialFile {
+    /// `$FURSION_CREDENTIALS`, or `.fursion/credentials` in the home dir
+    pub fn default_path() -> Option<PathBuf> {
+        if let Some(path) = env::var_os(CREDENTIAL_FILE_ENV) {
+            return Some(PathBuf::from(path));
+        }
+        env::var_os("HOME")
+            .or_else(|| env::var_os("USERPROFILE"))
+            .map(|home| PathBuf::from(home).join(".fursion").join("credentials"))
+    }
+
+    pub fn read(path: &Path) -> Result<Self, Error> {
+        if !Path::exists(path) {
+            return Ok(Self::default());
+        }
+        Self::from_str(&fs::read_to_string(path)?)
+    }
+
+    /// Writes the file, only readable by its owner on unix since it holds secrets
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut options = fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+            options.mode(0o600);
+            // The mode only applies to new files, older ones are restricted before writing
+            if Path::exists(path) {
+                fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+            }
+        }
+        options.open(path)?.write_all(self.to_string().as_bytes())?;
+        Ok(())
+    }
+
+    /// The credentials of the longest url prefix matching `url`
+    pub fn find(&self, url: &str) -> Option<&Credentials> {
+        self.entries
+            .iter()
+            .filter(|(prefix, _)| url.starts_with(prefix.as_str()))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, credentials)| credentials)
+    }
+
+    /// Sets the credentials of a url prefix, replacing the ones it had, the fields of a
+    /// line being separated by whitespace they can't hold any
+    pub fn set(&mut self, url: &str, credentials: Credentials) -> Result<(), Error> {
+        let fields = match &credentials {
+            Credentials::Token(token) => vec![("url", url), ("token", token)],
+            Credentials::Basic { user, password } => {
+                vec![("url", url), ("user", user), ("password", password)]
+            }
+        };
+        if let Some((name, _)) = fields
+            .iter()
+            .find(|(_, value)| value.is_empty() || value.contains(char::is_whitespace))
+        {
+            return Err(Error::AuthError(AuthErrorReason::UnstorableCredential(
+                (*name).to_owned(),
+            )));
+        }
+        self.remove(url);
+        self.entries.push((url.to_owned(), credentials));
+        Ok(())
+    }
+
+    /// Forgets the credentials of a url prefix, returns whether it had some
+    pub fn remove(&mut self, url: &str) -> bool {
+        let len = self.entries.len();
+        self.entries.retain(|(prefix, _)| prefix != url);
+        self.entries.len() != len
+    }
+}
+
+impl fmt::Display for CredentialFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (url, credentials) in &self.entries {
+            match credentials {
+                Credentials::Token(token) => writeln!(f, "{} token {}", url, token)?,
+                Credentials::Basic { user, password } => {
+                    writeln!(f, "{} basic {} {}", url, user, password)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for CredentialFile {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let mut entries = Vec::new();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let malformed =
+                || Error::AuthError(AuthErrorReason::CredentialFileMalformed(line.to_owned()));
+            let parts = line.split_whitespace().collect::<Vec<_>>();
+            let credentials = match parts[1..] {
+                ["token", token] => Credentials::Token(token.to_owned()),
+                ["basic", user, password] => Credentials::Basic {
+                    user: user.to_owned(),
+                    password: password.to_owned(),
+                },
+                _ => return Err(malformed()),
+            };
+            entries.push((parts[0].to_owned(), credentials));
+        }
+        Ok(CredentialFile { entries })
+    }
+}
+
+/// The credentials to send to `url`, given by the command of `$FURSION_CREDENTIAL_HELPER`
+/// if set, split on whitespace and run with `get <url>` added to its arguments, or else
+/// found in the credential file
+pub fn credentials_for(url: &str) -> Result<Option<Credentials>, Error> {
+    if let Ok(helper) = env::var(CREDENTIAL_HELPER_ENV) {
+        let mut parts = helper.split_whitespace();
+        if let Some(program) = parts.next() {
+            let output = process::Command::new(program)
+                .args(parts)
+                .args(["get", url])
+                .output()
+                .map_err(|e| Error::AuthError(AuthErrorReason::HelperFailed(e.to_string())))?;
+            if !output.status.success() {
+                return Err(Error::AuthError(AuthErrorReason::HelperFailed(
+                    String::from_utf8_lossy(&output.stderr).into_owned(),
+                )));
+            }
+            return Ok(Credentials::from_helper_output(&String::from_utf8_lossy(
+                &output.stdout,
+            )));
+        }
+    }
+    match CredentialFile::default_path() {
+        Some(path) => Ok(CredentialFile::read(&path)?.find(url).cloned()),
+        None => Ok(None),
+    }
+}
@@ -0,0 +1,613 @@
+use std::ops::Range;
+
+use crate::commit::{FileChange, FileChangeOperation, FileChanges};
+
+/// Granularity used when highlighting the changes inside of a line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiffMode {
+    /// Whole lines are shown as removed then added
+    #[default]
+    Line,
+    /// Words, whitespace runs and punctuation are compared separately
+    Word,
+    /// Every character is compared separately
+    Char,
+}
+
+/// Options of the diff rendering
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    pub mode: DiffMode,
+    /// Number of unchanged lines shown around each change
+    pub context: usize,
+    /// Uses ANSI colors instead of `[-removed-]{+added+}` markers
+    pub color: bool,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions {
+            mode: DiffMode::default(),
+            context: 3,
+            color: false,
+        }
+    }
+}
+
+/// A single step of an edit script
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffOp {
+    /// `len` equal items starting at `old` and `new`
+    Equal { old: usize, new: usize, len: usize },
+    /// `len` items removed from the old sequence starting at `old`
+    Delete { old: usize, len: usize },
+    /// `len` items inserted from the new sequence starting at `new`
+    Insert { new: usize, len: usize },
+}
+
+/// Kind of a piece of an inline diff
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentKind {
+    Equal,
+    Removed,
+    Added,
+}
+
+/// A piece of text of an inline diff
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffSegment {
+    pub kind: SegmentKind,
+    pub text: String,
+}
+
+/// A group of changed lines along with their context
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Hunk {
+    /// Lines of the old text covered by the hunk, context included
+    pub old_lines: Range<usize>,
+    /// Lines of the new text covered by the hunk, context included
+    pub new_lines: Range<usize>,
+    ops: Vec<DiffOp>,
+}
+
+/// Computes the shortest edit script between two sequences with the linear space
+/// variant of Myers' algorithm, which splits the sequences around the middle of an
+/// optimal path and diffs both halves
+pub fn diff_slices<T: PartialEq>(old: &[T], new: &[T]) -> Vec<DiffOp> {
+    let size = 2 * (max_d(old.len(), new.len()) + 2) + 1;
+    let mut forward = vec![0; size];
+    let mut backward = vec![0; size];
+    let mut steps = Vec::new();
+    diff_ranges(
+        old,
+        0..old.len(),
+        new,
+        0..new.len(),
+        &mut forward,
+        &mut backward,
+        &mut steps,
+    );
+    merge_ops(steps)
+}
+
+/// Bound of the number of edits searched from each end for the middle snake
+fn max_d(old_len: usize, new_len: usize) -> usize {
+    (old_len + new_len).div_ceil(2) + 1
+}
+
+fn common_prefix_len<T: PartialEq>(old: &[T], new: &[T]) -> usize {
+    old.iter().zip(new).take_while(|(a, b)| a == b).count()
+}
+
+fn common_suffix_len<T: PartialEq>(old: &[T], new: &[T]) -> usize {
+    old.iter()
+        .rev()
+        .zip(new.iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count()
+}
+
+/// Adds the edit script of `old[old_range]` to `new[new_range]` to `steps`
+fn diff_ranges<T: PartialEq>(
+    old: &[T],
+    mut old_range: Range<usize>,
+    new: &[T],
+    mut new_range: Range<usize>,
+    forward: &mut [usize],
+    backward: &mut [usize],
+    steps: &mut Vec<DiffOp>,
+) {
+    let prefix = common_prefix_len(&old[old_range.clone()], &new[new_range.clone()]);
+    if prefix > 0 {
+        steps.push(DiffOp::Equal {
+            old: old_range.start,
+            new: new_range.start,
+            len: prefix,
+        });
+    }
+    old_range.start += prefix;
+    new_range.start += prefix;
+    let suffix = common_suffix_len(&old[old_range.clone()], &new[new_range.clone()]);
+    old_range.end -= suffix;
+    new_range.end -= suffix;
+
+    if old_range.is_empty() && new_range.is_empty() {
+    } else if new_range.is_empty() {
+        steps.push(DiffOp::Delete {
+            old: old_range.start,
+            len: old_range.len(),
+        });
+    } else if old_range.is_empty() {
+        steps.push(DiffOp::Insert {
+            new: new_range.start,
+            len: new_range.len(),
+        });
+    } else {
+        let (x, y) = middle_snake(
+            old,
+            old_range.clone(),
+            new,
+            new_range.clone(),
+            forward,
+            backward,
+        );
+        diff_ranges(
+            old,
+            old_range.start..x,
+            new,
+            new_range.start..y,
+            forward,
+            backward,
+            steps,
+        );
+        diff_ranges(
+            old,
+            x..old_range.end,
+            new,
+            y..new_range.end,
+            forward,
+            backward,
+            steps,
+        );
+    }
+
+    if suffix > 0 {
+        steps.push(DiffOp::Equal {
+            old: old_range.end,
+            new: new_range.end,
+            len: suffix,
+        });
+    }
+}
+
+/// The point at which an optimal path from the start to the end of the ranges is split,
+/// found by searching from both ends until the paths overlap, the ranges having
+/// neither a common prefix nor a common suffix
+fn middle_snake<T: PartialEq>(
+    old: &[T],
+    old_range: Range<usize>,
+    new: &[T],
+    new_range: Range<usize>,
+    forward: &mut [usize],
+    backward: &mut [usize],
+) -> (usize, usize) {
+    let n = old_range.len() as isize;
+    let m = new_range.len() as isize;
+    let delta = n - m;
+    let odd = delta & 1 == 1;
+    // Arrays are indexed by diagonal `k = x - y`, shifted to be positive
+    let offset = (forward.len() / 2) as isize;
+    let at = |k: isize| (k + offset) as usize;
+    forward[at(1)] = 0;
+    backward[at(1)] = 0;
+
+    for d in 0..max_d(old_range.len(), new_range.len()) as isize {
+        // Furthest points reached from the start with `d` edits
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && forward[at(k - 1)] < forward[at(k + 1)]) {
+                forward[at(k + 1)] as isize
+            } else {
+                forward[at(k - 1)] as isize + 1
+            };
+            let y = x - k;
+            let snake_start = (x, y);
+            if x < n && y < m {
+                x += common_prefix_len(
+                    &old[old_range.start + x as usize..old_range.end],
+                    &new[new_range.start + y as usize..new_range.end],
+                ) as isize;
+            }
+            forward[at(k)] = x as usize;
+            if odd && (k - delta).abs() < d && x + backward[at(-(k - delta))] as isize >= n {
+                return (
+                    old_range.start + snake_start.0 as usize,
+                    new_range.start + snake_start.1 as usize,
+                );
+            }
+        }
+        // Furthest points reached from the end with `d` edits, counted backwards
+        for k in (-d..=d).rev().step_by(2) {
+            let mut x = if k == -d || (k != d && backward[at(k - 1)] < backward[at(k + 1)]) {
+                backward[at(k + 1)] as isize
+            } else {
+                backward[at(k - 1)] as isize + 1
+            };
+            let mut y = x - k;
+            if x < n && y < m {
+                let len = common_suffix_len(
+                    &old[old_range.start..old_range.start + (n - x) as usize],
+                    &new[new_range.start..new_range.start + (m - y) as usize],
+                ) as isize;
+                x += len;
+                y += len;
+            }
+            backward[at(k)] = x as usize;
+            if !odd && (k - delta).abs() <= d && x + forward[at(-(k - delta))] as isize >= n {
+                return (
+                    old_range.start + (n - x) as usize,
+                    new_range.start + (m - y) as usize,
+                );
+            }
+        }
+    }
+    // An optimal path has at most `n + m` edits so the searches always meet
+    unreachable!("the searches of the middle snake didn't meet")
+}
+
+/// Joins consecutive operations of the same kind, the deletions between two equal runs
+/// coming before the insertions
+fn merge_ops(steps: Vec<DiffOp>) -> Vec<DiffOp> {
+    let mut ops: Vec<DiffOp> = Vec::new();
+    for step in steps {
+        if let (Some(DiffOp::Insert { .. }), DiffOp::Delete { old, len: l }) = (ops.last(), step) {
+            let insert = ops.pop();
+            match ops.last_mut() {
+                Some(DiffOp::Delete { len, .. }) => *len += l,
+                _ => ops.push(DiffOp::Delete { old, len: l }),
+            }
+            ops.extend(insert);
+            continue;
+        }
+        match (ops.last_mut(), step) {
+            (Some(DiffOp::Equal { len, .. }), DiffOp::Equal { len: l, .. })
+            | (Some(DiffOp::Delete { len, .. }), DiffOp::Delete { len: l, .. })
+            | (Some(DiffOp::Insert { len, .. }), DiffOp::Insert { len: l, .. }) => *len += l,
+            _ => ops.push(step),
+        }
+    }
+    ops
+}
+
+/// Splits a text in lines, keeping the line endings
+pub fn split_lines(text: &str) -> Vec<&str> {
+    text.split_inclusive('\n').collect()
+}
+
+/// Splits a text in words, whitespace runs and single punctuation characters
+pub fn split_words(text: &str) -> Vec<&str> {
+    let mut tokens = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let class = char_class(c);
+        if class == CharClass::Punctuation {
+            tokens.push(&text[i..i + c.len_utf8()]);
+            start = i + c.len_utf8();
+            continue;
+        }
+        match chars.peek() {
+            Some((_, next)) if char_class(*next) == class => {}
+            Some((j, _)) => {
+                tokens.push(&text[start..*j]);
+                start = *j;
+            }
+            None => tokens.push(&text[start..]),
+        }
+    }
+    tokens
+}
+
+/// Splits a text in characters
+pub fn split_chars(text: &str) -> Vec<&str> {
+    text.char_indices()
+        .map(|(i, c)| &text[i..i + c.len_utf8()])
+        .collect()
+}
+
+#[derive(PartialEq, Eq)]
+enum CharClass {
+    Word,
+    Whitespace,
+    Punctuation,
+}
+
+fn char_class(c: char) -> CharClass {
+    if c.is_alphanumeric() || c == '_' {
+        CharClass::Word
+    } else if c.is_whitespace() {
+        CharClass::Whitespace
+    } else {
+        CharClass::Punctuation
+    }
+}
+
+/// Computes the changes needed to go from `old` to `new`, line by line,
+/// with ranges expressed as byte offsets in `old`
+pub fn diff_text(path: &str, old: &str, new: &str) -> FileChanges {
+    let old_lines = split_lines(old);
+    let new_lines = split_lines(new);
+    let old_offsets = offsets(&old_lines);
+    let new_offsets = offsets(&new_lines);
+
+    let ops = diff_slices(&old_lines, &new_lines);
+    let mut changes = Vec::new();
+    let mut i = 0;
+    while i < ops.len() {
+        let (old_start, new_start) = match ops[i] {
+            DiffOp::Equal { .. } => {
+                i += 1;
+                continue;
+            }
+            DiffOp::Delete { old, .. } => (old, new_start_of(&ops, i)),
+            DiffOp::Insert { new, .. } => (old_start_of(&ops, i), new),
+        };
+        let (mut old_len, mut new_len) = (0, 0);
+        while let Some(op) = ops.get(i) {
+            match op {
+                DiffOp::Delete { len, .. } => old_len += len,
+                DiffOp::Insert { len, .. } => new_len += len,
+                DiffOp::Equal { .. } => break,
+            }
+            i += 1;
+        }
+
+        let range = old_offsets[old_start]..old_offsets[old_start + old_len];
+        let text = &new[new_offsets[new_start]..new_offsets[new_start + new_len]];
+        changes.push(match (old_len, new_len) {
+            (_, 0) => FileChange {
+                range,
+                text: None,
+                operation: FileChangeOperation::Deletion,
+            },
+            (0, _) => FileChange {
+                range,
+                text: Some(text.to_owned()),
+                operation: FileChangeOperation::Insertion,
+            },
+            _ => FileChange {
+                range,
+                text: Some(text.to_owned()),
+                operation: FileChangeOperation::Edit,
+            },
+        });
+    }
+
+    FileChanges::new(path.to_owned(), changes)
+}
+
+/// Byte offset of the start of each line, plus the total length
+fn offsets(lines: &[&str]) -> Vec<usize> {
+    let mut offsets = Vec::with_capacity(lines.len() + 1);
+    let mut total = 0;
+    offsets.push(0);
+    for line in lines {
+        total += line.len();
+        offsets.push(total);
+    }
+    offsets
+}
+
+/// Position in the old sequence at which the operation `i` applies
+fn old_start_of(ops: &[DiffOp], i: usize) -> usize {
+    ops[..i]
+        .iter()
+        .map(|op| match op {
+            DiffOp::Equal { len, .. } | DiffOp::Delete { len, .. } => *len,
+            DiffOp::Insert { .. } => 0,
+        })
+        .sum()
+}
+
+/// Position in the new sequence at which the operation `i` applies
+fn new_start_of(ops: &[DiffOp], i: usize) -> usize {
+    ops[..i]
+        .iter()
+        .map(|op| match op {
+            DiffOp::Equal { len, .. } | DiffOp::Insert { len, .. } => *len,
+            DiffOp::Delete { .. } => 0,
+        })
+        .sum()
+}
+
+/// Share of lines two texts have in common, from 0 to 1
+pub fn similarity(a: &str, b: &str) -> f64 {
+    let a = split_lines(a);
+    let b = split_lines(b);
+    if a.is_empty() && b.is_empty() {
+        return 1.0;
+    }
+    let common: usize = diff_slices(&a, &b)
+        .iter()
+        .map(|op| match op {
+            DiffOp::Equal { len, .. } => *len,
+            _ => 0,
+        })
+        .sum();
+    2.0 * common as f64 / (a.len() + b.len()) as f64
+}
+
+/// Computes an inline diff of two texts at the given granularity
+pub fn inline_diff(old: &str, new: &str, mode: DiffMode) -> Vec<DiffSegment> {
+    let split = match mode {
+        DiffMode::Line => split_lines,
+        DiffMode::Word => split_words,
+        DiffMode::Char => split_chars,
+    };
+    let old_tokens = split(old);
+    let new_tokens = split(new);
+
+    let mut segments: Vec<DiffSegment> = Vec::new();
+    let mut push = |kind: SegmentKind, tokens: &[&str]| {
+        let text = tokens.concat();
+        match segments.last_mut() {
+            Some(last) if last.kind == kind => last.text.push_str(&text),
+            _ => segments.push(DiffSegment { kind, text }),
+        }
+    };
+    for op in diff_slices(&old_tokens, &new_tokens) {
+        match op {
+            DiffOp::Equal { old, len, .. } => push(SegmentKind::Equal, &old_tokens[old..old + len]),
+            DiffOp::Delete { old, len } => push(SegmentKind::Removed, &old_tokens[old..old + len]),
+            DiffOp::Insert { new, len } => push(SegmentKind::Added, &new_tokens[new..new + len]),
+        }
+    }
+    segments
+}
+
+/// Groups the line changes between two texts in hunks with `context` lines around them
+pub fn hunks(old: &str, new: &str, context: usize) -> Vec<Hunk> {
+    let old_lines = split_lines(old);
+    let new_lines = split_lines(new);
+    let ops = diff_slices(&old_lines, &new_lines);
+
+    let mut hunks: Vec<Hunk> = Vec::new();
+    for (i, op) in ops.iter().enumerate() {
+        if let DiffOp::Equal { .. } = op {
+            continue;
+        }
+        let old_start = old_start_of(&ops, i);
+        let new_start = new_start_of(&ops, i);
+        let (old_len, new_len) = match op {
+            DiffOp::Delete { len, .. } => (*len, 0),
+            DiffOp::Insert { len, .. } => (0, *len),
+            DiffOp::Equal { .. } => unreachable!(),
+        };
+        let before = context.min(old_start).min(new_start);
+        let old_range = old_start - before..(old_start + old_len + context).min(old_lines.len());
+        let new_range = new_start - before..(new_start + new_len + context).min(new_lines.len());
+
+        match hunks.last_mut() {
+            Some(last) if last.old_lines.end >= old_range.start => {
+                last.old_lines.end = last.old_lines.end.max(old_range.end);
+                last.new_lines.end = last.new_lines.end.max(new_range.end);
+            }
+            _ => hunks.push(Hunk {
+                old_lines: old_range,
+                new_lines: new_range,
+                ops: Vec::new(),
+            }),
+        }
+    }
+
+    for hunk in hunks.iter_mut() {
+        hunk.ops = diff_slices(
+            &old_lines[hunk.old_lines.clone()],
+            &new_lines[hunk.new_lines.clone()],
+        );
+    }
+    hunks
+}
+
+/// Renders the differences between two texts, in unified format for [`DiffMode::Line`]
+/// or with intra-line highlighting for [`DiffMode::Word`] and [`DiffMode::Char`]
+pub fn render(old: &str, new: &str, options: &DiffOptions) -> String {
+    let old_lines = split_lines(old);
+    let new_lines = split_lines(new);
+    let mut out = String::new();
+
+    for hunk in hunks(old, new, options.context) {
+        out.push_str(&format!(
+            "@@ -{},{} +{},{} @@\n",
+            hunk.old_lines.start + 1,
+            hunk.old_lines.len(),
+            hunk.new_lines.start + 1,
+            hunk.new_lines.len()
+        ));
+        let old_hunk = &old_lines[hunk.old_lines.clone()];
+        let new_hunk = &new_lines[hunk.new_lines.clone()];
+
+        let mut i = 0;
+        while i < hunk.ops.len() {
+            if let DiffOp::Equal { old, len, .. } = hunk.ops[i] {
+                for line in &old_hunk[old..old + len] {
+                    if options.mode == DiffMode::Line {
+                        out.push(' ');
+                    }
+                    push_line(&mut out, line);
+                    if options.mode == DiffMode::Line {
+                        push_newline_marker(&mut out, line);
+                    }
+                }
+                i += 1;
+                continue;
+            }
+
+            let (mut removed, mut added) = (Vec::new(), Vec::new());
+            while let Some(op) = hunk.ops.get(i) {
+                match *op {
+                    DiffOp::Delete { old, len } => removed.extend(&old_hunk[old..old + len]),
+                    DiffOp::Insert { new, len } => added.extend(&new_hunk[new..new + len]),
+                    DiffOp::Equal { .. } => break,
+                }
+                i += 1;
+            }
+
+            if options.mode == DiffMode::Line {
+                for line in removed {
+                    out.push_str(&paint("-", line, "31", options.color));
+                    push_line(&mut out, "");
+                    push_newline_marker(&mut out, line);
+                }
+                for line in added {
+                    out.push_str(&paint("+", line, "32", options.color));
+                    push_line(&mut out, "");
+                    push_newline_marker(&mut out, line);
+                }
+            } else {
+                let segments = inline_diff(&removed.concat(), &added.concat(), options.mode);
+                out.push_str(&render_segments(&segments, options.color));
+                if !out.ends_with('\n') {
+                    out.push('\n');
+                }
+            }
+        }
+    }
+    out
+}
+
+/// Renders inline segments, either colored or with `[-removed-]{+added+}` markers
+pub fn render_segments(segments: &[DiffSegment], color: bool) -> String {
+    segments
+        .iter()
+        .map(|segment| match (segment.kind, color) {
+            (SegmentKind::Equal, _) => segment.text.clone(),
+            (SegmentKind::Removed, true) => format!("\x1b[31m{}\x1b[0m", segment.text),
+            (SegmentKind::Added, true) => format!("\x1b[32m{}\x1b[0m", segment.text),
+            (SegmentKind::Removed, false) => format!("[-{}-]", segment.text),
+            (SegmentKind::Added, false) => format!("{{+{}+}}", segment.text),
+        })
+        .collect()
+}
+
+fn paint(prefix: &str, line: &str, color_code: &str, color: bool) -> String {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    if color {
+        format!("\x1b[{}m{}{}\x1b[0m", color_code, prefix, line)
+    } else {
+        format!("{}{}", prefix, line)
+    }
+}
+
+fn push_line(out: &mut String, line: &str) {
+    out.push_str(line);
+    if !line.ends_with('\n') {
+        out.push('\n');
+    }
+}
+
+/// Tells in a unified diff that the last line of a text has no line ending
+fn push_newline_marker(out: &mut String, line: &str) {
+    if !line.ends_with('\n') {
+        out.push_str("\\ No newline at end of file\n");
+    }
+}
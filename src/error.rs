@@ -1,4 +1,7 @@
-use actix_web::{http::header::ContentType, HttpResponse};
+use actix_web::{
+    http::header::{ContentType, WWW_AUTHENTICATE},
+    HttpResponse,
+};
 use reqwest::StatusCode;
 use serde::Serialize;
 use std::{fmt::Display, path::PathBuf, str::Utf8Error};
@@ -10,24 +13,57 @@ pub enum Error {
     RepoFetchFailed(String),
     HostError(HostErrorKind),
     CommitParseFailed(CommitParseFailedReason),
+    RefError(RefErrorReason),
+    OperationError(OperationErrorReason),
+    /// The operation would overwrite changes which are not committed
+    UncommittedChanges,
+    /// Paths of the files which were written with conflict markers
+    MergeConflicts(Vec<String>),
+    /// A stopped cherry-pick, revert, rebase or merge must be continued or aborted first
+    InProgress(String),
+    /// There is no stopped cherry-pick, revert, rebase or merge to continue or abort
+    NothingInProgress,
+    StashError(StashErrorReason),
+    RebaseError(RebaseErrorReason),
+    BisectError(BisectErrorReason),
+    /// A search pattern which isn't a valid regular expression
+    InvalidPattern(String),
+    PushError(PushErrorReason),
+    FetchError(FetchErrorReason),
+    PullError(PullErrorReason),
+    /// The remote couldn't be reached or answered with an error
+    RemoteError(String),
+    RemoteConfigError(RemoteConfigErrorReason),
+    BundleError(BundleErrorReason),
+    AuthError(AuthErrorReason),
     SerdeError(String),
     Unknown(String),
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(self.to_string().as_str())
+        write!(f, "{:?}", self)
     }
 }
 
 impl actix_web::error::ResponseError for Error {
     fn error_response(&self) -> actix_web::HttpResponse<actix_web::body::BoxBody> {
-        HttpResponse::build(match self {
+        let status = match self {
             Error::HostError(HostErrorKind::RepoNotFound) => StatusCode::NOT_FOUND,
+            Error::PushError(_) | Error::FetchError(_) => StatusCode::BAD_REQUEST,
+            Error::AuthError(
+                AuthErrorReason::MissingCredentials | AuthErrorReason::InvalidCredentials,
+            ) => StatusCode::UNAUTHORIZED,
+            Error::AuthError(AuthErrorReason::PermissionDenied(_)) => StatusCode::FORBIDDEN,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
-        })
-        .insert_header(ContentType::json())
-        .body(serde_json::to_string(self).unwrap())
+        };
+        let mut response = HttpResponse::build(status);
+        if status == StatusCode::UNAUTHORIZED {
+            response.insert_header((WWW_AUTHENTICATE, "Basic realm=\"fursion\""));
+        }
+        response
+            .insert_header(ContentType::json())
+            .body(serde_json::to_string(self).unwrap())
     }
 }
 
@@ -51,14 +87,19 @@ macro_rules! impl_from_error {
 impl_from_error!(Utf8Error);
 impl_from_error!(std::io::Error);
 impl_from_error!(serde_json::Error, SerdeError);
+impl_from_error!(regex::Error, InvalidPattern);
 
 #[derive(Debug, Serialize)]
 pub enum RepoErrorReason {
     RepoAlreadyExists(PathBuf),
     CantCreateFile(PathBuf, String),
     CantReadFile(PathBuf, String),
+    /// Only text files encoded in UTF-8 can be tracked
+    FileIsNotText(PathBuf),
     PathNotFound(PathBuf),
     DirIsNotAFursionRepo(PathBuf),
+    /// A clone is only made in a missing or empty directory
+    DirIsNotEmpty(PathBuf),
     FailedToReadFileMetadata(String),
     CantInitAtRootDiskLocation,
 }
@@ -66,12 +107,150 @@ pub enum RepoErrorReason {
 #[derive(Debug, Serialize)]
 pub enum HostErrorKind {
     RepoNotFound,
-    StateMutexThreadLocked,
+    /// A task run on the blocking thread pool panicked
+    TaskFailed,
 }
 
 #[derive(Debug, Serialize)]
 pub enum CommitParseFailedReason {
     CommitIdParseFailed,
+    CommitDataMalformed,
     FileChangeDataNotFound,
     FileChangeDataMalformed,
 }
+
+#[derive(Debug, Serialize)]
+pub enum RefErrorReason {
+    InvalidRefName(String),
+    BranchNotFound(String),
+    BranchAlreadyExists(String),
+    CantDeleteCurrentBranch(String),
+    TagNotFound(String),
+    TagAlreadyExists(String),
+    TagMalformed(String),
+    ReflogMalformed(String),
+    UnknownRevision(String),
+    /// A short commit id matching several commits, along with these commits
+    AmbiguousRevision(String, Vec<String>),
+    /// A revision expression which can't be parsed
+    InvalidRevision(String),
+    /// The revision goes past the parents of a commit
+    ParentNotFound(String, String),
+    /// A ref has fewer previous values than asked for
+    ReflogEntryNotFound(String, usize),
+    /// The path does not exist in the given commit
+    PathNotInRevision(String, String),
+    /// HEAD points to a branch which has no commit yet
+    NoCommitYet,
+}
+
+#[derive(Debug, Serialize)]
+pub enum OperationErrorReason {
+    OperationNotFound(String),
+    /// The operation log does not go further back
+    NothingToUndo,
+    /// The last operation is not an undo
+    NothingToRedo,
+}
+
+#[derive(Debug, Serialize)]
+pub enum StashErrorReason {
+    /// There are neither stated changes nor changes in the working directory
+    NothingToStash,
+    StashEntryNotFound(usize),
+    StashMalformed(String),
+}
+
+#[derive(Debug, Serialize)]
+pub enum RebaseErrorReason {
+    /// A line of a todo plan which can't be parsed
+    InvalidTodoLine(String),
+    /// The todo plan starts with a squash or a fixup
+    NothingToSquashInto,
+}
+
+#[derive(Debug, Serialize)]
+pub enum BisectErrorReason {
+    /// The command of a bisect run could not be started or was killed
+    CommandFailed(String),
+    /// The bad commit is one of the good ones or an ancestor of one, so no commit
+    /// between them can be the first bad one
+    BadIsAncestorOfGood(String),
+}
+
+#[derive(Debug, Serialize)]
+pub enum PushErrorReason {
+    /// A pushed commit or one of its parents is neither sent nor known by the server
+    MissingCommit(String),
+    /// A pushed commit has the id of another commit of the server
+    CommitIdCollision(String),
+    /// There is no checked out branch to push by default
+    DetachedHead,
+}
+
+#[derive(Debug, Serialize)]
+pub enum FetchErrorReason {
+    /// A wanted commit or the parent of a received one is unknown
+    MissingCommit(String),
+    /// A received commit has the id of another commit of the repo
+    CommitIdCollision(String),
+    /// A pack which can't be read, along with what is wrong
+    PackMalformed(String),
+}
+
+#[derive(Debug, Serialize)]
+pub enum PullErrorReason {
+    /// There is no checked out branch to pull into
+    DetachedHead,
+    /// The remote of the upstream of the branch isn't registered
+    NoUpstream(String),
+    /// The remote-tracking branch of the upstream wasn't fetched
+    UpstreamNotFetched(String),
+    /// The branch has commits the upstream doesn't have and only fast-forwards are allowed
+    NotFastForward(String),
+}
+
+#[derive(Debug, Serialize)]
+pub enum RemoteConfigErrorReason {
+    NotFound(String),
+    AlreadyExists(String),
+    /// Remote names can't contain `/` and must be valid ref names
+    InvalidName(String),
+    InvalidRefspec(String),
+    /// No transport is registered for the scheme of a url
+    UnsupportedScheme(String),
+}
+
+#[derive(Debug, Serialize)]
+pub enum BundleErrorReason {
+    /// None of the revisions of the bundle name a branch or a tag
+    Empty,
+    /// A bundle which can't be read, along with what is wrong
+    Malformed(String),
+    /// Commits the bundle builds on which the repo doesn't have
+    MissingPrerequisites(Vec<String>),
+    /// Bundles can only be fetched from
+    ReadOnly,
+}
+
+#[derive(Debug, Serialize)]
+pub enum AuthErrorReason {
+    /// The server needs credentials and none were sent
+    MissingCredentials,
+    /// The credentials are malformed or don't match any user
+    InvalidCredentials,
+    /// The user lacks the named permission on the repo
+    PermissionDenied(String),
+    UserNotFound(String),
+    UserAlreadyExists(String),
+    TeamNotFound(String),
+    TokenNotFound(String),
+    TokenAlreadyExists(String),
+    /// A line of the credential file which can't be read
+    CredentialFileMalformed(String),
+    /// The named field, the url, token, user or password, is empty or has whitespace,
+    /// which the credential file can't hold
+    UnstorableCredential(String),
+    /// The credential helper could not be run or failed, along with its output
+    HelperFailed(String),
+}
@@ -0,0 +1,238 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commit::CommitId,
+    error::{Error, FetchErrorReason},
+    pack::Pack,
+    push::{RefUpdateResult, RefUpdateStatus},
+    refs::Refs,
+    remote::Remote,
+    repo::Repo,
+    tag::Tag,
+};
+
+/// What a client asks a server for, the server sends the commits reachable from
+/// the wanted ones but not from the ones the client has
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct FetchRequest {
+    pub wants: Vec<CommitId>,
+    /// Commits the client has along with all their ancestors, unknown ones are ignored
+    pub haves: Vec<CommitId>,
+}
+
+impl FetchRequest {
+    /// Asks for everything the branches and tags of `refs` lead to, for a repo
+    /// having the commits `haves` lead to
+    pub fn new(refs: &Refs, haves: Vec<CommitId>) -> Self {
+        let mut wants = refs
+            .branches
+            .values()
+            .copied()
+            .chain(refs.tags.values().map(Tag::target))
+            .filter(|id| !haves.contains(id))
+            .collect::<Vec<_>>();
+        wants.sort();
+        wants.dedup();
+        FetchRequest { wants, haves }
+    }
+}
+
+/// What a fetch changed
+#[derive(Debug, Clone, Default)]
+pub struct FetchReport {
+    /// Name of the fetched remote
+    pub remote: String,
+    /// Number of commits which weren't known locally
+    pub received: usize,
+    /// The moved remote-tracking branches, named `<remote>/<branch>`
+    pub updates: Vec<RefUpdateResult>,
+    /// Names of the tags which were copied
+    pub tags: Vec<String>,
+}
+
+impl FetchReport {
+    pub fn is_empty(&self) -> bool {
+        self.received == 0 && self.updates.is_empty() && self.tags.is_empty()
+    }
+}
+
+impl Repo {
+    /// Fetches every remote, see [`Repo::fetch_from`]
+    pub async fn fetch(&mut self) -> Result<Vec<FetchReport>, Error> {
+        let mut reports = Vec::new();
+        for remote in self.remotes.clone() {
+            reports.push(self.fetch_from(&remote).await?);
+        }
+        Ok(reports)
+    }
+
+    /// Downloads the commits of a remote which aren't known locally and stores where its
+    /// branches are as remote-tracking branches, the local branches are left as they are
+    pub async fn fetch_from(&mut self, remote: &Remote) -> Result<FetchReport, Error> {
+        let remote_refs = remote.list_refs().await?;
+        let request = FetchRequest::new(&remote_refs, self.refs.tips());
+        let pack = if request.wants.iter().all(|id| self.history.contains(id)) {
+            Pack::default()
+        } else {
+            remote.fetch_pack(&request).await?
+        };
+
+        let report = self.store_fetch(remote, &remote_refs, pack)?;
+        if !report.is_empty() {
+            self.record_operation(&format!("fetch from {}", remote.name))?;
+        }
+        Ok(report)
+    }
+
+    /// The commits reachable from the wanted ones but not from the ones the client has,
+    /// parents first
+    pub fn pack_missing(&self, request: &FetchRequest) -> Result<Pack, Error> {
+        if let Some(id) = request.wants.iter().find(|id| !self.history.contains(id)) {
+            return Err(Error::FetchError(FetchErrorReason::MissingCommit(
+                id.to_hex(),
+            )));
+        }
+        let haves = request
+            .haves
+            .iter()
+            .filter(|id| self.history.contains(id))
+            .copied()
+            .collect::<Vec<_>>();
+        let known = self.history.ancestors_of_all(&haves);
+
+        let commits = self
+            .history
+            .walk(&request.wants)
+            .into_iter()
+            .rev()
+            .filter(|commit| !known.contains(&commit.id))
+            .cloned()
+            .collect();
+        Ok(Pack { commits })
+    }
+
+    /// Stores the commits of a pack and updates the remote-tracking branches of `remote`
+    /// to the branches of `remote_refs` as its refspecs say, removing the ones of deleted
+    /// branches, and copies the new tags, the refs with invalid names being rejected
+    pub(crate) fn store_fetch(
+        &mut self,
+        remote: &Remote,
+        remote_refs: &Refs,
+        pack: Pack,
+    ) -> Result<FetchReport, Error> {
+        let mut report = FetchReport {
+            remote: remote.name.clone(),
+            ..Default::default()
+        };
+        for commit in pack.commits {
+            // Ids are short enough for the commits of different repos to share one
+            match self.history.get(&commit.id) {
+                Some(known) if *known == commit => continue,
+                Some(_) => {
+                    return Err(Error::FetchError(FetchErrorReason::CommitIdCollision(
+                        commit.id.to_hex(),
+                    )))
+                }
+                None => {}
+            }
+            if let Some(parent) = commit
+                .parents
+                .iter()
+                .find(|parent| !self.history.contains(parent))
+            {
+                return Err(Error::FetchError(FetchErrorReason::MissingCommit(
+                    parent.to_hex(),
+                )));
+            }
+            self.history.push(commit);
+            report.received += 1;
+        }
+        if let Some(id) = FetchRequest::new(remote_refs, Vec::new())
+            .wants
+            .iter()
+            .find(|id| !self.history.contains(id))
+        {
+            return Err(Error::FetchError(FetchErrorReason::MissingCommit(
+                id.to_hex(),
+            )));
+        }
+
+        let tracked = remote_refs
+            .branches
+            .iter()
+            .filter_map(|(branch, id)| {
+                let (tracking, force) = remote.tracking_name(branch)?;
+                let valid = Refs::check_name(branch).is_ok() && Refs::check_name(&tracking).is_ok();
+                Some((tracking, force, *id, valid))
+            })
+            .collect::<Vec<_>>();
+        let stale = self
+            .refs
+            .remotes
+            .keys()
+            .filter(|tracking| {
+                remote.is_tracking(tracking)
+                    && !tracked.iter().any(|(name, _, _, _)| name == *tracking)
+            })
+            .cloned()
+            .collect::<Vec<_>>();
+        for tracking in stale {
+            let old = self.refs.remotes.remove(&tracking);
+            report.updates.push(RefUpdateResult {
+                name: tracking,
+                old,
+                new: None,
+                status: RefUpdateStatus::Updated,
+            });
+        }
+        for (tracking, force, id, valid) in tracked {
+            let old = self.refs.remotes.get(&tracking).copied();
+            let status = match old {
+                _ if !valid => RefUpdateStatus::RejectedInvalidName,
+                Some(old) if old == id => continue,
+                Some(old) if self.history.is_ancestor(&old, &id) => RefUpdateStatus::Updated,
+                Some(_) if force => RefUpdateStatus::ForcedUpdate,
+                Some(_) => RefUpdateStatus::RejectedNonFastForward,
+                None => RefUpdateStatus::Updated,
+            };
+            if !status.is_rejected() {
+                self.refs.remotes.insert(tracking.clone(), id);
+            }
+            report.updates.push(RefUpdateResult {
+                name: tracking,
+                old,
+                new: Some(id),
+                status,
+            });
+        }
+
+        let known_tags: HashSet<&String> = self.refs.tags.keys().collect();
+        let new_tags = remote_refs
+            .tags
+            .iter()
+            .filter(|(tag_name, _)| !known_tags.contains(tag_name))
+            .map(|(tag_name, tag)| (tag_name.clone(), tag.clone()))
+            .collect::<Vec<_>>();
+        for (tag_name, tag) in new_tags {
+            if Refs::check_name(&tag_name).is_err() {
+                report.updates.push(RefUpdateResult {
+                    name: format!("{}/{}", Tag::DIR_NAME, tag_name),
+                    old: None,
+                    new: Some(tag.target()),
+                    status: RefUpdateStatus::RejectedInvalidName,
+                });
+                continue;
+            }
+            self.refs.tags.insert(tag_name.clone(), tag);
+            report.tags.push(tag_name);
+        }
+
+        if report.received > 0 {
+            self.save_history()?;
+        }
+        self.save_refs()?;
+        Ok(report)
+    }
+}
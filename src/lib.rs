@@ -2,11 +2,39 @@
 //No code in there just exports of the crate lib//
 //////////////////////////////////////////////////
 
+pub mod access;
+pub mod auth;
+pub mod bisect;
+pub mod blame;
+pub mod bundle;
+pub mod cherry_pick;
+pub mod clone;
 pub mod commit;
+pub mod config;
+pub mod credential;
+pub mod date;
+pub mod diff;
 pub mod error;
+pub mod fetch;
+pub mod log;
+pub mod merge;
+pub mod operation;
+pub mod pack;
+pub mod pull;
+pub mod push;
+pub mod rebase;
+pub mod reflog;
+pub mod refs;
 pub mod remote;
 pub mod repo;
+pub mod reset;
+pub mod revision;
+pub mod search;
 pub mod server;
+pub mod stash;
+pub mod tag;
+pub mod transport;
+pub mod tree;
 
 #[cfg(test)]
 mod test;
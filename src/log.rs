@@ -0,0 +1,359 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::{
+    commit::{Commit, CommitId},
+    date,
+    error::Error,
+    refs::Head,
+    repo::{Repo, RepoHistory},
+    revision::RevisionSet,
+    tree,
+};
+
+/// Filters applied when walking the commit history
+#[derive(Debug, Clone, Default)]
+pub struct LogQuery {
+    /// Only commits whose author contains this text
+    pub author: Option<String>,
+    /// Only commits made at or after this timestamp
+    pub since: Option<u64>,
+    /// Only commits made at or before this timestamp
+    pub until: Option<u64>,
+    /// Only commits whose message contains this text
+    pub grep: Option<String>,
+    /// Only commits touching one of these files or directories
+    pub paths: Vec<String>,
+    /// Stops after this many commits
+    pub max_count: Option<usize>,
+}
+
+impl LogQuery {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn author(mut self, author: &str) -> Self {
+        self.author = Some(author.to_owned());
+        self
+    }
+
+    pub fn since(mut self, timestamp: u64) -> Self {
+        self.since = Some(timestamp);
+        self
+    }
+
+    pub fn until(mut self, timestamp: u64) -> Self {
+        self.until = Some(timestamp);
+        self
+    }
+
+    pub fn grep(mut self, text: &str) -> Self {
+        self.grep = Some(text.to_owned());
+        self
+    }
+
+    pub fn path(mut self, path: &str) -> Self {
+        self.paths.push(path.to_owned());
+        self
+    }
+
+    pub fn max_count(mut self, count: usize) -> Self {
+        self.max_count = Some(count);
+        self
+    }
+
+    /// Whether a commit passes every filter
+    pub fn matches(&self, commit: &Commit) -> bool {
+        self.author
+            .as_ref()
+            .is_none_or(|author| commit.author.contains(author.as_str()))
+            && self.since.is_none_or(|since| commit.timestamp >= since)
+            && self.until.is_none_or(|until| commit.timestamp <= until)
+            && self
+                .grep
+                .as_ref()
+                .is_none_or(|text| commit.message.contains(text.as_str()))
+            && (self.paths.is_empty()
+                || commit.changes().iter().any(|changes| {
+                    self.paths
+                        .iter()
+                        .any(|path| tree::path_matches(changes.path(), path))
+                }))
+    }
+}
+
+impl RepoHistory {
+    /// Every commit reachable from `starts`, children always coming before their parents
+    /// and the most recent commits first otherwise
+    pub fn walk(&self, starts: &[CommitId]) -> Vec<&Commit> {
+        let mut reachable: HashMap<CommitId, &Commit> = HashMap::new();
+        let mut stack: Vec<CommitId> = starts.to_vec();
+        while let Some(id) = stack.pop() {
+            if reachable.contains_key(&id) {
+                continue;
+            }
+            if let Some(commit) = self.get(&id) {
+                reachable.insert(id, commit);
+                stack.extend(commit.parents.iter().copied());
+            }
+        }
+
+        let mut children_left: HashMap<CommitId, usize> = HashMap::new();
+        for commit in reachable.values() {
+            for parent in commit.parents.iter().collect::<HashSet<_>>() {
+                *children_left.entry(*parent).or_default() += 1;
+            }
+        }
+
+        let mut sequence = 0;
+        let mut ready = BinaryHeap::new();
+        let mut queued = HashSet::new();
+        for id in starts {
+            if reachable.contains_key(id)
+                && children_left.get(id).copied().unwrap_or(0) == 0
+                && queued.insert(*id)
+            {
+                sequence += 1;
+                ready.push((reachable[id].timestamp, sequence, *id));
+            }
+        }
+
+        let mut commits = Vec::with_capacity(reachable.len());
+        while let Some((_, _, id)) = ready.pop() {
+            let commit = reachable[&id];
+            commits.push(commit);
+            for parent in commit.parents.iter().collect::<HashSet<_>>() {
+                let Some(count) = children_left.get_mut(parent) else {
+                    continue;
+                };
+                *count -= 1;
+                if *count == 0 && reachable.contains_key(parent) {
+                    sequence += 1;
+                    ready.push((reachable[parent].timestamp, sequence, *parent));
+                }
+            }
+        }
+        commits
+    }
+
+    /// Walks the commits of a revision set keeping the ones matching the query
+    pub fn query(&self, set: &RevisionSet, query: &LogQuery) -> Vec<&Commit> {
+        let excluded = self.ancestors_of_all(&set.exclude);
+        self.walk(&set.include)
+            .into_iter()
+            .filter(|commit| !excluded.contains(&commit.id) && query.matches(commit))
+            .take(query.max_count.unwrap_or(usize::MAX))
+            .collect()
+    }
+}
+
+impl Repo {
+    /// Commits of the given revision expressions, HEAD if there is none, matching the query,
+    /// see [`Repo::resolve_set`] for the expressions
+    pub fn log(&self, revs: &[&str], query: &LogQuery) -> Result<Vec<&Commit>, Error> {
+        let mut set = self.resolve_set(revs)?;
+        if set.include.is_empty() {
+            set.include.extend(self.head());
+        }
+        Ok(self.history.query(&set, query))
+    }
+
+    /// Names of the refs pointing to a commit, like `HEAD -> main, feature`
+    pub fn decorations(&self, id: &CommitId) -> String {
+        let mut names = self.refs.names_of(id);
+        match &self.refs.head {
+            Head::Branch(branch) => {
+                if let Some(name) = names.iter_mut().find(|name| *name == branch) {
+                    *name = format!("HEAD -> {}", name);
+                }
+            }
+            Head::Detached(head) if head == id => names.insert(0, "HEAD".to_owned()),
+            Head::Detached(_) => {}
+        }
+        names.join(", ")
+    }
+}
+
+/// How much of each commit is shown
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// The short id and the first line of the message
+    OneLine,
+    /// The id, parents, author, date and whole message
+    #[default]
+    Full,
+}
+
+/// Renders commits, listed children first, optionally with an ascii graph of the branches
+pub fn render(repo: &Repo, commits: &[&Commit], format: LogFormat, graph: bool) -> String {
+    let mut out = String::new();
+    let mut lanes = Lanes::new(commits);
+
+    for commit in commits {
+        let text = render_commit(repo, commit, format);
+        if !graph {
+            out.push_str(&text);
+            continue;
+        }
+
+        let column = lanes.column_of(commit);
+        let continues = commit.parents.iter().any(|id| lanes.shown.contains(id));
+        for (i, line) in text.lines().enumerate() {
+            let mark = match (i, continues) {
+                (0, _) => '*',
+                (_, true) => '|',
+                (_, false) => ' ',
+            };
+            let row = format!("{} {}", lanes.row(column, mark), line);
+            out.push_str(row.trim_end());
+            out.push('\n');
+        }
+        for line in lanes.advance(column, commit) {
+            out.push_str(&line);
+            out.push('\n');
+        }
+    }
+    out
+}
+
+fn render_commit(repo: &Repo, commit: &Commit, format: LogFormat) -> String {
+    let decorations = repo.decorations(&commit.id);
+    let decorations = if decorations.is_empty() {
+        String::new()
+    } else {
+        format!(" ({})", decorations)
+    };
+
+    match format {
+        LogFormat::OneLine => format!(
+            "{}{} {}\n",
+            commit.id.to_hex(),
+            decorations,
+            commit.summary()
+        ),
+        LogFormat::Full => {
+            let mut text = format!("commit {}{}\n", commit.id.to_hex(), decorations);
+            if commit.is_merge() {
+                let parents = commit
+                    .parents
+                    .iter()
+                    .map(CommitId::to_hex)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                text.push_str(&format!("Merge: {}\n", parents));
+            }
+            text.push_str(&format!("Author: {}\n", commit.author));
+            text.push_str(&format!(
+                "Date:   {}\n\n",
+                date::format_timestamp(commit.timestamp)
+            ));
+            for line in commit.message.lines() {
+                text.push_str(&format!("    {}\n", line));
+            }
+            text.push('\n');
+            text
+        }
+    }
+}
+
+/// State of the ascii graph, each lane waits for the commit it leads to
+struct Lanes {
+    lanes: Vec<CommitId>,
+    shown: HashSet<CommitId>,
+}
+
+impl Lanes {
+    fn new(commits: &[&Commit]) -> Self {
+        Lanes {
+            lanes: Vec::new(),
+            shown: commits.iter().map(|commit| commit.id).collect(),
+        }
+    }
+
+    /// The lane waiting for a commit, a new one is opened if none is
+    fn column_of(&mut self, commit: &Commit) -> usize {
+        match self.lanes.iter().position(|id| *id == commit.id) {
+            Some(column) => column,
+            None => {
+                self.lanes.push(commit.id);
+                self.lanes.len() - 1
+            }
+        }
+    }
+
+    /// A `|` for every lane, except `mark` in `column`
+    fn row(&self, column: usize, mark: char) -> String {
+        (0..self.lanes.len())
+            .map(|i| if i == column { mark } else { '|' }.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Replaces the lane of a commit by its parents, giving back the lines drawing
+    /// the lanes which are forked, joined or ended
+    fn advance(&mut self, column: usize, commit: &Commit) -> Vec<String> {
+        let mut lines = Vec::new();
+        let parents: Vec<CommitId> = commit
+            .parents
+            .iter()
+            .filter(|id| self.shown.contains(id))
+            .copied()
+            .collect();
+
+        match parents.split_first() {
+            None => {
+                self.lanes.remove(column);
+                if column < self.lanes.len() {
+                    lines.push(self.shift_line(column, false));
+                }
+            }
+            Some((first, others)) => {
+                self.lanes[column] = *first;
+                for (i, parent) in others.iter().enumerate() {
+                    self.lanes.insert(column + 1 + i, *parent);
+                    lines.push(self.fork_line(column + 1 + i));
+                }
+            }
+        }
+
+        // Joins the lanes which wait for the same commit
+        let mut i = 1;
+        while i < self.lanes.len() {
+            if self.lanes[..i].contains(&self.lanes[i]) {
+                self.lanes.remove(i);
+                lines.push(self.shift_line(i, true));
+            } else {
+                i += 1;
+            }
+        }
+        lines
+    }
+
+    /// Line drawn once the lane at `from` was removed, the next ones moving to the left,
+    /// `joined` tells if the removed lane goes into the previous one
+    fn shift_line(&self, from: usize, joined: bool) -> String {
+        let mut chars = vec![' '; self.lanes.len() * 2 + 2];
+        for lane in 0..from {
+            chars[lane * 2] = '|';
+        }
+        if joined {
+            chars[from * 2 - 1] = '/';
+        }
+        for lane in from..self.lanes.len() {
+            chars[lane * 2 + 1] = '/';
+        }
+        chars.into_iter().collect::<String>().trim_end().to_owned()
+    }
+
+    /// Line drawn once a lane was inserted at `column`, the next ones moving to the right
+    fn fork_line(&self, column: usize) -> String {
+        let mut chars = vec![' '; self.lanes.len() * 2];
+        for lane in 0..column {
+            chars[lane * 2] = '|';
+        }
+        for lane in column..self.lanes.len() {
+            chars[lane * 2 - 1] = '\\';
+        }
+        chars.into_iter().collect::<String>().trim_end().to_owned()
+    }
+}
@@ -0,0 +1,368 @@
+use std::{collections::BTreeSet, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commit::CommitId,
+    diff::{self, DiffOp},
+    error::Error,
+    repo::{Repo, FURSION_DIR},
+    tree::{self, Tree},
+};
+
+pub const CONFLICT_START: &str = "<<<<<<<";
+pub const CONFLICT_SEPARATOR: &str = "=======";
+pub const CONFLICT_END: &str = ">>>>>>>";
+
+/// Names shown on the conflict markers of each side
+#[derive(Debug, Clone, Copy)]
+pub struct MergeLabels<'a> {
+    pub ours: &'a str,
+    pub theirs: &'a str,
+}
+
+/// Files merged from two sides, the conflicting ones containing conflict markers
+#[derive(Debug, Clone, Default)]
+pub struct MergeResult {
+    pub tree: Tree,
+    /// Paths of the files which could not be merged cleanly
+    pub conflicts: Vec<String>,
+}
+
+impl MergeResult {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+}
+
+/// Lines of the base replaced by some lines of one side
+#[derive(Debug, Clone, Copy)]
+struct Change {
+    base_start: usize,
+    base_end: usize,
+    start: usize,
+    end: usize,
+}
+
+/// Groups the line diff of `base` and `side` into the regions of the base which were changed
+fn changes(base: &[&str], side: &[&str]) -> Vec<Change> {
+    let mut changes: Vec<Change> = Vec::new();
+    let (mut base_position, mut position) = (0, 0);
+    for op in diff::diff_slices(base, side) {
+        let (base_start, start) = (base_position, position);
+        match op {
+            DiffOp::Equal { len, .. } => {
+                base_position += len;
+                position += len;
+                continue;
+            }
+            DiffOp::Delete { len, .. } => base_position += len,
+            DiffOp::Insert { len, .. } => position += len,
+        }
+        let (base_end, end) = (base_position, position);
+        match changes.last_mut() {
+            Some(last) if last.base_end == base_start && last.end == start => {
+                last.base_end = base_end;
+                last.end = end;
+            }
+            _ => changes.push(Change {
+                base_start,
+                base_end,
+                start,
+                end,
+            }),
+        }
+    }
+    changes
+}
+
+/// Lines of one side covering the base lines `start..end` which contain all the given changes
+fn side_lines<'a>(side: &[&'a str], changes: &[Change], start: usize, end: usize) -> Vec<&'a str> {
+    match (changes.first(), changes.last()) {
+        (Some(first), Some(last)) => {
+            let side_start = first.start - (first.base_start - start);
+            let side_end = last.end + (end - last.base_end);
+            side[side_start..side_end].to_vec()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// Merges the changes made to `base` by both sides line by line, gives back the merged text
+/// and whether there were conflicts, which are surrounded by conflict markers
+pub fn merge_text(base: &str, ours: &str, theirs: &str, labels: MergeLabels) -> (String, bool) {
+    let base_lines = diff::split_lines(base);
+    let our_lines = diff::split_lines(ours);
+    let their_lines = diff::split_lines(theirs);
+    let our_changes = changes(&base_lines, &our_lines);
+    let their_changes = changes(&base_lines, &their_lines);
+
+    let mut merged = String::new();
+    let mut conflicted = false;
+    let mut position = 0;
+    let (mut i, mut j) = (0, 0);
+    while i < our_changes.len() || j < their_changes.len() {
+        // Takes the next change and every change of either side overlapping it
+        let next_start = match (our_changes.get(i), their_changes.get(j)) {
+            (Some(a), Some(b)) => a.base_start.min(b.base_start),
+            (Some(a), None) => a.base_start,
+            (None, Some(b)) => b.base_start,
+            (None, None) => unreachable!(),
+        };
+        let (start, mut end) = (next_start, next_start);
+        let (our_first, their_first) = (i, j);
+        loop {
+            if let Some(c) = our_changes.get(i).filter(|c| c.base_start <= end) {
+                end = end.max(c.base_end);
+                i += 1;
+            } else if let Some(c) = their_changes.get(j).filter(|c| c.base_start <= end) {
+                end = end.max(c.base_end);
+                j += 1;
+            } else {
+                break;
+            }
+        }
+
+        merged.extend(base_lines[position..start].iter().copied());
+        let ours = &our_changes[our_first..i];
+        let theirs = &their_changes[their_first..j];
+        let lines = if theirs.is_empty() {
+            side_lines(&our_lines, ours, start, end)
+        } else if ours.is_empty() {
+            side_lines(&their_lines, theirs, start, end)
+        } else {
+            let ours = side_lines(&our_lines, ours, start, end);
+            let theirs = side_lines(&their_lines, theirs, start, end);
+            if ours == theirs {
+                ours
+            } else {
+                conflicted = true;
+                push_conflict(&mut merged, &ours, &theirs, labels);
+                Vec::new()
+            }
+        };
+        merged.extend(lines);
+        position = end;
+    }
+    merged.extend(base_lines[position..].iter().copied());
+    (merged, conflicted)
+}
+
+fn push_conflict(merged: &mut String, ours: &[&str], theirs: &[&str], labels: MergeLabels) {
+    let push_lines = |merged: &mut String, lines: &[&str]| {
+        for line in lines {
+            merged.push_str(line);
+        }
+        if !merged.ends_with('\n') {
+            merged.push('\n');
+        }
+    };
+    merged.push_str(&format!("{} {}\n", CONFLICT_START, labels.ours));
+    push_lines(merged, ours);
+    merged.push_str(&format!("{}\n", CONFLICT_SEPARATOR));
+    push_lines(merged, theirs);
+    merged.push_str(&format!("{} {}\n", CONFLICT_END, labels.theirs));
+}
+
+/// Merges the files changed from `base` by both sides, a file removed on one side
+/// and changed on the other is kept in its changed version and seen as conflicting
+pub fn merge_trees(base: &Tree, ours: &Tree, theirs: &Tree, labels: MergeLabels) -> MergeResult {
+    let mut result = MergeResult::default();
+    let paths: BTreeSet<&String> = base
+        .keys()
+        .chain(ours.keys())
+        .chain(theirs.keys())
+        .collect();
+    for path in paths {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+        let content = if o == t || t == b {
+            o.cloned()
+        } else if o == b {
+            t.cloned()
+        } else {
+            match (o, t) {
+                (Some(o), Some(t)) => {
+                    let b = b.map_or("", String::as_str);
+                    let (merged, conflicted) = merge_text(b, o, t, labels);
+                    if conflicted {
+                        result.conflicts.push(path.clone());
+                    }
+                    Some(merged)
+                }
+                (changed, removed) => {
+                    result.conflicts.push(path.clone());
+                    changed.or(removed).cloned()
+                }
+            }
+        };
+        if let Some(content) = content {
+            result.tree.insert(path.clone(), content);
+        }
+    }
+    result
+}
+
+/// How [`Repo::merge`] brought the other commit in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MergeOutcome {
+    /// The commit was already reachable from HEAD
+    UpToDate,
+    /// HEAD was an ancestor of the commit and simply moved to it
+    FastForward(CommitId),
+    /// A merge commit was made
+    Merged(CommitId),
+}
+
+/// A merge waiting for its conflicts to be resolved
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct PendingMerge {
+    /// The commit merged into HEAD
+    pub theirs: CommitId,
+    /// Message of the merge commit made once the conflicts are resolved
+    pub message: String,
+    /// Paths of the files written with conflict markers
+    pub conflicts: Vec<String>,
+}
+
+impl PendingMerge {
+    pub const FILE_NAME: &'static str = "MERGE_HEAD";
+
+    pub fn read(fursion_dir_path: &Path) -> Result<Option<Self>, Error> {
+        let path = fursion_dir_path.join(Self::FILE_NAME);
+        if !Path::exists(&path) {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+    }
+
+    pub fn save(&self, fursion_dir_path: &Path) -> Result<(), Error> {
+        fs::write(
+            fursion_dir_path.join(Self::FILE_NAME),
+            serde_json::to_string(self)?,
+        )?;
+        Ok(())
+    }
+
+    pub fn delete(fursion_dir_path: &Path) -> Result<(), Error> {
+        let path = fursion_dir_path.join(Self::FILE_NAME);
+        if Path::exists(&path) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Repo {
+    /// Brings a commit into HEAD, by moving HEAD forward to it when possible or else by
+    /// making a merge commit with `message`, stops with [`Error::MergeConflicts`] leaving
+    /// the conflicting files to be resolved before [`Repo::merge_continue`]
+    pub fn merge(&mut self, theirs: CommitId, message: &str) -> Result<MergeOutcome, Error> {
+        self.check_nothing_in_progress()?;
+        if self.has_uncommitted_changes()? {
+            return Err(Error::UncommittedChanges);
+        }
+        self.history.find(&theirs)?;
+
+        let Some(ours) = self.head() else {
+            self.fast_forward(theirs, &format!("merge {}", theirs.to_hex()))?;
+            return Ok(MergeOutcome::FastForward(theirs));
+        };
+        if self.history.is_ancestor(&theirs, &ours) {
+            return Ok(MergeOutcome::UpToDate);
+        }
+        if self.history.is_ancestor(&ours, &theirs) {
+            self.fast_forward(theirs, &format!("merge {}", theirs.to_hex()))?;
+            return Ok(MergeOutcome::FastForward(theirs));
+        }
+
+        let base = match self.history.merge_bases(&ours, &theirs).first() {
+            Some(base) => self.tree_at(base)?,
+            None => Tree::new(),
+        };
+        let head_tree = self.head_tree()?;
+        let labels = MergeLabels {
+            ours: "HEAD",
+            theirs: &theirs.to_hex(),
+        };
+        let merged = merge_trees(&base, &head_tree, &self.tree_at(&theirs)?, labels);
+        tree::write_working_tree(&self.path, &head_tree, &merged.tree)?;
+        self.reread_files()?;
+
+        if !merged.is_clean() {
+            PendingMerge {
+                theirs,
+                message: message.to_owned(),
+                conflicts: merged.conflicts.clone(),
+            }
+            .save(&self.path.join(FURSION_DIR))?;
+            self.record_operation(&format!("merge (conflicts): {}", theirs.to_hex()))?;
+            return Err(Error::MergeConflicts(merged.conflicts));
+        }
+        let changes = tree::diff_trees(&head_tree, &merged.tree);
+        let author = self.metadata.author.clone();
+        let id = self.commit_changes(message, &author, vec![ours, theirs], changes, "merge")?;
+        Ok(MergeOutcome::Merged(id))
+    }
+
+    /// The merge stopped on conflicts, if any
+    pub fn pending_merge(&self) -> Result<Option<PendingMerge>, Error> {
+        PendingMerge::read(&self.path.join(FURSION_DIR))
+    }
+
+    /// Makes the merge commit of a stopped merge once the conflict markers are removed
+    pub fn merge_continue(&mut self) -> Result<CommitId, Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        let pending = PendingMerge::read(&fursion_dir)?.ok_or(Error::NothingInProgress)?;
+        let unresolved = self.unresolved_conflicts(&pending.conflicts)?;
+        if !unresolved.is_empty() {
+            return Err(Error::MergeConflicts(unresolved));
+        }
+
+        let changes = tree::diff_trees(&self.head_tree()?, &self.working_tree()?);
+        self.stated_changes.clear();
+        let author = self.metadata.author.clone();
+        let parents = self.head().into_iter().chain([pending.theirs]).collect();
+        let id = self.commit_changes(&pending.message, &author, parents, changes, "merge")?;
+        PendingMerge::delete(&fursion_dir)?;
+        Ok(id)
+    }
+
+    /// Gives up a stopped merge, putting back the files as they are at HEAD
+    pub fn merge_abort(&mut self) -> Result<(), Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        PendingMerge::read(&fursion_dir)?.ok_or(Error::NothingInProgress)?;
+
+        tree::write_working_tree(&self.path, &self.working_tree()?, &self.head_tree()?)?;
+        self.reread_files()?;
+        self.stated_changes.clear();
+        self.save_stated_changes()?;
+        PendingMerge::delete(&fursion_dir)?;
+        self.record_operation("merge (abort)")?;
+        Ok(())
+    }
+
+    /// The files among `paths` which still contain conflict markers
+    pub(crate) fn unresolved_conflicts(&self, paths: &[String]) -> Result<Vec<String>, Error> {
+        let working = self.working_tree()?;
+        Ok(paths
+            .iter()
+            .filter(|path| {
+                working.get(*path).is_some_and(|content| {
+                    content.lines().any(|line| line.starts_with(CONFLICT_START))
+                })
+            })
+            .cloned()
+            .collect())
+    }
+
+    /// Moves HEAD forward to a descendant, updating the files, the tree must be clean
+    pub(crate) fn fast_forward(&mut self, to: CommitId, reason: &str) -> Result<(), Error> {
+        let old_tree = self.head_tree()?;
+        tree::write_working_tree(&self.path, &old_tree, &self.tree_at(&to)?)?;
+        self.reread_files()?;
+        let message = format!("{}: Fast-forward", reason);
+        self.move_head(to, &message)?;
+        self.record_operation(&message)?;
+        Ok(())
+    }
+}
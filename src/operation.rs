@@ -0,0 +1,263 @@
+use std::{collections::BTreeSet, fs, mem, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commit::{self, FileChanges},
+    error::{Error, OperationErrorReason},
+    refs::Refs,
+    repo::{Repo, FURSION_DIR},
+    tree,
+};
+
+/// How an operation changed the state of the repo
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum OperationKind {
+    /// A regular call like a commit or a checkout
+    Regular,
+    /// Went back to the state before an operation, which is kept here
+    Undo(usize),
+    /// Went back to the state of an undone operation
+    Redo,
+    /// Went back to the state of any earlier operation
+    Restore,
+}
+
+/// A recorded call which changed the repo, along with the state it left it in
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Operation {
+    /// Sequence number of the operation, starting at 0
+    pub id: usize,
+    /// The operation whose state the repo was in before this one
+    pub parent: Option<usize>,
+    /// The operation whose state this one went back to, for undo, redo and restore
+    pub restored: Option<usize>,
+    pub kind: OperationKind,
+    /// What was done, like `commit: Fix typo`
+    pub description: String,
+    pub who: String,
+    /// Seconds since the unix epoch at which the operation was done
+    pub timestamp: u64,
+    /// The refs after the operation
+    pub refs: Refs,
+    /// The stated changes after the operation
+    pub stated_changes: Vec<FileChanges>,
+}
+
+impl Operation {
+    /// The operation which first reached the state this one left the repo in
+    pub fn state(&self) -> usize {
+        self.restored.unwrap_or(self.id)
+    }
+}
+
+/// Every operation done on a repo, one file per operation
+pub struct OperationLog;
+
+impl OperationLog {
+    pub const DIR_NAME: &'static str = "operations";
+
+    pub fn save(fursion_dir_path: &Path, operation: &Operation) -> Result<(), Error> {
+        let dir = fursion_dir_path.join(Self::DIR_NAME);
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join(operation.id.to_string()),
+            serde_json::to_string(operation)?,
+        )?;
+        Ok(())
+    }
+
+    pub fn get(fursion_dir_path: &Path, id: usize) -> Result<Operation, Error> {
+        let path = fursion_dir_path.join(Self::DIR_NAME).join(id.to_string());
+        if !Path::exists(&path) {
+            return Err(Error::OperationError(
+                OperationErrorReason::OperationNotFound(id.to_string()),
+            ));
+        }
+        Ok(serde_json::from_slice(&fs::read(path)?)?)
+    }
+
+    /// Ids of every operation, oldest first
+    pub fn ids(fursion_dir_path: &Path) -> Result<Vec<usize>, Error> {
+        let dir = fursion_dir_path.join(Self::DIR_NAME);
+        if !Path::exists(&dir) {
+            return Ok(Vec::new());
+        }
+        let mut ids = fs::read_dir(dir)?
+            .map(|entry| Ok(entry?.file_name().to_string_lossy().parse::<usize>().ok()))
+            .collect::<Result<Vec<_>, Error>>()?
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        ids.sort();
+        Ok(ids)
+    }
+
+    pub fn latest(fursion_dir_path: &Path) -> Result<Option<Operation>, Error> {
+        match Self::ids(fursion_dir_path)?.last() {
+            Some(id) => Self::get(fursion_dir_path, *id).map(Some),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Repo {
+    /// Every operation done on the repo, newest first
+    pub fn operations(&self) -> Result<Vec<Operation>, Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        OperationLog::ids(&fursion_dir)?
+            .into_iter()
+            .rev()
+            .map(|id| OperationLog::get(&fursion_dir, id))
+            .collect()
+    }
+
+    /// Records the current refs and stated changes as the result of an operation
+    pub(crate) fn record_operation(&self, description: &str) -> Result<usize, Error> {
+        self.record_operation_of_kind(OperationKind::Regular, None, description)
+    }
+
+    fn record_operation_of_kind(
+        &self,
+        kind: OperationKind,
+        restored: Option<usize>,
+        description: &str,
+    ) -> Result<usize, Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        let latest = OperationLog::latest(&fursion_dir)?;
+        let operation = Operation {
+            id: latest.as_ref().map_or(0, |op| op.id + 1),
+            parent: latest.as_ref().map(Operation::state),
+            restored,
+            kind,
+            description: description.to_owned(),
+            who: self.metadata.author.clone(),
+            timestamp: commit::now(),
+            refs: self.refs.clone(),
+            stated_changes: self.stated_changes.clone(),
+        };
+        OperationLog::save(&fursion_dir, &operation)?;
+        Ok(operation.id)
+    }
+
+    /// Goes back to the state before the last operation, repeated undos going further back
+    pub fn undo(&mut self) -> Result<usize, Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        let latest = OperationLog::latest(&fursion_dir)?
+            .ok_or(Error::OperationError(OperationErrorReason::NothingToUndo))?;
+
+        // A restore is undone by itself, anything else by going before the state it reached
+        let undone = match latest.kind {
+            OperationKind::Restore => latest.clone(),
+            _ => OperationLog::get(&fursion_dir, latest.state())?,
+        };
+        let target = undone
+            .parent
+            .ok_or(Error::OperationError(OperationErrorReason::NothingToUndo))?;
+
+        let target = OperationLog::get(&fursion_dir, target)?;
+        self.restore_operation_state(&target)?;
+        self.record_operation_of_kind(
+            OperationKind::Undo(undone.state()),
+            Some(target.state()),
+            &format!("undo operation {}", undone.id),
+        )
+    }
+
+    /// Goes back to the state undone by the last undo which was not redone yet
+    pub fn redo(&mut self) -> Result<usize, Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+
+        // The undos and redos done since the last other operation
+        let mut trailing = Vec::new();
+        for id in OperationLog::ids(&fursion_dir)?.into_iter().rev() {
+            let operation = OperationLog::get(&fursion_dir, id)?;
+            match operation.kind {
+                OperationKind::Undo(_) | OperationKind::Redo => trailing.push(operation.kind),
+                _ => break,
+            }
+        }
+        let mut undone = Vec::new();
+        for kind in trailing.into_iter().rev() {
+            match kind {
+                OperationKind::Undo(id) => undone.push(id),
+                _ => {
+                    undone.pop();
+                }
+            }
+        }
+
+        let target = undone
+            .pop()
+            .ok_or(Error::OperationError(OperationErrorReason::NothingToRedo))?;
+        let target = OperationLog::get(&fursion_dir, target)?;
+        self.restore_operation_state(&target)?;
+        self.record_operation_of_kind(
+            OperationKind::Redo,
+            Some(target.state()),
+            &format!("redo operation {}", target.id),
+        )
+    }
+
+    /// Goes back to the state the repo was in right after an operation
+    pub fn restore_operation(&mut self, id: usize) -> Result<usize, Error> {
+        let target = OperationLog::get(&self.path.join(FURSION_DIR), id)?;
+        self.restore_operation_state(&target)?;
+        self.record_operation_of_kind(
+            OperationKind::Restore,
+            Some(target.state()),
+            &format!("restore operation {}", id),
+        )
+    }
+
+    /// Puts back the refs and stated changes of an operation, the working directory
+    /// follows the stated state except for the files with changes which are not stated
+    fn restore_operation_state(&mut self, operation: &Operation) -> Result<(), Error> {
+        let old_index = self.index_tree()?;
+        let working = self.working_tree()?;
+        let old_refs = mem::replace(&mut self.refs, operation.refs.clone());
+        self.stated_changes = operation.stated_changes.clone();
+        let new_index = self.index_tree()?;
+
+        let mut new_working = working.clone();
+        let paths: BTreeSet<&String> = old_index.keys().chain(new_index.keys()).collect();
+        for path in paths {
+            if working.get(path) != old_index.get(path) {
+                continue;
+            }
+            match new_index.get(path) {
+                Some(content) => new_working.insert(path.clone(), content.clone()),
+                None => new_working.remove(path),
+            };
+        }
+        tree::write_working_tree(&self.path, &working, &new_working)?;
+
+        self.reread_files()?;
+
+        let message = format!("restore: operation {}", operation.id);
+        let branches: BTreeSet<&String> = old_refs
+            .branches
+            .keys()
+            .chain(self.refs.branches.keys())
+            .collect();
+        for branch in branches {
+            let (old, new) = (
+                old_refs.branches.get(branch),
+                self.refs.branches.get(branch),
+            );
+            if old != new {
+                self.log_ref_update(branch, old.copied(), new.copied(), &message)?;
+            }
+        }
+        if old_refs.head_commit() != self.head() {
+            self.log_ref_update(
+                Refs::HEAD_FILE_NAME,
+                old_refs.head_commit(),
+                self.head(),
+                &message,
+            )?;
+        }
+        self.save_refs()?;
+        self.save_stated_changes()
+    }
+}
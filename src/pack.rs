@@ -0,0 +1,74 @@
+use std::{fmt, str::FromStr};
+
+use crate::{
+    commit::Commit,
+    error::{Error, FetchErrorReason},
+};
+
+/// Commits sent at once by a server, parents always coming before their children
+///
+/// A pack is a header line with the number of commits, then every commit in its text
+/// format, each preceded by a line with its length in bytes, so that the commits are
+/// written one after another as they are found
+#[derive(Debug, Clone, Default)]
+pub struct Pack {
+    pub commits: Vec<Commit>,
+}
+
+impl Pack {
+    const HEADER: &'static str = "FURSION PACK 1";
+    pub const CONTENT_TYPE: &'static str = "application/x-fursion-pack";
+
+    pub fn header(count: usize) -> String {
+        format!("{} {}\n", Self::HEADER, count)
+    }
+
+    /// The text of a single commit in the pack
+    pub fn entry(commit: &Commit) -> String {
+        let text = commit.to_string();
+        format!("{}\n{}", text.len(), text)
+    }
+}
+
+impl fmt::Display for Pack {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&Self::header(self.commits.len()))?;
+        for commit in &self.commits {
+            f.write_str(&Self::entry(commit))?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Pack {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let malformed =
+            |reason: &str| Error::FetchError(FetchErrorReason::PackMalformed(reason.to_owned()));
+
+        let (header, mut rest) = s.split_once('\n').ok_or_else(|| malformed("no header"))?;
+        let count: usize = header
+            .strip_prefix(Self::HEADER)
+            .and_then(|count| count.trim().parse().ok())
+            .ok_or_else(|| malformed("invalid header"))?;
+
+        // The count comes from the sender, entries are only made for the commits actually read
+        let mut commits = Vec::new();
+        for _ in 0..count {
+            let (len, entry) = rest
+                .split_once('\n')
+                .ok_or_else(|| malformed("missing commit"))?;
+            let len: usize = len.parse().map_err(|_| malformed("invalid length"))?;
+            let text = entry
+                .get(..len)
+                .ok_or_else(|| malformed("truncated commit"))?;
+            commits.push(Commit::from_str(text)?);
+            rest = &entry[len..];
+        }
+        if !rest.is_empty() {
+            return Err(malformed("trailing data"));
+        }
+        Ok(Pack { commits })
+    }
+}
@@ -0,0 +1,131 @@
+use crate::{
+    commit::CommitId,
+    config::{PullMode, Upstream},
+    error::{Error, PullErrorReason},
+    fetch::FetchReport,
+    merge::MergeOutcome,
+    rebase::{RebaseOptions, RebaseStatus},
+    repo::Repo,
+};
+
+/// How a pull brought the upstream into the checked out branch
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PullOutcome {
+    /// The branch already had every commit of the upstream
+    UpToDate,
+    /// The branch was moved forward to the upstream
+    FastForward {
+        from: Option<CommitId>,
+        to: CommitId,
+    },
+    /// A merge commit of the branch and the upstream was made
+    Merged(CommitId),
+    /// The commits of the branch were replayed on the upstream, HEAD being the last one
+    Rebased(Option<CommitId>),
+    /// The merge or rebase stopped, the files written with conflict markers must be
+    /// resolved before continuing it
+    Conflicts(Vec<String>),
+}
+
+/// What a pull did
+#[derive(Debug, Clone)]
+pub struct PullReport {
+    pub fetch: FetchReport,
+    /// The remote-tracking branch pulled from, like `origin/main`
+    pub upstream: String,
+    pub mode: PullMode,
+    pub outcome: PullOutcome,
+}
+
+impl Repo {
+    /// Fetches the remote of the upstream of the checked out branch, see [`Repo::upstream`],
+    /// then brings the upstream into the branch as set by [`RepoConfig::pull`](crate::config::RepoConfig::pull)
+    pub async fn pull(&mut self) -> Result<PullReport, Error> {
+        self.pull_with(self.config()?.pull).await
+    }
+
+    /// Same as [`Repo::pull`] with another way to bring the upstream in
+    pub async fn pull_with(&mut self, mode: PullMode) -> Result<PullReport, Error> {
+        let branch = self
+            .refs
+            .current_branch()
+            .ok_or(Error::PullError(PullErrorReason::DetachedHead))?
+            .to_owned();
+        let upstream = self.upstream(&branch)?;
+        let remote = self
+            .remote(&upstream.remote)
+            .map_err(|_| Error::PullError(PullErrorReason::NoUpstream(branch.clone())))?
+            .clone();
+        // Checked before fetching so that a pull which can't go through changes nothing
+        self.check_nothing_in_progress()?;
+        if self.has_uncommitted_changes()? {
+            return Err(Error::UncommittedChanges);
+        }
+
+        let fetch = self.fetch_from(&remote).await?;
+        let outcome = self.pull_fetched(&upstream, mode)?;
+        Ok(PullReport {
+            fetch,
+            upstream: self.upstream_tracking_name(&upstream),
+            mode,
+            outcome,
+        })
+    }
+
+    /// Brings the fetched remote-tracking branch of `upstream` into the checked out branch,
+    /// conflicts stop the merge or rebase and are reported rather than failing
+    pub fn pull_fetched(
+        &mut self,
+        upstream: &Upstream,
+        mode: PullMode,
+    ) -> Result<PullOutcome, Error> {
+        let tracking = self.upstream_tracking_name(upstream);
+        let target = *self.refs.remotes.get(&tracking).ok_or_else(|| {
+            Error::PullError(PullErrorReason::UpstreamNotFetched(tracking.clone()))
+        })?;
+        self.check_nothing_in_progress()?;
+        if self.has_uncommitted_changes()? {
+            return Err(Error::UncommittedChanges);
+        }
+
+        let head = self.head();
+        match head {
+            Some(head) if self.history.is_ancestor(&target, &head) => {
+                return Ok(PullOutcome::UpToDate);
+            }
+            Some(head) if !self.history.is_ancestor(&head, &target) => {}
+            _ => {
+                self.fast_forward(target, &format!("pull {}", tracking))?;
+                return Ok(PullOutcome::FastForward {
+                    from: head,
+                    to: target,
+                });
+            }
+        }
+
+        let result = match mode {
+            PullMode::FastForwardOnly => {
+                return Err(Error::PullError(PullErrorReason::NotFastForward(tracking)));
+            }
+            PullMode::Merge => {
+                let message = format!("Merge branch '{}' of {}", upstream.branch, upstream.remote);
+                self.merge(target, &message).map(|outcome| match outcome {
+                    MergeOutcome::UpToDate => PullOutcome::UpToDate,
+                    MergeOutcome::FastForward(to) => PullOutcome::FastForward { from: head, to },
+                    MergeOutcome::Merged(id) => PullOutcome::Merged(id),
+                })
+            }
+            PullMode::Rebase => self
+                .rebase(target, &RebaseOptions::default())
+                .map(|status| match status {
+                    RebaseStatus::Finished(id) => PullOutcome::Rebased(id),
+                    // The default plan only picks, so it never stops at an edit
+                    RebaseStatus::StoppedAtEdit(id) => PullOutcome::Rebased(Some(id)),
+                }),
+        };
+        match result {
+            Err(Error::MergeConflicts(conflicts)) => Ok(PullOutcome::Conflicts(conflicts)),
+            result => result,
+        }
+    }
+}
@@ -0,0 +1,378 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commit::{Commit, CommitId},
+    error::{Error, PushErrorReason, RefErrorReason},
+    reflog::Reflog,
+    refs::{Head, Refs},
+    remote::Remote,
+    repo::{Repo, FURSION_DIR},
+    tag::Tag,
+    tree,
+};
+
+/// What to push, the checked out branch and no tag by default
+#[derive(Debug, Clone, Default)]
+pub struct PushOptions {
+    /// Branches to create or move on the remote to their local commit
+    pub branches: Vec<String>,
+    /// Branches to delete on the remote
+    pub delete: Vec<String>,
+    /// Pushes every tag
+    pub tags: bool,
+    /// Moves the branches even when the remote commit would be lost,
+    /// and replaces the tags which point elsewhere
+    pub force: bool,
+}
+
+/// A branch the client asks the server to move
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct BranchUpdate {
+    pub name: String,
+    /// Where the client last saw the branch on the server, [`None`] if it didn't exist
+    pub old: Option<CommitId>,
+    /// [`None`] deletes the branch
+    pub new: Option<CommitId>,
+}
+
+/// Everything sent to the server by a push
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct PushRequest {
+    /// The commits the server lacks, parents always coming before their children
+    pub commits: Vec<Commit>,
+    pub branches: Vec<BranchUpdate>,
+    pub tags: BTreeMap<String, Tag>,
+    pub force: bool,
+}
+
+/// What the server did with a ref
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum RefUpdateStatus {
+    /// The ref was created, moved or deleted
+    Updated,
+    /// The ref was forced over commits which are no longer reachable from it
+    ForcedUpdate,
+    /// The ref already had this value
+    UpToDate,
+    /// The new commit doesn't descend from the current one
+    RejectedNonFastForward,
+    /// The branch moved on the server since the client last saw it
+    RejectedStale,
+    /// The tag already exists and points elsewhere
+    RejectedAlreadyExists,
+    /// The branch is checked out on the server and has changes which aren't committed
+    RejectedDirtyCheckout,
+    /// The branch to delete is checked out on the server
+    RejectedCurrentBranch,
+    /// The branch is protected against this update, see
+    /// [`AccessControl`](crate::access::AccessControl)
+    RejectedProtected,
+    /// The name can't be used for a ref, see [`Refs::check_name`]
+    RejectedInvalidName,
+}
+
+impl RefUpdateStatus {
+    pub fn is_rejected(&self) -> bool {
+        !matches!(self, Self::Updated | Self::ForcedUpdate | Self::UpToDate)
+    }
+}
+
+/// The outcome of a push for a single ref
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct RefUpdateResult {
+    /// The branch name, or the tag name prefixed with `tags/`
+    pub name: String,
+    pub old: Option<CommitId>,
+    pub new: Option<CommitId>,
+    pub status: RefUpdateStatus,
+}
+
+/// What the server answers to a push
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct PushReport {
+    /// Number of commits the server didn't have
+    pub received: usize,
+    pub updates: Vec<RefUpdateResult>,
+}
+
+impl PushReport {
+    pub fn is_rejected(&self) -> bool {
+        self.updates
+            .iter()
+            .any(|update| update.status.is_rejected())
+    }
+}
+
+impl Repo {
+    /// Pushes branches and tags, how much of the commits is uploaded depends on the
+    /// transport of the remote, see [`Transport::push_objects`](crate::transport::Transport::push_objects)
+    pub async fn push(&self, remote: &Remote, options: &PushOptions) -> Result<PushReport, Error> {
+        let remote_refs = remote.list_refs().await?;
+        let request = self.push_request(&remote_refs, options)?;
+        remote.send_push(&request).await
+    }
+
+    /// The push of `options` to a remote having `remote_refs`, holding every commit which
+    /// may be missing on the remote, see [`Repo::missing_commits`] to trim them
+    pub fn push_request(
+        &self,
+        remote_refs: &Refs,
+        options: &PushOptions,
+    ) -> Result<PushRequest, Error> {
+        let mut branches = options.branches.clone();
+        if branches.is_empty() && options.delete.is_empty() && !options.tags {
+            let current = self
+                .refs
+                .current_branch()
+                .ok_or(Error::PushError(PushErrorReason::DetachedHead))?;
+            branches.push(current.to_owned());
+        }
+
+        let mut updates = Vec::new();
+        for name in &branches {
+            let new = *self.refs.branches.get(name).ok_or(Error::RefError(
+                RefErrorReason::BranchNotFound(name.clone()),
+            ))?;
+            updates.push(BranchUpdate {
+                name: name.clone(),
+                old: remote_refs.branches.get(name).copied(),
+                new: Some(new),
+            });
+        }
+        for name in &options.delete {
+            updates.push(BranchUpdate {
+                name: name.clone(),
+                old: remote_refs.branches.get(name).copied(),
+                new: None,
+            });
+        }
+        let tags = if options.tags {
+            self.refs
+                .tags
+                .iter()
+                .filter(|(name, tag)| remote_refs.tags.get(*name) != Some(tag))
+                .map(|(name, tag)| (name.clone(), tag.clone()))
+                .collect()
+        } else {
+            BTreeMap::new()
+        };
+
+        // The commits reachable from a ref of the remote known locally are already there
+        let tips = updates
+            .iter()
+            .filter_map(|update| update.new)
+            .chain(tags.values().map(Tag::target))
+            .collect::<Vec<_>>();
+        let known = remote_refs
+            .branches
+            .values()
+            .copied()
+            .chain(remote_refs.tags.values().map(Tag::target))
+            .filter(|id| self.history.contains(id))
+            .collect::<Vec<_>>();
+        let known = self.history.ancestors_of_all(&known);
+        let commits = self
+            .history
+            .walk(&tips)
+            .into_iter()
+            .rev()
+            .filter(|commit| !known.contains(&commit.id))
+            .cloned()
+            .collect();
+
+        Ok(PushRequest {
+            commits,
+            branches: updates,
+            tags,
+            force: options.force,
+        })
+    }
+
+    /// The commits among `ids` which the repo doesn't have
+    pub fn missing_commits(&self, ids: &[CommitId]) -> Vec<CommitId> {
+        ids.iter()
+            .filter(|id| !self.history.contains(id))
+            .copied()
+            .collect()
+    }
+
+    /// Stores the pushed commits and updates the refs, a branch only moves forward
+    /// unless the push is forced, the checked out branch also updates the working directory
+    pub fn receive_push(&mut self, request: PushRequest) -> Result<PushReport, Error> {
+        self.receive_push_guarded(request, &|_, _| true)
+    }
+
+    /// Same as [`Repo::receive_push`], the branch updates `allowed` refuses being rejected,
+    /// it is given the branch name and whether the update drops commits from the branch
+    pub fn receive_push_guarded(
+        &mut self,
+        mut request: PushRequest,
+        allowed: &dyn Fn(&str, bool) -> bool,
+    ) -> Result<PushReport, Error> {
+        let mut report = PushReport::default();
+        for commit in std::mem::take(&mut request.commits) {
+            // Ids are short enough for the commits of different repos to share one
+            match self.history.get(&commit.id) {
+                Some(known) if *known == commit => continue,
+                Some(_) => {
+                    return Err(Error::PushError(PushErrorReason::CommitIdCollision(
+                        commit.id.to_hex(),
+                    )))
+                }
+                None => {}
+            }
+            if let Some(parent) = commit
+                .parents
+                .iter()
+                .find(|parent| !self.history.contains(parent))
+            {
+                return Err(Error::PushError(PushErrorReason::MissingCommit(
+                    parent.to_hex(),
+                )));
+            }
+            self.history.push(commit);
+            report.received += 1;
+        }
+        if report.received > 0 {
+            self.save_history()?;
+        }
+        for id in request
+            .branches
+            .iter()
+            .filter_map(|update| update.new)
+            .chain(request.tags.values().map(Tag::target))
+        {
+            if !self.history.contains(&id) {
+                return Err(Error::PushError(PushErrorReason::MissingCommit(
+                    id.to_hex(),
+                )));
+            }
+        }
+
+        // The refs updated before a failure are saved all the same, so that the files
+        // stay in line with the repo
+        let result = self.receive_ref_updates(request, allowed, &mut report);
+        self.save_refs()?;
+        result?;
+        let updated = report
+            .updates
+            .iter()
+            .filter(|update| {
+                matches!(
+                    update.status,
+                    RefUpdateStatus::Updated | RefUpdateStatus::ForcedUpdate
+                )
+            })
+            .map(|update| update.name.as_str())
+            .collect::<Vec<_>>();
+        if !updated.is_empty() {
+            self.record_operation(&format!("receive push {}", updated.join(", ")))?;
+        }
+        Ok(report)
+    }
+
+    /// Moves the branches and tags of a push, adding their outcome to `report`
+    fn receive_ref_updates(
+        &mut self,
+        request: PushRequest,
+        allowed: &dyn Fn(&str, bool) -> bool,
+        report: &mut PushReport,
+    ) -> Result<(), Error> {
+        for update in &request.branches {
+            let old = self.refs.branches.get(&update.name).copied();
+            let status = self.receive_branch_update(update, request.force, allowed)?;
+            report.updates.push(RefUpdateResult {
+                name: update.name.clone(),
+                old,
+                new: update.new,
+                status,
+            });
+        }
+        for (name, tag) in request.tags {
+            let old = self.refs.tags.get(&name).map(Tag::target);
+            let status = match self.refs.tags.get(&name) {
+                _ if Refs::check_name(&name).is_err() => RefUpdateStatus::RejectedInvalidName,
+                Some(current) if *current == tag => RefUpdateStatus::UpToDate,
+                Some(_) if !request.force => RefUpdateStatus::RejectedAlreadyExists,
+                current => {
+                    let status = match current {
+                        Some(_) => RefUpdateStatus::ForcedUpdate,
+                        None => RefUpdateStatus::Updated,
+                    };
+                    self.refs.tags.insert(name.clone(), tag.clone());
+                    status
+                }
+            };
+            report.updates.push(RefUpdateResult {
+                name: format!("{}/{}", Tag::DIR_NAME, name),
+                old,
+                new: Some(tag.target()),
+                status,
+            });
+        }
+        Ok(())
+    }
+
+    fn receive_branch_update(
+        &mut self,
+        update: &BranchUpdate,
+        force: bool,
+        allowed: &dyn Fn(&str, bool) -> bool,
+    ) -> Result<RefUpdateStatus, Error> {
+        if Refs::check_name(&update.name).is_err() {
+            return Ok(RefUpdateStatus::RejectedInvalidName);
+        }
+        let current = self.refs.branches.get(&update.name).copied();
+        let checked_out = self.refs.head == Head::Branch(update.name.clone());
+        if current == update.new {
+            return Ok(RefUpdateStatus::UpToDate);
+        }
+        if current != update.old && !force {
+            return Ok(RefUpdateStatus::RejectedStale);
+        }
+
+        let Some(new) = update.new else {
+            if checked_out {
+                return Ok(RefUpdateStatus::RejectedCurrentBranch);
+            }
+            if !allowed(&update.name, true) {
+                return Ok(RefUpdateStatus::RejectedProtected);
+            }
+            self.refs.branches.remove(&update.name);
+            Reflog::delete(&self.path.join(FURSION_DIR), &update.name)?;
+            return Ok(RefUpdateStatus::Updated);
+        };
+        let status = match current {
+            Some(current) if !self.history.is_ancestor(&current, &new) => {
+                if !force {
+                    return Ok(RefUpdateStatus::RejectedNonFastForward);
+                }
+                RefUpdateStatus::ForcedUpdate
+            }
+            _ => RefUpdateStatus::Updated,
+        };
+        if !allowed(&update.name, status == RefUpdateStatus::ForcedUpdate) {
+            return Ok(RefUpdateStatus::RejectedProtected);
+        }
+
+        let message = match status {
+            RefUpdateStatus::ForcedUpdate => "push: forced-update",
+            _ => "push",
+        };
+        if checked_out {
+            if self.has_uncommitted_changes()? {
+                return Ok(RefUpdateStatus::RejectedDirtyCheckout);
+            }
+            let old_tree = self.head_tree()?;
+            tree::write_working_tree(&self.path, &old_tree, &self.tree_at(&new)?)?;
+            self.reread_files()?;
+            self.move_head(new, message)?;
+        } else {
+            self.refs.branches.insert(update.name.clone(), new);
+            self.log_ref_update(&update.name, current, Some(new), message)?;
+        }
+        Ok(status)
+    }
+}
@@ -0,0 +1,544 @@
+use std::{fmt, fs, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cherry_pick::PendingPick,
+    commit::CommitId,
+    error::{Error, RebaseErrorReason},
+    log::LogQuery,
+    merge::{self, MergeLabels, PendingMerge},
+    refs::{Head, Refs},
+    repo::{Repo, FURSION_DIR},
+    revision::RevisionSet,
+    tree::{self, Tree},
+};
+
+/// What to do with a commit of a rebase
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq)]
+pub enum TodoAction {
+    /// Replays the commit
+    Pick,
+    /// Replays the commit with the summary line given on the todo line
+    Reword,
+    /// Replays the commit and stops to let it be changed
+    Edit,
+    /// Melds the commit into the previous one, keeping both messages
+    Squash,
+    /// Melds the commit into the previous one, keeping the previous message
+    Fixup,
+    /// Leaves the commit out
+    Drop,
+}
+
+impl TodoAction {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TodoAction::Pick => "pick",
+            TodoAction::Reword => "reword",
+            TodoAction::Edit => "edit",
+            TodoAction::Squash => "squash",
+            TodoAction::Fixup => "fixup",
+            TodoAction::Drop => "drop",
+        }
+    }
+
+    /// Parses an action by its name or its first letter
+    pub fn from_name(s: &str) -> Option<Self> {
+        Some(match s {
+            "pick" | "p" => TodoAction::Pick,
+            "reword" | "r" => TodoAction::Reword,
+            "edit" | "e" => TodoAction::Edit,
+            "squash" | "s" => TodoAction::Squash,
+            "fixup" | "f" => TodoAction::Fixup,
+            "drop" | "d" => TodoAction::Drop,
+            _ => return None,
+        })
+    }
+}
+
+/// A line of the todo plan of a rebase, like `pick 69FC64 Fix typo`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct TodoItem {
+    pub action: TodoAction,
+    pub commit: CommitId,
+    /// The summary of the commit, the new summary line for [`TodoAction::Reword`]
+    pub text: String,
+}
+
+impl TodoItem {
+    const COMMENT_PREFIX: char = '#';
+
+    /// Parses a todo plan, skipping empty lines and `#` comments
+    pub fn list_from_str(s: &str) -> Result<Vec<Self>, Error> {
+        s.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with(Self::COMMENT_PREFIX))
+            .map(Self::from_str)
+            .collect()
+    }
+
+    pub fn list_to_string(list: &[Self]) -> String {
+        list.iter().map(|item| format!("{}\n", item)).collect()
+    }
+}
+
+impl fmt::Display for TodoItem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {}",
+            self.action.name(),
+            self.commit.to_hex(),
+            self.text
+        )
+    }
+}
+
+impl FromStr for TodoItem {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid = || Error::RebaseError(RebaseErrorReason::InvalidTodoLine(s.to_owned()));
+        let mut parts = s.trim().splitn(3, ' ');
+        let action = parts
+            .next()
+            .and_then(TodoAction::from_name)
+            .ok_or_else(invalid)?;
+        let commit =
+            CommitId::from_hex(parts.next().ok_or_else(invalid)?).map_err(|_| invalid())?;
+        Ok(TodoItem {
+            action,
+            commit,
+            text: parts.next().unwrap_or("").trim().to_owned(),
+        })
+    }
+}
+
+/// Why a rebase stopped before replaying every commit
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub enum RebaseStop {
+    /// The files listed could not be merged
+    Conflicts(Vec<String>),
+    /// The commit was replayed and can be changed before going on
+    Edit,
+}
+
+/// Where a rebase ended up
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RebaseStatus {
+    /// Every commit was replayed, HEAD being the last one
+    Finished(Option<CommitId>),
+    /// Stopped on an `edit` line after replaying the commit
+    StoppedAtEdit(CommitId),
+}
+
+/// Options of [`Repo::rebase`]
+#[derive(Debug, Clone, Default)]
+pub struct RebaseOptions {
+    /// Replays the commits on this commit rather than on the upstream
+    pub onto: Option<CommitId>,
+    /// Plan to follow rather than picking every commit, see [`Repo::rebase_todo`]
+    pub todo: Option<Vec<TodoItem>>,
+}
+
+/// A rebase in progress, saved in the `.fursion/rebase` dir along with its remaining todo plan
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RebaseState {
+    pub onto: CommitId,
+    /// HEAD before the rebase started
+    pub orig_head: CommitId,
+    /// The branch being rebased, [`None`] if HEAD was detached
+    pub branch: Option<String>,
+    /// The line being replayed when the rebase stopped
+    pub current: Option<TodoItem>,
+    pub stop: Option<RebaseStop>,
+}
+
+impl RebaseState {
+    pub const DIR_NAME: &'static str = "rebase";
+    const STATE_FILE_NAME: &'static str = "state";
+    const TODO_FILE_NAME: &'static str = "todo";
+
+    pub fn read(fursion_dir_path: &Path) -> Result<Option<Self>, Error> {
+        let path = fursion_dir_path
+            .join(Self::DIR_NAME)
+            .join(Self::STATE_FILE_NAME);
+        if !Path::exists(&path) {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_slice(&fs::read(path)?)?))
+    }
+
+    pub fn save(&self, fursion_dir_path: &Path) -> Result<(), Error> {
+        let dir = fursion_dir_path.join(Self::DIR_NAME);
+        fs::create_dir_all(&dir)?;
+        fs::write(
+            dir.join(Self::STATE_FILE_NAME),
+            serde_json::to_string(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// The remaining lines of the todo plan, which can be edited while the rebase is stopped
+    pub fn read_todo(fursion_dir_path: &Path) -> Result<Vec<TodoItem>, Error> {
+        let path = Self::todo_path(fursion_dir_path);
+        if !Path::exists(&path) {
+            return Ok(Vec::new());
+        }
+        let data = fs::read(path)?;
+        TodoItem::list_from_str(std::str::from_utf8(&data)?)
+    }
+
+    pub fn save_todo(fursion_dir_path: &Path, todo: &[TodoItem]) -> Result<(), Error> {
+        fs::create_dir_all(fursion_dir_path.join(Self::DIR_NAME))?;
+        fs::write(
+            Self::todo_path(fursion_dir_path),
+            TodoItem::list_to_string(todo),
+        )?;
+        Ok(())
+    }
+
+    pub fn todo_path(fursion_dir_path: &Path) -> std::path::PathBuf {
+        fursion_dir_path
+            .join(Self::DIR_NAME)
+            .join(Self::TODO_FILE_NAME)
+    }
+
+    pub fn delete(fursion_dir_path: &Path) -> Result<(), Error> {
+        let dir = fursion_dir_path.join(Self::DIR_NAME);
+        if Path::exists(&dir) {
+            fs::remove_dir_all(dir)?;
+        }
+        Ok(())
+    }
+}
+
+impl Repo {
+    /// Fails if a cherry-pick, revert, rebase or merge is stopped
+    pub(crate) fn check_nothing_in_progress(&self) -> Result<(), Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        if let Some(pending) = PendingPick::read(&fursion_dir)? {
+            return Err(Error::InProgress(pending.kind.name().to_owned()));
+        }
+        if RebaseState::read(&fursion_dir)?.is_some() {
+            return Err(Error::InProgress("rebase".to_owned()));
+        }
+        if PendingMerge::read(&fursion_dir)?.is_some() {
+            return Err(Error::InProgress("merge".to_owned()));
+        }
+        Ok(())
+    }
+
+    /// The default plan of a rebase on `upstream`, picking every commit reachable
+    /// from HEAD but not from `upstream`, oldest first, merge commits being left out
+    pub fn rebase_todo(&self, upstream: CommitId) -> Result<Vec<TodoItem>, Error> {
+        let set = RevisionSet {
+            include: vec![self.head_or_err()?],
+            exclude: vec![upstream],
+        };
+        let mut commits = self.history.query(&set, &LogQuery::new());
+        commits.reverse();
+        Ok(commits
+            .into_iter()
+            .filter(|commit| !commit.is_merge())
+            .map(|commit| TodoItem {
+                action: TodoAction::Pick,
+                commit: commit.id,
+                text: commit.summary().to_owned(),
+            })
+            .collect())
+    }
+
+    /// The rebase in progress, if any
+    pub fn rebase_state(&self) -> Result<Option<RebaseState>, Error> {
+        RebaseState::read(&self.path.join(FURSION_DIR))
+    }
+
+    /// Replays the commits of HEAD which are not in `upstream` on top of it, or on
+    /// top of [`RebaseOptions::onto`], then moves the checked out branch to the result,
+    /// stops with [`Error::MergeConflicts`] when a commit can't be replayed cleanly
+    pub fn rebase(
+        &mut self,
+        upstream: CommitId,
+        options: &RebaseOptions,
+    ) -> Result<RebaseStatus, Error> {
+        self.check_nothing_in_progress()?;
+        if self.has_uncommitted_changes()? {
+            return Err(Error::UncommittedChanges);
+        }
+        let orig_head = self.head_or_err()?;
+        let onto = options.onto.unwrap_or(upstream);
+        self.history.find(&onto)?;
+
+        let todo = match &options.todo {
+            Some(todo) => todo.clone(),
+            None => self.rebase_todo(upstream)?,
+        };
+        let first = todo.iter().find(|item| item.action != TodoAction::Drop);
+        if first.is_some_and(|item| matches!(item.action, TodoAction::Squash | TodoAction::Fixup)) {
+            return Err(Error::RebaseError(RebaseErrorReason::NothingToSquashInto));
+        }
+        for item in &todo {
+            self.history.find(&item.commit)?;
+        }
+
+        let fursion_dir = self.path.join(FURSION_DIR);
+        let state = RebaseState {
+            onto,
+            orig_head,
+            branch: self.refs.current_branch().map(str::to_owned),
+            current: None,
+            stop: None,
+        };
+        state.save(&fursion_dir)?;
+        RebaseState::save_todo(&fursion_dir, &todo)?;
+
+        let old_tree = self.head_tree()?;
+        self.refs.head = Head::Detached(onto);
+        tree::write_working_tree(&self.path, &old_tree, &self.head_tree()?)?;
+        self.reread_files()?;
+        self.log_ref_update(
+            Refs::HEAD_FILE_NAME,
+            Some(orig_head),
+            Some(onto),
+            &format!("rebase (start): checkout {}", onto.to_hex()),
+        )?;
+        self.save_refs()?;
+
+        self.run_rebase(state)
+    }
+
+    /// Goes on with a stopped rebase once the conflicts are resolved, or once the commit
+    /// stopped at is changed, the changes left in the working directory being added to it
+    pub fn rebase_continue(&mut self) -> Result<RebaseStatus, Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        let mut state = RebaseState::read(&fursion_dir)?.ok_or(Error::NothingInProgress)?;
+
+        match (state.stop.take(), state.current.take()) {
+            (Some(RebaseStop::Conflicts(conflicts)), Some(item)) => {
+                let unresolved = self.unresolved_conflicts(&conflicts)?;
+                if !unresolved.is_empty() {
+                    return Err(Error::MergeConflicts(unresolved));
+                }
+
+                let working = self.working_tree()?;
+                self.stated_changes.clear();
+                let id = self.commit_todo_item(&item, Some(&working))?;
+                if item.action == TodoAction::Edit {
+                    state.stop = Some(RebaseStop::Edit);
+                    state.save(&fursion_dir)?;
+                    return Ok(RebaseStatus::StoppedAtEdit(id));
+                }
+            }
+            (Some(RebaseStop::Edit), _) if self.has_uncommitted_changes()? => {
+                let head = self.history.find(&self.head_or_err()?)?.clone();
+                let working = self.working_tree()?;
+                let parent_tree = match head.parents.first() {
+                    Some(parent) => self.tree_at(parent)?,
+                    None => Tree::new(),
+                };
+                self.stated_changes.clear();
+                self.commit_changes(
+                    &head.message,
+                    &head.author,
+                    head.parents.clone(),
+                    tree::diff_trees(&parent_tree, &working),
+                    "rebase (amend)",
+                )?;
+            }
+            _ => {}
+        }
+        self.run_rebase(state)
+    }
+
+    /// Leaves out the commit a rebase stopped on and goes on with the next ones
+    pub fn rebase_skip(&mut self) -> Result<RebaseStatus, Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        let mut state = RebaseState::read(&fursion_dir)?.ok_or(Error::NothingInProgress)?;
+
+        tree::write_working_tree(&self.path, &self.working_tree()?, &self.head_tree()?)?;
+        self.reread_files()?;
+        self.stated_changes.clear();
+        self.save_stated_changes()?;
+
+        state.stop = None;
+        state.current = None;
+        self.run_rebase(state)
+    }
+
+    /// Gives up a rebase, putting back HEAD and the files as they were before it started
+    pub fn rebase_abort(&mut self) -> Result<(), Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        let state = RebaseState::read(&fursion_dir)?.ok_or(Error::NothingInProgress)?;
+
+        let old_head = self.head();
+        let working = self.working_tree()?;
+        self.refs.head = match &state.branch {
+            Some(branch) => Head::Branch(branch.clone()),
+            None => Head::Detached(state.orig_head),
+        };
+        tree::write_working_tree(&self.path, &working, &self.head_tree()?)?;
+        self.reread_files()?;
+        self.stated_changes.clear();
+        self.save_stated_changes()?;
+
+        self.log_ref_update(
+            Refs::HEAD_FILE_NAME,
+            old_head,
+            Some(state.orig_head),
+            "rebase (abort)",
+        )?;
+        self.save_refs()?;
+        RebaseState::delete(&fursion_dir)?;
+        self.record_operation("rebase (abort)")?;
+        Ok(())
+    }
+
+    /// Replays the remaining lines of the todo plan until it is done or a line stops it
+    fn run_rebase(&mut self, mut state: RebaseState) -> Result<RebaseStatus, Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        loop {
+            let mut todo = RebaseState::read_todo(&fursion_dir)?;
+            if todo.is_empty() {
+                return self.finish_rebase(&state);
+            }
+            let item = todo.remove(0);
+            RebaseState::save_todo(&fursion_dir, &todo)?;
+            if item.action == TodoAction::Drop {
+                continue;
+            }
+
+            let tree = match self.replay_todo_item(&item)? {
+                Ok(tree) => tree,
+                Err(conflicts) => {
+                    state.current = Some(item);
+                    state.stop = Some(RebaseStop::Conflicts(conflicts.clone()));
+                    state.save(&fursion_dir)?;
+                    return Err(Error::MergeConflicts(conflicts));
+                }
+            };
+            let id = self.commit_todo_item(&item, tree.as_ref())?;
+            if item.action == TodoAction::Edit {
+                state.stop = Some(RebaseStop::Edit);
+                state.save(&fursion_dir)?;
+                return Ok(RebaseStatus::StoppedAtEdit(id));
+            }
+        }
+    }
+
+    /// Merges the changes of the commit of a todo line on top of HEAD into the working
+    /// directory, gives back the merged files, [`None`] if the commit can be reused as is,
+    /// or the conflicting paths
+    fn replay_todo_item(
+        &mut self,
+        item: &TodoItem,
+    ) -> Result<Result<Option<Tree>, Vec<String>>, Error> {
+        let commit = self.history.find(&item.commit)?.clone();
+        let reusable = matches!(item.action, TodoAction::Pick | TodoAction::Edit);
+        if reusable && commit.parents.first().copied() == self.head() {
+            let old_tree = self.head_tree()?;
+            tree::write_working_tree(&self.path, &old_tree, &self.tree_at(&commit.id)?)?;
+            self.reread_files()?;
+            return Ok(Ok(None));
+        }
+
+        let base = match commit.parents.first() {
+            Some(parent) => self.tree_at(parent)?,
+            None => Tree::new(),
+        };
+        let head_tree = self.head_tree()?;
+        let labels = MergeLabels {
+            ours: "HEAD",
+            theirs: &format!("{} ({})", commit.id.to_hex(), commit.summary()),
+        };
+        let merged = merge::merge_trees(&base, &head_tree, &self.tree_at(&commit.id)?, labels);
+        tree::write_working_tree(&self.path, &head_tree, &merged.tree)?;
+        self.reread_files()?;
+
+        if merged.is_clean() {
+            Ok(Ok(Some(merged.tree)))
+        } else {
+            Ok(Err(merged.conflicts))
+        }
+    }
+
+    /// Makes the commit of a replayed todo line out of the merged files,
+    /// reusing the original commit if there are none
+    fn commit_todo_item(
+        &mut self,
+        item: &TodoItem,
+        tree: Option<&Tree>,
+    ) -> Result<CommitId, Error> {
+        let commit = self.history.find(&item.commit)?.clone();
+        let Some(tree) = tree else {
+            self.move_head(commit.id, &format!("rebase (pick): {}", commit.summary()))?;
+            self.record_operation(&format!("rebase (pick): {}", commit.summary()))?;
+            return Ok(commit.id);
+        };
+
+        match item.action {
+            TodoAction::Squash | TodoAction::Fixup => {
+                let previous = self.history.find(&self.head_or_err()?)?.clone();
+                let parent_tree = match previous.parents.first() {
+                    Some(parent) => self.tree_at(parent)?,
+                    None => Tree::new(),
+                };
+                let message = if item.action == TodoAction::Squash {
+                    format!("{}\n\n{}", previous.message, commit.message)
+                } else {
+                    previous.message.clone()
+                };
+                let kind = format!("rebase ({})", item.action.name());
+                self.commit_changes(
+                    &message,
+                    &previous.author,
+                    previous.parents.clone(),
+                    tree::diff_trees(&parent_tree, tree),
+                    &kind,
+                )
+            }
+            _ => {
+                let message = if item.action == TodoAction::Reword && item.text != commit.summary()
+                {
+                    // Only the summary line is written in the todo plan, the body is kept
+                    match commit.message.split_once('\n') {
+                        Some((_, body)) => format!("{}\n{}", item.text, body),
+                        None => item.text.clone(),
+                    }
+                } else {
+                    commit.message.clone()
+                };
+                let kind = format!("rebase ({})", item.action.name());
+                let changes = tree::diff_trees(&self.head_tree()?, tree);
+                let parents = self.head().into_iter().collect();
+                self.commit_changes(&message, &commit.author, parents, changes, &kind)
+            }
+        }
+    }
+
+    /// Moves the rebased branch to the last replayed commit and checks it out again
+    fn finish_rebase(&mut self, state: &RebaseState) -> Result<RebaseStatus, Error> {
+        let head = self.head();
+        if let (Some(branch), Some(id)) = (&state.branch, head) {
+            let old = self.refs.branches.insert(branch.clone(), id);
+            self.log_ref_update(
+                branch,
+                old,
+                Some(id),
+                &format!("rebase (finish): {} onto {}", branch, state.onto.to_hex()),
+            )?;
+            self.refs.head = Head::Branch(branch.clone());
+            self.log_ref_update(
+                Refs::HEAD_FILE_NAME,
+                head,
+                head,
+                &format!("rebase (finish): returning to {}", branch),
+            )?;
+        }
+        self.save_refs()?;
+        RebaseState::delete(&self.path.join(FURSION_DIR))?;
+        self.record_operation(&format!("rebase (finish): onto {}", state.onto.to_hex()))?;
+        Ok(RebaseStatus::Finished(head))
+    }
+}
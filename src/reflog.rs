@@ -0,0 +1,189 @@
+use std::{
+    fmt,
+    fs::{self, OpenOptions},
+    io::Write,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commit::{self, CommitId},
+    error::{Error, RefErrorReason},
+    refs::{Head, Refs},
+    repo::{Repo, FURSION_DIR},
+};
+
+/// A movement of a ref
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct ReflogEntry {
+    /// The commit the ref pointed to before, [`None`] if it did not exist
+    pub old: Option<CommitId>,
+    /// The commit the ref points to after, [`None`] if it was deleted
+    pub new: Option<CommitId>,
+    /// Who moved the ref
+    pub who: String,
+    /// Seconds since the unix epoch at which the ref moved
+    pub timestamp: u64,
+    /// Why the ref moved, like `commit: Fix typo`
+    pub message: String,
+}
+
+impl ReflogEntry {
+    const NO_COMMIT: &'static str = "-";
+}
+
+impl fmt::Display for ReflogEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let id = |id: &Option<CommitId>| id.map_or(Self::NO_COMMIT.to_owned(), |id| id.to_hex());
+        write!(
+            f,
+            "{} {} {} {}\t{}",
+            id(&self.old),
+            id(&self.new),
+            self.timestamp,
+            self.who,
+            self.message.replace('\n', " ")
+        )
+    }
+}
+
+impl FromStr for ReflogEntry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let malformed = || Error::RefError(RefErrorReason::ReflogMalformed(s.to_owned()));
+        let (data, message) = s.split_once('\t').ok_or_else(malformed)?;
+        let data = data.splitn(4, ' ').collect::<Vec<_>>();
+        if data.len() != 4 {
+            return Err(malformed());
+        }
+
+        let id = |s: &str| match s {
+            Self::NO_COMMIT => Ok(None),
+            s => CommitId::from_hex(s).map(Some),
+        };
+        Ok(ReflogEntry {
+            old: id(data[0])?,
+            new: id(data[1])?,
+            timestamp: data[2].parse().map_err(|_| malformed())?,
+            who: data[3].to_owned(),
+            message: message.to_owned(),
+        })
+    }
+}
+
+/// Append only logs of the movements of HEAD and each branch
+pub struct Reflog;
+
+impl Reflog {
+    pub const DIR_NAME: &'static str = "logs";
+
+    /// Path of the log of a ref, `HEAD` or a branch name
+    fn get_path(fursion_dir_path: &Path, ref_name: &str) -> PathBuf {
+        let dir = fursion_dir_path.join(Self::DIR_NAME);
+        if ref_name == Refs::HEAD_FILE_NAME {
+            dir.join(Refs::HEAD_FILE_NAME)
+        } else {
+            dir.join(Refs::DIR_NAME)
+                .join(Refs::BRANCHES_DIR_NAME)
+                .join(ref_name)
+        }
+    }
+
+    pub fn append(
+        fursion_dir_path: &Path,
+        ref_name: &str,
+        entry: &ReflogEntry,
+    ) -> Result<(), Error> {
+        let path = Self::get_path(fursion_dir_path, ref_name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        writeln!(file, "{}", entry)?;
+        Ok(())
+    }
+
+    /// Entries of a ref, oldest first
+    pub fn read(fursion_dir_path: &Path, ref_name: &str) -> Result<Vec<ReflogEntry>, Error> {
+        let path = Self::get_path(fursion_dir_path, ref_name);
+        if !Path::exists(&path) {
+            return Ok(Vec::new());
+        }
+        let data = fs::read(path)?;
+        std::str::from_utf8(&data)?
+            .lines()
+            .filter(|line| !line.is_empty())
+            .map(ReflogEntry::from_str)
+            .collect()
+    }
+
+    pub fn delete(fursion_dir_path: &Path, ref_name: &str) -> Result<(), Error> {
+        let path = Self::get_path(fursion_dir_path, ref_name);
+        if Path::exists(&path) {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+impl Repo {
+    /// Entries of the log of a ref, `HEAD` or a branch name, newest first
+    pub fn reflog(&self, ref_name: &str) -> Result<Vec<ReflogEntry>, Error> {
+        let ref_name = if ref_name == "@" { "HEAD" } else { ref_name };
+        if ref_name != Refs::HEAD_FILE_NAME && !self.refs.branches.contains_key(ref_name) {
+            return Err(Error::RefError(RefErrorReason::BranchNotFound(
+                ref_name.to_owned(),
+            )));
+        }
+        let mut entries = Reflog::read(&self.path.join(FURSION_DIR), ref_name)?;
+        entries.reverse();
+        Ok(entries)
+    }
+
+    /// Appends an entry to the log of a ref, `HEAD` or a branch name
+    pub(crate) fn log_ref_update(
+        &self,
+        ref_name: &str,
+        old: Option<CommitId>,
+        new: Option<CommitId>,
+        message: &str,
+    ) -> Result<(), Error> {
+        let entry = ReflogEntry {
+            old,
+            new,
+            who: self.metadata.author.clone(),
+            timestamp: commit::now(),
+            message: message.to_owned(),
+        };
+        Reflog::append(&self.path.join(FURSION_DIR), ref_name, &entry)
+    }
+
+    /// Moves HEAD, and the checked out branch if any, to a commit and logs why
+    pub(crate) fn move_head(&mut self, new: CommitId, message: &str) -> Result<(), Error> {
+        let old = self.head();
+        self.refs.set_head_commit(new);
+        if let Head::Branch(branch) = &self.refs.head {
+            self.log_ref_update(branch, old, Some(new), message)?;
+        }
+        self.log_ref_update(Refs::HEAD_FILE_NAME, old, Some(new), message)?;
+        self.save_refs()
+    }
+
+    /// Value of a ref `index` updates ago, using its log
+    pub(crate) fn resolve_reflog(&self, ref_name: &str, index: usize) -> Result<CommitId, Error> {
+        let entries = self.reflog(ref_name)?;
+        if index == 0 && entries.is_empty() {
+            return self.resolve(ref_name);
+        }
+        entries
+            .get(index)
+            .and_then(|entry| entry.new)
+            .ok_or(Error::RefError(RefErrorReason::ReflogEntryNotFound(
+                ref_name.to_owned(),
+                index,
+            )))
+    }
+}
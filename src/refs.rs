@@ -0,0 +1,212 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commit::CommitId,
+    error::{Error, RefErrorReason},
+    tag::{self, Tag},
+};
+
+/// Name of the branch created with a new repo
+pub const DEFAULT_BRANCH: &str = "main";
+
+/// What the repo currently has checked out
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub enum Head {
+    /// A branch, which may not point to any commit yet
+    Branch(String),
+    /// A commit which is not the tip of a checked out branch
+    Detached(CommitId),
+}
+
+/// The named pointers to commits of a repo
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct Refs {
+    pub head: Head,
+    pub branches: BTreeMap<String, CommitId>,
+    pub tags: BTreeMap<String, Tag>,
+    /// Where the branches of the remotes were when last fetched, named `<remote>/<branch>`
+    #[serde(default)]
+    pub remotes: BTreeMap<String, CommitId>,
+}
+
+impl Default for Refs {
+    fn default() -> Self {
+        Refs {
+            head: Head::Branch(DEFAULT_BRANCH.to_owned()),
+            branches: BTreeMap::new(),
+            tags: BTreeMap::new(),
+            remotes: BTreeMap::new(),
+        }
+    }
+}
+
+impl Refs {
+    pub const HEAD_FILE_NAME: &'static str = "HEAD";
+    pub const DIR_NAME: &'static str = "refs";
+    pub const BRANCHES_DIR_NAME: &'static str = "heads";
+    pub const REMOTES_DIR_NAME: &'static str = "remotes";
+    const HEAD_BRANCH_PREFIX: &'static str = "ref: ";
+
+    /// The commit HEAD points to, if any
+    pub fn head_commit(&self) -> Option<CommitId> {
+        match &self.head {
+            Head::Branch(name) => self.branches.get(name).copied(),
+            Head::Detached(id) => Some(*id),
+        }
+    }
+
+    /// The name of the checked out branch, if HEAD is not detached
+    pub fn current_branch(&self) -> Option<&str> {
+        match &self.head {
+            Head::Branch(name) => Some(name),
+            Head::Detached(_) => None,
+        }
+    }
+
+    /// Moves HEAD to a new commit, updating the checked out branch if any
+    pub fn set_head_commit(&mut self, id: CommitId) {
+        match &self.head {
+            Head::Branch(name) => {
+                self.branches.insert(name.clone(), id);
+            }
+            Head::Detached(_) => self.head = Head::Detached(id),
+        }
+    }
+
+    /// Every commit a branch, a remote-tracking branch, a tag or HEAD points to
+    pub fn tips(&self) -> Vec<CommitId> {
+        let mut tips = self
+            .branches
+            .values()
+            .chain(self.remotes.values())
+            .copied()
+            .chain(self.tags.values().map(Tag::target))
+            .chain(self.head_commit())
+            .collect::<Vec<_>>();
+        tips.sort();
+        tips.dedup();
+        tips
+    }
+
+    /// Names of the refs pointing to a commit, used to decorate commits
+    pub fn names_of(&self, id: &CommitId) -> Vec<String> {
+        let branches = self
+            .branches
+            .iter()
+            .filter(|(_, target)| *target == id)
+            .map(|(name, _)| name.clone());
+        let remotes = self
+            .remotes
+            .iter()
+            .filter(|(_, target)| *target == id)
+            .map(|(name, _)| name.clone());
+        let tags = self
+            .tags
+            .iter()
+            .filter(|(_, tag)| tag.target() == *id)
+            .map(|(name, _)| format!("tag: {}", name));
+        branches.chain(remotes).chain(tags).collect()
+    }
+
+    /// Checks that a ref name can be used as a file name
+    pub fn check_name(name: &str) -> Result<(), Error> {
+        let invalid = name.is_empty()
+            || name.starts_with(['-', '.', '/'])
+            || name.ends_with(['/', '.'])
+            || name.contains("..")
+            || name.contains("//")
+            || name.contains(['~', '^', ':', '?', '*', '[', '\\', ' ', '@'])
+            || name == "HEAD";
+        if invalid {
+            return Err(Error::RefError(RefErrorReason::InvalidRefName(
+                name.to_owned(),
+            )));
+        }
+        Ok(())
+    }
+
+    pub fn read(fursion_dir_path: &Path) -> Result<Self, Error> {
+        let head_path = fursion_dir_path.join(Self::HEAD_FILE_NAME);
+        let head = if Path::exists(&head_path) {
+            let data = fs::read(&head_path)?;
+            let s = std::str::from_utf8(&data)?.trim();
+            match s.strip_prefix(Self::HEAD_BRANCH_PREFIX) {
+                Some(branch) => Head::Branch(branch.to_owned()),
+                None => Head::Detached(CommitId::from_hex(s)?),
+            }
+        } else {
+            Head::Branch(DEFAULT_BRANCH.to_owned())
+        };
+
+        let refs_path = fursion_dir_path.join(Self::DIR_NAME);
+        let branches = read_ref_dir(&refs_path.join(Self::BRANCHES_DIR_NAME))?;
+        let tags = tag::read_tag_dir(&refs_path.join(Tag::DIR_NAME))?;
+        let remotes = read_ref_dir(&refs_path.join(Self::REMOTES_DIR_NAME))?;
+
+        Ok(Refs {
+            head,
+            branches,
+            tags,
+            remotes,
+        })
+    }
+
+    pub fn save(&self, fursion_dir_path: &Path) -> Result<(), Error> {
+        let head = match &self.head {
+            Head::Branch(name) => format!("{}{}", Self::HEAD_BRANCH_PREFIX, name),
+            Head::Detached(id) => id.to_hex(),
+        };
+        fs::write(fursion_dir_path.join(Self::HEAD_FILE_NAME), head)?;
+
+        let refs_path = fursion_dir_path.join(Self::DIR_NAME);
+        write_ref_dir(&refs_path.join(Self::BRANCHES_DIR_NAME), &self.branches)?;
+        write_ref_dir(&refs_path.join(Self::REMOTES_DIR_NAME), &self.remotes)?;
+        tag::write_tag_dir(&refs_path.join(Tag::DIR_NAME), &self.tags)
+    }
+}
+
+/// Reads every ref file under a directory, nested directories giving `/` separated names
+pub(crate) fn read_ref_dir(dir: &Path) -> Result<BTreeMap<String, CommitId>, Error> {
+    let mut refs = BTreeMap::new();
+    if Path::exists(dir) {
+        read_ref_dir_into(dir, "", &mut refs)?;
+    }
+    Ok(refs)
+}
+
+fn read_ref_dir_into(
+    dir: &Path,
+    prefix: &str,
+    refs: &mut BTreeMap<String, CommitId>,
+) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            read_ref_dir_into(&entry.path(), &format!("{}/", name), refs)?;
+        } else {
+            let data = fs::read(entry.path())?;
+            let id = CommitId::from_hex(std::str::from_utf8(&data)?.trim())?;
+            refs.insert(name, id);
+        }
+    }
+    Ok(())
+}
+
+/// Writes every ref as a file under a directory, removing the ones which no longer exist
+pub(crate) fn write_ref_dir(dir: &Path, refs: &BTreeMap<String, CommitId>) -> Result<(), Error> {
+    if Path::exists(dir) {
+        fs::remove_dir_all(dir)?;
+    }
+    fs::create_dir_all(dir)?;
+    for (name, id) in refs {
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, id.to_hex())?;
+    }
+    Ok(())
+}
@@ -1,18 +1,346 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+};
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
-pub struct Remote(String);
+use crate::{
+    error::{Error, RemoteConfigErrorReason},
+    fetch::FetchRequest,
+    pack::Pack,
+    push::{PushReport, PushRequest},
+    refs::Refs,
+    repo::{Repo, FURSION_DIR},
+    transport::transport_for,
+};
+
+/// Name of the remote a repo is cloned from
+pub const DEFAULT_REMOTE_NAME: &str = "origin";
+
+/// Which branches of a remote a fetch stores in which remote-tracking branches, written
+/// `[+]<branch>:<tracking branch>` where both sides may hold a single `*`, like `+*:origin/*`
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Refspec {
+    /// Whether the remote-tracking branches are moved even when it isn't a fast-forward
+    pub force: bool,
+    pub src: String,
+    pub dst: String,
+}
+
+impl Refspec {
+    /// Every branch of the remote, in remote-tracking branches named `<remote>/<branch>`
+    pub fn default_for(remote_name: &str) -> Self {
+        Refspec {
+            force: true,
+            src: "*".to_owned(),
+            dst: format!("{}/*", remote_name),
+        }
+    }
+
+    /// The remote-tracking branch of a branch of the remote, [`None`] if not fetched
+    pub fn tracking_name(&self, branch: &str) -> Option<String> {
+        Self::map(&self.src, &self.dst, branch)
+    }
+
+    /// The branch of the remote a remote-tracking branch stores, [`None`] if it doesn't
+    /// come from this refspec
+    pub fn source_name(&self, tracking: &str) -> Option<String> {
+        Self::map(&self.dst, &self.src, tracking)
+    }
+
+    /// Matches `name` against the pattern `from` and puts what the `*` matched in `to`
+    fn map(from: &str, to: &str, name: &str) -> Option<String> {
+        match from.split_once('*') {
+            Some((prefix, suffix)) => {
+                let matched = name
+                    .strip_prefix(prefix)?
+                    .strip_suffix(suffix)
+                    .filter(|matched| !matched.is_empty())?;
+                Some(to.replacen('*', matched, 1))
+            }
+            None => (name == from).then(|| to.to_owned()),
+        }
+    }
+}
+
+impl fmt::Display for Refspec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}{}:{}",
+            if self.force { "+" } else { "" },
+            self.src,
+            self.dst
+        )
+    }
+}
+
+impl FromStr for Refspec {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let invalid =
+            || Error::RemoteConfigError(RemoteConfigErrorReason::InvalidRefspec(s.to_owned()));
+        let (force, spec) = match s.strip_prefix('+') {
+            Some(spec) => (true, spec),
+            None => (false, s),
+        };
+        let (src, dst) = spec.split_once(':').ok_or_else(invalid)?;
+        let stars = |side: &str| side.matches('*').count();
+        if src.is_empty() || dst.is_empty() || stars(src) > 1 || stars(src) != stars(dst) {
+            return Err(invalid());
+        }
+        Ok(Refspec {
+            force,
+            src: src.to_owned(),
+            dst: dst.to_owned(),
+        })
+    }
+}
+
+/// A repo hosted elsewhere that branches are fetched from and pushed to
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct Remote {
+    pub name: String,
+    /// Url of the repo on a server, like `http://host/author/repo`, or the path of a repo
+    /// on this machine, plain or as a `file://` url
+    pub url: String,
+    /// Url pushed to, [`Remote::url`] if [`None`]
+    #[serde(default)]
+    pub push_url: Option<String>,
+    /// Branches stored by a fetch, every branch if empty
+    #[serde(default)]
+    pub fetch: Vec<Refspec>,
+}
 
 impl Remote {
     pub const FILE_NAME: &'static str = "remotes";
 
-    /// * `url` - the url of the remote must end with .fursion
-    pub fn new(url: &str) -> Self {
-        Self(url.to_string())
+    /// A remote fetching every branch
+    pub fn new(name: &str, url: &str) -> Self {
+        Remote {
+            name: name.to_owned(),
+            url: url.to_owned(),
+            push_url: None,
+            fetch: vec![Refspec::default_for(name)],
+        }
+    }
+
+    /// The path of the repo at `url` when it is on this machine
+    pub fn local_path(url: &str) -> Option<PathBuf> {
+        let path = match url.strip_prefix("file://") {
+            Some(path) => path,
+            None if url.contains("://") => return None,
+            None => url,
+        };
+        let path = Path::new(path);
+        match path.file_name() {
+            Some(name) if name == FURSION_DIR => path.parent().map(Path::to_owned),
+            _ => Some(path.to_owned()),
+        }
+    }
+
+    pub fn push_url(&self) -> &str {
+        self.push_url.as_deref().unwrap_or(&self.url)
+    }
+
+    /// The refspecs of the fetches, the default one if none are set
+    pub fn refspecs(&self) -> Vec<Refspec> {
+        if self.fetch.is_empty() {
+            return vec![Refspec::default_for(&self.name)];
+        }
+        self.fetch.clone()
+    }
+
+    /// The remote-tracking branch a branch of the remote is stored in, along with whether
+    /// it may be moved when it isn't a fast-forward
+    pub fn tracking_name(&self, branch: &str) -> Option<(String, bool)> {
+        self.refspecs()
+            .iter()
+            .find_map(|spec| Some((spec.tracking_name(branch)?, spec.force)))
+    }
+
+    /// Whether a remote-tracking branch is written by fetches of this remote
+    pub fn is_tracking(&self, tracking: &str) -> bool {
+        self.refspecs()
+            .iter()
+            .any(|spec| spec.source_name(tracking).is_some())
+    }
+
+    /// Reads the remotes of a repo, the older format with a url per line being
+    /// read as remotes named like the remote-tracking branches they had
+    pub fn read_all(fursion_dir_path: &Path) -> Result<Vec<Self>, Error> {
+        let path = fursion_dir_path.join(Self::FILE_NAME);
+        if !Path::exists(&path) {
+            return Ok(Vec::new());
+        }
+        let data = fs::read_to_string(path)?;
+        if data.trim_start().starts_with('[') {
+            return Ok(serde_json::from_str(&data)?);
+        }
+        Ok(data
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .enumerate()
+            .map(|(i, url)| {
+                let name = match i {
+                    0 => DEFAULT_REMOTE_NAME.to_owned(),
+                    i => format!("{}{}", DEFAULT_REMOTE_NAME, i + 1),
+                };
+                Remote::new(&name, url.trim())
+            })
+            .collect())
+    }
+
+    pub fn save_all(fursion_dir_path: &Path, remotes: &[Self]) -> Result<(), Error> {
+        fs::write(
+            fursion_dir_path.join(Self::FILE_NAME),
+            serde_json::to_string_pretty(remotes)?,
+        )?;
+        Ok(())
+    }
+
+    /// The branches, tags and HEAD of the remote repo
+    pub async fn list_refs(&self) -> Result<Refs, Error> {
+        transport_for(&self.url)?.list_refs(&self.url).await
+    }
+
+    /// The commits the remote has and the client doesn't, see [`Repo::pack_missing`]
+    pub async fn fetch_pack(&self, request: &FetchRequest) -> Result<Pack, Error> {
+        transport_for(&self.url)?
+            .fetch_objects(&self.url, request)
+            .await
+    }
+
+    /// Uploads commits and asks the remote to update its refs
+    pub async fn send_push(&self, request: &PushRequest) -> Result<PushReport, Error> {
+        let url = self.push_url();
+        transport_for(url)?.push_objects(url, request).await
+    }
+}
+
+impl Repo {
+    pub fn remote(&self, name: &str) -> Result<&Remote, Error> {
+        self.remotes
+            .iter()
+            .find(|remote| remote.name == name)
+            .ok_or_else(|| {
+                Error::RemoteConfigError(RemoteConfigErrorReason::NotFound(name.to_owned()))
+            })
+    }
+
+    fn remote_mut(&mut self, name: &str) -> Result<&mut Remote, Error> {
+        self.remotes
+            .iter_mut()
+            .find(|remote| remote.name == name)
+            .ok_or_else(|| {
+                Error::RemoteConfigError(RemoteConfigErrorReason::NotFound(name.to_owned()))
+            })
+    }
+
+    /// Remote names are the first part of the names of their remote-tracking branches
+    fn check_new_remote_name(&self, name: &str) -> Result<(), Error> {
+        if name.contains('/') || Refs::check_name(name).is_err() {
+            return Err(Error::RemoteConfigError(
+                RemoteConfigErrorReason::InvalidName(name.to_owned()),
+            ));
+        }
+        if self.remotes.iter().any(|remote| remote.name == name) {
+            return Err(Error::RemoteConfigError(
+                RemoteConfigErrorReason::AlreadyExists(name.to_owned()),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Registers a remote fetching every branch, nothing is fetched yet
+    pub fn add_remote(&mut self, name: &str, url: &str) -> Result<(), Error> {
+        self.check_new_remote_name(name)?;
+        self.remotes.push(Remote::new(name, url));
+        self.save_remotes()
+    }
+
+    /// Forgets a remote along with its remote-tracking branches and the upstreams set to it
+    pub fn remove_remote(&mut self, name: &str) -> Result<(), Error> {
+        let remote = self.remote(name)?.clone();
+        self.remotes.retain(|other| other.name != name);
+        self.refs
+            .remotes
+            .retain(|tracking, _| !remote.is_tracking(tracking));
+
+        let mut config = self.config()?;
+        config
+            .upstreams
+            .retain(|_, upstream| upstream.remote != name);
+        self.save_config(&config)?;
+        self.save_remotes()?;
+        self.save_refs()?;
+        self.record_operation(&format!("remote remove {}", name))?;
+        Ok(())
+    }
+
+    /// Renames a remote, its remote-tracking branches named after it and the upstreams
+    /// set to it follow
+    pub fn rename_remote(&mut self, old: &str, new: &str) -> Result<(), Error> {
+        self.remote(old)?;
+        self.check_new_remote_name(new)?;
+        let old_prefix = format!("{}/", old);
+        let new_prefix = format!("{}/", new);
+        let remote = self.remote_mut(old)?;
+        remote.name = new.to_owned();
+        for spec in &mut remote.fetch {
+            if let Some(rest) = spec.dst.strip_prefix(&old_prefix) {
+                spec.dst = format!("{}{}", new_prefix, rest);
+            }
+        }
+
+        let renamed = self
+            .refs
+            .remotes
+            .keys()
+            .filter(|tracking| tracking.starts_with(&old_prefix))
+            .cloned()
+            .collect::<Vec<_>>();
+        for tracking in renamed {
+            if let Some(id) = self.refs.remotes.remove(&tracking) {
+                let name = format!("{}{}", new_prefix, &tracking[old_prefix.len()..]);
+                self.refs.remotes.insert(name, id);
+            }
+        }
+
+        let mut config = self.config()?;
+        for upstream in config.upstreams.values_mut() {
+            if upstream.remote == old {
+                upstream.remote = new.to_owned();
+            }
+        }
+        self.save_config(&config)?;
+        self.save_remotes()?;
+        self.save_refs()?;
+        self.record_operation(&format!("remote rename {} {}", old, new))?;
+        Ok(())
+    }
+
+    /// Changes the url of a remote, or the one it is pushed to if `push`
+    pub fn set_remote_url(&mut self, name: &str, url: &str, push: bool) -> Result<(), Error> {
+        let remote = self.remote_mut(name)?;
+        if push {
+            remote.push_url = Some(url.to_owned());
+        } else {
+            remote.url = url.to_owned();
+        }
+        self.save_remotes()
+    }
+
+    /// Changes which branches of a remote are fetched, every branch if empty
+    pub fn set_remote_refspecs(&mut self, name: &str, refspecs: Vec<Refspec>) -> Result<(), Error> {
+        self.remote_mut(name)?.fetch = refspecs;
+        self.save_remotes()
     }
 
-    /// Gives back the remote url
-    pub fn get(&self) -> String {
-        self.0.clone()
+    pub fn save_remotes(&self) -> Result<(), Error> {
+        Remote::save_all(&self.path.join(FURSION_DIR), &self.remotes)
     }
 }
@@ -1,20 +1,38 @@
 use std::{
+    collections::HashMap,
     ffi::{OsStr, OsString},
     fs, mem,
     path::{Path, PathBuf},
+    sync::{Mutex, MutexGuard, PoisonError},
 };
 
-use futures;
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
 
 use crate::{
-    commit::{Commit, FileChanges},
-    error::{Error, RepoErrorReason},
+    commit::{Commit, CommitId, FileChanges},
+    error::{Error, RefErrorReason, RepoErrorReason},
+    reflog::Reflog,
+    refs::{Head, Refs},
     remote::Remote,
+    tree::{self, Tree},
 };
 
 pub const FURSION_DIR: &str = ".fursion";
 
+/// Reads a value saved by [`save_json`], the default value if the file doesn't exist
+pub(crate) fn read_json_or_default<T: DeserializeOwned + Default>(path: &Path) -> Result<T, Error> {
+    if !Path::exists(path) {
+        return Ok(T::default());
+    }
+    Ok(serde_json::from_slice(&fs::read(path)?)?)
+}
+
+/// Saves a value as indented JSON
+pub(crate) fn save_json<T: Serialize>(path: &Path, value: &T) -> Result<(), Error> {
+    fs::write(path, serde_json::to_string_pretty(value)?)?;
+    Ok(())
+}
+
 /// Repository Object
 #[derive(Debug, Deserialize, Serialize, Clone)]
 pub struct Repo {
@@ -24,6 +42,8 @@ pub struct Repo {
     pub remotes: Vec<Remote>,
     /// The commit history of the repo
     pub history: RepoHistory,
+    /// HEAD and the branches of the repo
+    pub refs: Refs,
     /// The repo metadata mainly author name and repo name
     pub metadata: RepoMetadata,
     /// List of stated changes in the repo
@@ -50,47 +70,160 @@ pub struct File {
     pub name: OsString,
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+#[derive(Debug, Serialize, Clone)]
 pub struct RepoHistory {
     vec: Vec<Commit>,
+    /// Position of each commit in `vec`
+    #[serde(skip)]
+    index: HashMap<CommitId, usize>,
+    #[serde(skip)]
+    trees: TreeCache,
+}
+
+impl<'de> Deserialize<'de> for RepoHistory {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        struct Fields {
+            vec: Vec<Commit>,
+        }
+        Ok(Self::from_commits(Fields::deserialize(deserializer)?.vec))
+    }
+}
+
+/// Trees rebuilt by [`RepoHistory::tree_at`], along with how many commits of their first
+/// parent chain were replayed, kept every [`TreeCache::INTERVAL`] commits of a chain so
+/// that rebuilding any tree only replays the commits since the last kept one
+#[derive(Debug, Default)]
+struct TreeCache(Mutex<HashMap<CommitId, (usize, Tree)>>);
+
+impl TreeCache {
+    const INTERVAL: usize = 32;
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<CommitId, (usize, Tree)>> {
+        // The trees are only inserted once complete, so they are valid after a panic
+        self.0.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Clone for TreeCache {
+    fn clone(&self) -> Self {
+        TreeCache(Mutex::new(self.lock().clone()))
+    }
 }
 
 impl RepoHistory {
     pub const FILE_NAME: &'static str = "history";
 
     fn new() -> Self {
-        RepoHistory { vec: Vec::new() }
+        Self::from_commits(Vec::new())
+    }
+
+    fn from_commits(vec: Vec<Commit>) -> Self {
+        let index = vec
+            .iter()
+            .enumerate()
+            .map(|(i, commit)| (commit.id, i))
+            .collect();
+        RepoHistory {
+            vec,
+            index,
+            trees: TreeCache::default(),
+        }
     }
 
-    fn push(&mut self, commit: Commit) {
+    pub(crate) fn push(&mut self, commit: Commit) {
+        self.index.insert(commit.id, self.vec.len());
         self.vec.push(commit)
     }
 
+    pub fn get(&self, id: &CommitId) -> Option<&Commit> {
+        self.index.get(id).map(|i| &self.vec[*i])
+    }
+
+    pub fn contains(&self, id: &CommitId) -> bool {
+        self.get(id).is_some()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Commit> {
+        self.vec.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.vec.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.vec.is_empty()
+    }
+
+    /// Gets a commit or fails with an unknown revision error
+    pub fn find(&self, id: &CommitId) -> Result<&Commit, Error> {
+        self.get(id)
+            .ok_or(Error::RefError(RefErrorReason::UnknownRevision(
+                id.to_hex(),
+            )))
+    }
+
+    /// Rebuilds the content of every file at a commit by replaying the changes
+    /// of its first parent chain from the first commit, or from the closest tree kept
+    /// by an earlier call
+    pub fn tree_at(&self, id: &CommitId) -> Result<Tree, Error> {
+        let mut chain = Vec::new();
+        let (mut depth, mut tree) = {
+            let trees = self.trees.lock();
+            let mut next = Some(*id);
+            loop {
+                let Some(id) = next else {
+                    break (0, Tree::new());
+                };
+                if let Some((depth, tree)) = trees.get(&id) {
+                    break (*depth, tree.clone());
+                }
+                let commit = self.find(&id)?;
+                chain.push(commit);
+                next = commit.parents.first().copied();
+            }
+        };
+
+        let mut kept = Vec::new();
+        for commit in chain.iter().rev() {
+            tree::apply_changes(&mut tree, commit.changes());
+            depth += 1;
+            if depth % TreeCache::INTERVAL == 0 {
+                kept.push((commit.id, (depth, tree.clone())));
+            }
+        }
+        self.trees.lock().extend(kept);
+        Ok(tree)
+    }
+
     pub fn save(&self, fursion_dir_path: &Path) -> Result<(), Error> {
-        self.vec
-            .iter()
-            .map(|commit| {
-                fs::write(
-                    Self::get_path(fursion_dir_path).join(commit.id.to_hex()),
-                    commit.to_string(),
-                )?;
-                Ok(())
-            })
-            .collect()
+        fs::create_dir_all(Self::get_path(fursion_dir_path))?;
+        self.vec.iter().try_for_each(|commit| {
+            fs::write(
+                Self::get_path(fursion_dir_path).join(commit.id.to_hex()),
+                commit.to_string(),
+            )?;
+            Ok(())
+        })
     }
 
     pub fn read(fursion_dir_path: &Path) -> Result<Self, Error> {
+        if !Path::exists(&Self::get_path(fursion_dir_path)) {
+            return Ok(Self::new());
+        }
+
         let commits = fs::read_dir(Self::get_path(fursion_dir_path))?
             .map(|res| {
                 let path = res?.path();
                 let data = fs::read(&path)?;
                 let s = std::str::from_utf8(&data)?;
-                let commit = Commit::from_str(s)?;
+                let commit = s.parse::<Commit>()?;
                 Ok(commit)
             })
             .collect::<Result<Vec<_>, Error>>()?;
 
-        Ok(Self { vec: commits })
+        Ok(Self::from_commits(commits))
     }
 
     fn get_path(fursion_dir_path: &Path) -> PathBuf {
@@ -118,21 +251,7 @@ impl Repo {
             ));
         }
 
-        let remotes_path = fursion_dir.join(Remote::FILE_NAME);
-        let remotes_data = if Path::exists(&remotes_path) {
-            fs::read(remotes_path.clone()).map_err(|e| {
-                Error::RepoReadFailed(RepoErrorReason::CantReadFile(
-                    remotes_path.clone(),
-                    e.to_string(),
-                ))
-            })
-        } else {
-            Ok(Vec::new())
-        }?;
-
-        let remotes_str =
-            std::str::from_utf8(&remotes_data).map_err(|e| Error::Unknown(e.to_string()))?;
-        let remotes = remotes_str.lines().map(Remote::new).collect();
+        let remotes = Remote::read_all(&fursion_dir)?;
 
         let files = recursive_read_dir(path, EXCLUDE_FURSION_DIR)?;
 
@@ -150,10 +269,11 @@ impl Repo {
             ))
         })?;
 
-        let state_path = fursion_dir.join("state");
-        let stated_changes = FileChanges::from_file(&state_path)?;
+        let stated_changes =
+            FileChanges::list_from_file(&fursion_dir.join(Self::STATED_CHANGES_FILE_NAME))?;
 
         let history = RepoHistory::read(&fursion_dir)?;
+        let refs = Refs::read(&fursion_dir)?;
         let ignored = Self::get_ignored(path)?;
 
         Ok(Repo {
@@ -163,6 +283,7 @@ impl Repo {
             files,
             remotes,
             history,
+            refs,
             ignored,
         })
     }
@@ -196,45 +317,254 @@ impl Repo {
                     .to_string_lossy()
                     .to_string(),
             },
-            stated_changes: FileChanges::default(),
+            stated_changes: Vec::new(),
             remotes: Vec::new(),
             files: recursive_read_dir(path, EXCLUDE_FURSION_DIR)?,
             history: RepoHistory::new(),
+            refs: Refs::default(),
             ignored,
         };
 
         repo.save_all()?;
+        repo.record_operation("initialize repository")?;
 
         Ok(repo)
     }
 
-    pub fn commit(&mut self, message: &str) -> Result<(), Error> {
+    /// Looks for the repo containing `path`, checking its parent directories
+    pub fn discover(path: &Path) -> Result<Self, Error> {
+        let repo_path = path
+            .ancestors()
+            .find(|dir| Path::exists(&dir.join(FURSION_DIR)))
+            .ok_or(Error::RepoReadFailed(
+                RepoErrorReason::DirIsNotAFursionRepo(path.to_owned()),
+            ))?;
+        Self::read(repo_path)
+    }
+
+    pub fn commit(&mut self, message: &str) -> Result<CommitId, Error> {
         let changes = if !self.stated_changes.is_empty() {
-            mem::replace(&mut self.stated_changes, FileChanges::default())
+            mem::take(&mut self.stated_changes)
         } else {
             self.get_diff()?
         };
 
-        let commit = Commit::new(message, changes);
+        let kind = if self.head().is_none() {
+            "commit (initial)"
+        } else {
+            "commit"
+        };
+        let author = self.metadata.author.clone();
+        let parents = self.head().into_iter().collect();
+        self.commit_changes(message, &author, parents, changes, kind)
+    }
 
-        self.history.push(commit);
+    /// Replaces the commit HEAD points to by one with the same parents and author, the
+    /// stated changes, or every change if none, added, and the new message if given
+    pub fn commit_amend(&mut self, message: Option<&str>) -> Result<CommitId, Error> {
+        let head = self.history.find(&self.head_or_err()?)?.clone();
+        let changes = if !self.stated_changes.is_empty() {
+            mem::take(&mut self.stated_changes)
+        } else {
+            self.get_diff()?
+        };
+
+        let mut tree = self.head_tree()?;
+        tree::apply_changes(&mut tree, &changes);
+        let parent_tree = match head.parents.first() {
+            Some(parent) => self.tree_at(parent)?,
+            None => Tree::new(),
+        };
+        self.commit_changes(
+            message.unwrap_or(&head.message),
+            &head.author,
+            head.parents.clone(),
+            tree::diff_trees(&parent_tree, &tree),
+            "commit (amend)",
+        )
+    }
+
+    /// Commits changes relative to the first parent and moves HEAD to the new commit,
+    /// `kind` tells in the reflog and the operation log what made it, like `commit` or `revert`
+    pub(crate) fn commit_changes(
+        &mut self,
+        message: &str,
+        author: &str,
+        parents: Vec<CommitId>,
+        changes: Vec<FileChanges>,
+        kind: &str,
+    ) -> Result<CommitId, Error> {
+        let commit = Commit::new(message, author, parents, changes);
+        let id = commit.id;
+        let description = format!("{}: {}", kind, commit.summary());
 
+        self.history.push(commit);
         self.save_history()?;
+        self.save_stated_changes()?;
+
+        self.move_head(id, &description)?;
+        self.record_operation(&description)?;
+        Ok(id)
+    }
+
+    /// The commit HEAD points to, [`None`] if nothing was committed on the current branch
+    pub fn head(&self) -> Option<CommitId> {
+        self.refs.head_commit()
+    }
+
+    /// Same as [`Repo::head`] but fails when there is no commit yet
+    pub fn head_or_err(&self) -> Result<CommitId, Error> {
+        self.head()
+            .ok_or(Error::RefError(RefErrorReason::NoCommitYet))
+    }
+
+    /// Content of the files at a commit
+    pub fn tree_at(&self, id: &CommitId) -> Result<Tree, Error> {
+        self.history.tree_at(id)
+    }
+
+    /// Content of the files at HEAD
+    pub fn head_tree(&self) -> Result<Tree, Error> {
+        match self.head() {
+            Some(id) => self.tree_at(&id),
+            None => Ok(Tree::new()),
+        }
+    }
+
+    /// Content of the files at HEAD with the stated changes applied
+    pub fn index_tree(&self) -> Result<Tree, Error> {
+        let mut tree = self.head_tree()?;
+        tree::apply_changes(&mut tree, &self.stated_changes);
+        Ok(tree)
+    }
+
+    /// Content of the files in the working directory
+    pub fn working_tree(&self) -> Result<Tree, Error> {
+        tree::read_working_tree(&self.path, &self.ignored)
+    }
+
+    /// Changes between HEAD and the working directory
+    pub fn get_diff(&self) -> Result<Vec<FileChanges>, Error> {
+        Ok(tree::diff_trees(&self.head_tree()?, &self.working_tree()?))
+    }
+
+    /// Changes of the working directory which are not stated yet
+    pub fn get_unstated_diff(&self) -> Result<Vec<FileChanges>, Error> {
+        Ok(tree::diff_trees(&self.index_tree()?, &self.working_tree()?))
+    }
+
+    /// Whether there are stated changes or changes in the working directory
+    pub fn has_uncommitted_changes(&self) -> Result<bool, Error> {
+        Ok(!self.stated_changes.is_empty() || !self.get_diff()?.is_empty())
+    }
+
+    /// States the changes of the working directory for the given paths, every change if empty
+    pub fn stage(&mut self, paths: &[&str]) -> Result<(), Error> {
+        let mut index = self.index_tree()?;
+        let working = self.working_tree()?;
+        let selected =
+            |path: &str| paths.is_empty() || paths.iter().any(|p| tree::path_matches(path, p));
+
+        index.retain(|path, _| !selected(path) || working.contains_key(path));
+        for (path, content) in working.iter().filter(|(path, _)| selected(path)) {
+            index.insert(path.clone(), content.clone());
+        }
+
+        self.stated_changes = tree::diff_trees(&self.head_tree()?, &index);
+        self.save_stated_changes()?;
+        self.record_operation(&format!("stage: {}", describe_paths(paths)))?;
+        Ok(())
+    }
+
+    /// Removes the stated changes of the given paths, every change if empty
+    pub fn unstage(&mut self, paths: &[&str]) -> Result<(), Error> {
+        self.stated_changes.retain(|changes| {
+            !paths.is_empty() && !paths.iter().any(|p| tree::path_matches(changes.path(), p))
+        });
+        self.save_stated_changes()?;
+        self.record_operation(&format!("unstage: {}", describe_paths(paths)))?;
+        Ok(())
+    }
+
+    /// Creates a branch pointing to `start`, HEAD if [`None`]
+    pub fn create_branch(&mut self, name: &str, start: Option<CommitId>) -> Result<(), Error> {
+        Refs::check_name(name)?;
+        if self.refs.branches.contains_key(name) {
+            return Err(Error::RefError(RefErrorReason::BranchAlreadyExists(
+                name.to_owned(),
+            )));
+        }
+        let target = match start {
+            Some(id) => id,
+            None => self.head_or_err()?,
+        };
+        self.refs.branches.insert(name.to_owned(), target);
+        self.log_ref_update(name, None, Some(target), "branch: Created")?;
+        self.save_refs()?;
+        self.record_operation(&format!("create branch {}", name))?;
         Ok(())
     }
 
-    ///WIP
-    pub fn get_diff(&self) -> Result<FileChanges, Error> {
-        Ok(FileChanges::default())
+    pub fn delete_branch(&mut self, name: &str) -> Result<(), Error> {
+        if self.refs.current_branch() == Some(name) {
+            return Err(Error::RefError(RefErrorReason::CantDeleteCurrentBranch(
+                name.to_owned(),
+            )));
+        }
+        self.refs
+            .branches
+            .remove(name)
+            .ok_or(Error::RefError(RefErrorReason::BranchNotFound(
+                name.to_owned(),
+            )))?;
+        Reflog::delete(&self.path.join(FURSION_DIR), name)?;
+        self.save_refs()?;
+        self.record_operation(&format!("delete branch {}", name))?;
+        Ok(())
+    }
+
+    /// Checks out a branch, or detaches HEAD at any other revision,
+    /// fails if there are uncommitted changes
+    pub fn checkout(&mut self, target: &str) -> Result<(), Error> {
+        if self.has_uncommitted_changes()? {
+            return Err(Error::UncommittedChanges);
+        }
+
+        let head = if self.refs.branches.contains_key(target) {
+            Head::Branch(target.to_owned())
+        } else {
+            Head::Detached(self.resolve(target)?)
+        };
+
+        let old_tree = self.head_tree()?;
+        let old_head = self.head();
+        let from = match &self.refs.head {
+            Head::Branch(name) => name.clone(),
+            Head::Detached(id) => id.to_hex(),
+        };
+
+        self.refs.head = head;
+        let new_tree = self.head_tree()?;
+        tree::write_working_tree(&self.path, &old_tree, &new_tree)?;
+        self.reread_files()?;
+
+        self.log_ref_update(
+            Refs::HEAD_FILE_NAME,
+            old_head,
+            self.head(),
+            &format!("checkout: moving from {} to {}", from, target),
+        )?;
+        self.save_refs()?;
+        self.record_operation(&format!("checkout {}", target))?;
+        Ok(())
     }
 
     pub fn save_all(&self) -> Result<(), Error> {
         self.save_history()?;
         self.save_metadata()?;
         self.save_stated_changes()?;
-
-        let fursion_dir = self.path.join(FURSION_DIR);
-        fs::write(fursion_dir.join(Remote::FILE_NAME), "")?; // WIP
+        self.save_refs()?;
+        self.save_remotes()?;
 
         Ok(())
     }
@@ -244,6 +574,11 @@ impl Repo {
         self.history.save(&fursion_dir)
     }
 
+    pub fn save_refs(&self) -> Result<(), Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        self.refs.save(&fursion_dir)
+    }
+
     pub fn save_metadata(&self) -> Result<(), Error> {
         let fursion_dir = self.path.join(FURSION_DIR);
         fs::write(
@@ -257,7 +592,7 @@ impl Repo {
         let fursion_dir = self.path.join(FURSION_DIR);
         fs::write(
             fursion_dir.join(Self::STATED_CHANGES_FILE_NAME),
-            self.stated_changes.to_string(),
+            FileChanges::list_to_string(&self.stated_changes),
         )?;
         Ok(())
     }
@@ -269,7 +604,7 @@ impl Repo {
             let str = std::str::from_utf8(&data)?;
             str.lines()
                 .filter_map(|line| {
-                    if line == "" || line.starts_with("#") || line.starts_with("//") {
+                    if line.is_empty() || line.starts_with("#") || line.starts_with("//") {
                         None
                     } else {
                         Some(line.to_owned())
@@ -282,24 +617,10 @@ impl Repo {
         Ok(ignored)
     }
 
-    pub fn pull(&mut self) {}
-
-    pub async fn fetch_remote(&self, remote: &Remote) -> Result<Repo, Error> {
-        let res = reqwest::get(remote.get())
-            .await
-            .map_err(|e| Error::RepoFetchFailed(e.to_string()))?;
-
-        let repo = res
-            .json()
-            .await
-            .map_err(|e| Error::RepoFetchFailed(e.to_string()))?;
-
-        Ok(repo)
-    }
-
-    pub async fn fetch(&self) -> Vec<Result<Repo, Error>> {
-        let iter = self.remotes.iter().map(|r| self.fetch_remote(r));
-        futures::future::join_all(iter).await
+    /// Lists the files of the working directory again after they were written
+    pub(crate) fn reread_files(&mut self) -> Result<(), Error> {
+        self.files = recursive_read_dir(&self.path, EXCLUDE_FURSION_DIR)?;
+        Ok(())
     }
 
     /// Reloads the repo's data by mutating, and returns the old data
@@ -309,6 +630,15 @@ impl Repo {
     }
 }
 
+/// Paths given to a stage like call as shown in the operation log
+fn describe_paths(paths: &[&str]) -> String {
+    if paths.is_empty() {
+        "every change".to_owned()
+    } else {
+        paths.join(", ")
+    }
+}
+
 /// Recursively reads a directory outputting a vec of {File} object
 fn recursive_read_dir(
     path: &Path,
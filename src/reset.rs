@@ -0,0 +1,73 @@
+use crate::{
+    commit::CommitId,
+    error::Error,
+    repo::Repo,
+    tree::{self, Tree},
+};
+
+/// What a reset changes besides moving HEAD
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ResetMode {
+    /// Keeps the stated changes and the working directory, the difference with the
+    /// new HEAD being stated
+    Soft,
+    /// Drops the stated changes and keeps the working directory
+    #[default]
+    Mixed,
+    /// Drops the stated changes and puts the files back as they are at the new HEAD,
+    /// files which were never committed nor stated are kept
+    Hard,
+}
+
+impl ResetMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ResetMode::Soft => "soft",
+            ResetMode::Mixed => "mixed",
+            ResetMode::Hard => "hard",
+        }
+    }
+}
+
+impl Repo {
+    /// Moves HEAD, and the checked out branch if any, to a commit
+    pub fn reset(&mut self, target: CommitId, mode: ResetMode) -> Result<(), Error> {
+        self.history.find(&target)?;
+        let old_head_tree = self.head_tree()?;
+        let old_index = self.index_tree()?;
+        let target_tree = self.tree_at(&target)?;
+
+        match mode {
+            ResetMode::Soft => {
+                self.stated_changes = tree::diff_trees(&target_tree, &old_index);
+            }
+            ResetMode::Mixed => self.stated_changes.clear(),
+            ResetMode::Hard => {
+                self.stated_changes.clear();
+                let working = self.working_tree()?;
+                let mut new_working: Tree = working
+                    .iter()
+                    .filter(|(path, _)| {
+                        !old_head_tree.contains_key(*path)
+                            && !old_index.contains_key(*path)
+                            && !target_tree.contains_key(*path)
+                    })
+                    .map(|(path, content)| (path.clone(), content.clone()))
+                    .collect();
+                new_working.extend(target_tree.clone());
+                tree::write_working_tree(&self.path, &working, &new_working)?;
+                self.reread_files()?;
+            }
+        }
+        self.save_stated_changes()?;
+
+        let message = format!("reset: moving to {}", target.to_hex());
+        self.move_head(target, &message)?;
+        self.record_operation(&format!(
+            "reset ({}): moving to {}",
+            mode.name(),
+            target.to_hex()
+        ))?;
+        Ok(())
+    }
+}
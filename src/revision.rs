@@ -0,0 +1,234 @@
+use std::collections::HashSet;
+
+use crate::{
+    commit::CommitId,
+    error::{Error, RefErrorReason},
+    repo::{Repo, RepoHistory},
+};
+
+/// Minimum number of hex digits for a short commit id
+pub const MIN_SHORT_ID_LEN: usize = 4;
+
+/// A set of commits described by revision expressions, the commits reachable
+/// from `include` but not from `exclude`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RevisionSet {
+    pub include: Vec<CommitId>,
+    pub exclude: Vec<CommitId>,
+}
+
+/// One step applied after the base of a revision
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Suffix {
+    /// `~n`, the n-th first parent generation
+    Ancestor(usize),
+    /// `^n`, the n-th parent, `^0` being the commit itself
+    Parent(usize),
+}
+
+impl Repo {
+    /// Resolves a revision expression to a single commit
+    ///
+    /// The expression is a base, `HEAD` (or `@`), a branch, a tag, a remote-tracking branch
+    /// like `origin/main`, or a unique prefix of at least [`MIN_SHORT_ID_LEN`] hex digits
+    /// of a commit id, optionally followed by
+    /// `@{n}` for the n-th previous value of a ref according to its reflog,
+    /// then by any number of `~n` and `^n`
+    pub fn resolve(&self, rev: &str) -> Result<CommitId, Error> {
+        let invalid = || Error::RefError(RefErrorReason::InvalidRevision(rev.to_owned()));
+
+        let base_end = rev.find(['~', '^']).unwrap_or(rev.len());
+        let (base, suffixes) = rev.split_at(base_end);
+
+        let mut id = match base.find("@{") {
+            Some(start) => {
+                let index = base[start + 2..]
+                    .strip_suffix('}')
+                    .and_then(|n| n.parse::<usize>().ok())
+                    .ok_or_else(invalid)?;
+                let name = match &base[..start] {
+                    "" | "@" => "HEAD",
+                    name => name,
+                };
+                self.resolve_reflog(name, index)?
+            }
+            None => self.resolve_base(base)?,
+        };
+
+        for suffix in parse_suffixes(suffixes).ok_or_else(invalid)? {
+            id = match suffix {
+                Suffix::Ancestor(n) => (0..n).try_fold(id, |id, _| self.nth_parent(&id, 1, rev))?,
+                Suffix::Parent(0) => id,
+                Suffix::Parent(n) => self.nth_parent(&id, n, rev)?,
+            };
+        }
+        Ok(id)
+    }
+
+    /// Resolves a list of revision expressions, each being a single revision, `^A` to exclude
+    /// the commits reachable from `A`, `A..B` for the commits reachable from `B` but not `A`,
+    /// or `A...B` for the commits reachable from either but not both, a missing side being HEAD
+    pub fn resolve_set(&self, revs: &[&str]) -> Result<RevisionSet, Error> {
+        let mut set = RevisionSet::default();
+        for rev in revs {
+            let side = |s: &str| {
+                if s.is_empty() {
+                    self.head_or_err()
+                } else {
+                    self.resolve(s)
+                }
+            };
+
+            if let Some((a, b)) = rev.split_once("...") {
+                let (a, b) = (side(a)?, side(b)?);
+                set.include.extend([a, b]);
+                set.exclude.extend(self.history.merge_bases(&a, &b));
+            } else if let Some((a, b)) = rev.split_once("..") {
+                set.exclude.push(side(a)?);
+                set.include.push(side(b)?);
+            } else if let Some(excluded) = rev.strip_prefix('^') {
+                set.exclude.push(self.resolve(excluded)?);
+            } else {
+                set.include.push(self.resolve(rev)?);
+            }
+        }
+        Ok(set)
+    }
+
+    /// Resolves a ref name or a commit id prefix
+    fn resolve_base(&self, base: &str) -> Result<CommitId, Error> {
+        if base == "HEAD" || base == "@" {
+            return self.head_or_err();
+        }
+        if let Some(id) = self.refs.branches.get(base) {
+            return Ok(*id);
+        }
+        if let Some(tag) = self.refs.tags.get(base) {
+            return Ok(tag.target());
+        }
+        if let Some(id) = self.refs.remotes.get(base) {
+            return Ok(*id);
+        }
+
+        let unknown = || Error::RefError(RefErrorReason::UnknownRevision(base.to_owned()));
+        if base.len() < MIN_SHORT_ID_LEN
+            || base.len() > CommitId::HEX_LEN
+            || !base.chars().all(|c| c.is_ascii_hexdigit())
+        {
+            return Err(unknown());
+        }
+
+        let mut matches = self
+            .history
+            .iter()
+            .map(|commit| commit.id)
+            .filter(|id| id.starts_with(base))
+            .collect::<Vec<_>>();
+        matches.sort();
+        match matches.as_slice() {
+            [] => Err(unknown()),
+            [id] => Ok(*id),
+            _ => Err(Error::RefError(RefErrorReason::AmbiguousRevision(
+                base.to_owned(),
+                matches.iter().map(CommitId::to_hex).collect(),
+            ))),
+        }
+    }
+
+    fn nth_parent(&self, id: &CommitId, n: usize, rev: &str) -> Result<CommitId, Error> {
+        self.history
+            .find(id)?
+            .parents
+            .get(n - 1)
+            .copied()
+            .ok_or(Error::RefError(RefErrorReason::ParentNotFound(
+                rev.to_owned(),
+                id.to_hex(),
+            )))
+    }
+}
+
+/// Parses a sequence of `~n` and `^n`, the number defaulting to 1
+fn parse_suffixes(s: &str) -> Option<Vec<Suffix>> {
+    let mut suffixes = Vec::new();
+    let mut rest = s;
+    while let Some(c) = rest.chars().next() {
+        if c != '~' && c != '^' {
+            return None;
+        }
+        let digits = rest[1..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(rest.len(), |i| i + 1);
+        let n = match &rest[1..digits] {
+            "" => 1,
+            n => n.parse().ok()?,
+        };
+        suffixes.push(match c {
+            '~' => Suffix::Ancestor(n),
+            _ => Suffix::Parent(n),
+        });
+        rest = &rest[digits..];
+    }
+    Some(suffixes)
+}
+
+impl RepoHistory {
+    /// Every commit reachable from `id`, itself included
+    pub fn ancestors(&self, id: &CommitId) -> HashSet<CommitId> {
+        self.ancestors_of_all(&[*id])
+    }
+
+    /// Every commit reachable from one of the `ids`, themselves included
+    pub fn ancestors_of_all(&self, ids: &[CommitId]) -> HashSet<CommitId> {
+        let mut seen = HashSet::new();
+        let mut stack = ids.to_vec();
+        while let Some(id) = stack.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            if let Some(commit) = self.get(&id) {
+                stack.extend(commit.parents.iter().copied());
+            }
+        }
+        seen
+    }
+
+    /// Whether `ancestor` is reachable from `descendant`, a commit being its own ancestor
+    pub fn is_ancestor(&self, ancestor: &CommitId, descendant: &CommitId) -> bool {
+        self.ancestors(descendant).contains(ancestor)
+    }
+
+    /// The best common ancestors of two commits, the ones which are not
+    /// an ancestor of another common ancestor
+    pub fn merge_bases(&self, a: &CommitId, b: &CommitId) -> Vec<CommitId> {
+        let a_ancestors = self.ancestors(a);
+        let common: HashSet<CommitId> = self
+            .ancestors(b)
+            .into_iter()
+            .filter(|id| a_ancestors.contains(id))
+            .collect();
+
+        let parents_of_common = common
+            .iter()
+            .filter_map(|id| self.get(id))
+            .flat_map(|commit| commit.parents.iter().copied())
+            .collect::<Vec<_>>();
+        let dominated = self.ancestors_of_all(&parents_of_common);
+
+        let mut bases = common
+            .into_iter()
+            .filter(|id| !dominated.contains(id))
+            .collect::<Vec<_>>();
+        bases.sort();
+        bases
+    }
+
+    /// The commits reachable from the included commits but not from the excluded ones
+    pub fn resolve_set(&self, set: &RevisionSet) -> HashSet<CommitId> {
+        let excluded = self.ancestors_of_all(&set.exclude);
+        self.ancestors_of_all(&set.include)
+            .into_iter()
+            .filter(|id| !excluded.contains(id))
+            .collect()
+    }
+}
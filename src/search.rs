@@ -0,0 +1,162 @@
+use std::collections::HashMap;
+
+use regex::Regex;
+
+use crate::{
+    commit::{Commit, CommitId},
+    error::Error,
+    log::LogQuery,
+    repo::Repo,
+    tree::{self, Tree},
+};
+
+/// What is searched for, a plain string or a regular expression
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Text(String),
+    Regex(Regex),
+}
+
+impl Pattern {
+    pub fn text(text: &str) -> Self {
+        Pattern::Text(text.to_owned())
+    }
+
+    /// A plain string matched whatever the case of the letters
+    pub fn text_ignore_case(text: &str) -> Self {
+        let regex = Regex::new(&format!("(?i){}", regex::escape(text)))
+            .expect("an escaped string is a valid regex");
+        Pattern::Regex(regex)
+    }
+
+    pub fn regex(expression: &str) -> Result<Self, Error> {
+        Ok(Pattern::Regex(Regex::new(expression)?))
+    }
+
+    pub fn is_match(&self, haystack: &str) -> bool {
+        match self {
+            Pattern::Text(text) => haystack.contains(text.as_str()),
+            Pattern::Regex(regex) => regex.is_match(haystack),
+        }
+    }
+
+    /// Number of non overlapping occurrences
+    pub fn count(&self, haystack: &str) -> usize {
+        match self {
+            Pattern::Text(text) if text.is_empty() => 0,
+            Pattern::Text(text) => haystack.matches(text.as_str()).count(),
+            Pattern::Regex(regex) => regex.find_iter(haystack).count(),
+        }
+    }
+}
+
+/// A line matching a grep pattern
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrepMatch {
+    pub path: String,
+    /// Starting at 1
+    pub line_number: usize,
+    pub line: String,
+}
+
+/// A commit changing the number of occurrences of a pickaxe pattern
+#[derive(Debug, Clone)]
+pub struct PickaxeMatch<'a> {
+    pub commit: &'a Commit,
+    /// Paths of the files in which the pattern was added or removed
+    pub paths: Vec<String>,
+}
+
+/// Every line of the files of a tree matching the pattern, only in the given files
+/// or directories if `paths` isn't empty
+pub fn grep_tree(tree: &Tree, pattern: &Pattern, paths: &[String]) -> Vec<GrepMatch> {
+    tree.iter()
+        .filter(|(path, _)| paths.is_empty() || paths.iter().any(|p| tree::path_matches(path, p)))
+        .flat_map(|(path, content)| {
+            content
+                .lines()
+                .enumerate()
+                .filter(|(_, line)| pattern.is_match(line))
+                .map(|(i, line)| GrepMatch {
+                    path: path.clone(),
+                    line_number: i + 1,
+                    line: line.to_owned(),
+                })
+        })
+        .collect()
+}
+
+impl Repo {
+    /// Searches the files as they are at a commit, or in the working directory if [`None`]
+    pub fn grep(
+        &self,
+        at: Option<CommitId>,
+        pattern: &Pattern,
+        paths: &[String],
+    ) -> Result<Vec<GrepMatch>, Error> {
+        let tree = match at {
+            Some(id) => self.tree_at(&id)?,
+            None => self.working_tree()?,
+        };
+        Ok(grep_tree(&tree, pattern, paths))
+    }
+
+    /// Commits of the given revision expressions matching the query whose changes add or
+    /// remove occurrences of the pattern, that is the ones after which a file doesn't hold
+    /// it the same number of times, compared to the first parent
+    pub fn pickaxe(
+        &self,
+        revs: &[&str],
+        query: &LogQuery,
+        pattern: &Pattern,
+    ) -> Result<Vec<PickaxeMatch<'_>>, Error> {
+        let unlimited = LogQuery {
+            max_count: None,
+            ..query.clone()
+        };
+        let commits = self.log(revs, &unlimited)?;
+
+        // The commits are gone through from the oldest, the files of a commit being
+        // rebuilt from those of its parent when it was the one just gone through
+        let mut changed_paths: HashMap<CommitId, Vec<String>> = HashMap::new();
+        let mut previous: Option<(CommitId, Tree)> = None;
+        for commit in commits.iter().rev() {
+            let mut tree = match (previous.take(), commit.parents.first()) {
+                (Some((id, tree)), Some(parent)) if id == *parent => tree,
+                (_, Some(parent)) => self.tree_at(parent)?,
+                (_, None) => Tree::new(),
+            };
+
+            let paths = commit
+                .changes()
+                .iter()
+                .filter(|changes| {
+                    let old = tree.get(changes.path()).map(String::as_str).unwrap_or("");
+                    let new = if changes.is_removal() {
+                        String::new()
+                    } else {
+                        changes.apply(old)
+                    };
+                    pattern.count(old) != pattern.count(&new)
+                })
+                .map(|changes| changes.path().to_owned())
+                .collect::<Vec<_>>();
+            if !paths.is_empty() {
+                changed_paths.insert(commit.id, paths);
+            }
+
+            tree::apply_changes(&mut tree, commit.changes());
+            previous = Some((commit.id, tree));
+        }
+
+        let matches = commits
+            .into_iter()
+            .filter_map(|commit| {
+                let paths = changed_paths.remove(&commit.id)?;
+                Some(PickaxeMatch { commit, paths })
+            })
+            .take(query.max_count.unwrap_or(usize::MAX))
+            .collect();
+        Ok(matches)
+    }
+}
@@ -1,59 +1,339 @@
-use std::sync::Arc;
+use std::{
+    collections::{btree_map::Entry, BTreeMap},
+    convert::Infallible,
+    fs,
+    path::{Path, PathBuf},
+    sync::Arc,
+};
 
-use actix_web::{get, web, App, HttpServer, Responder};
-use futures::lock::Mutex;
-use once_cell::sync::Lazy;
+use actix_web::{
+    get, http::header::AUTHORIZATION, post, web, App, HttpRequest, HttpResponse, HttpServer,
+    Responder,
+};
+use serde::{Deserialize, Serialize};
+use tokio::sync::{OnceCell, RwLock, RwLockWriteGuard};
 
 use crate::{
-    error::{Error, HostErrorKind},
-    repo::Repo,
+    access::Permission,
+    auth::UserStore,
+    commit::CommitId,
+    credential::Credentials,
+    error::{AuthErrorReason, Error, HostErrorKind},
+    fetch::FetchRequest,
+    pack::Pack,
+    push::PushRequest,
+    refs::Refs,
+    repo::{Repo, RepoMetadata, FURSION_DIR},
 };
 
-static SERVER_STATE: Lazy<Arc<Mutex<ServerState>>> =
-    Lazy::new(|| Arc::new(Mutex::new(ServerState::default())));
+/// Largest request body accepted, pushes carry whole commits
+const MAX_PAYLOAD_SIZE: usize = 64 * 1024 * 1024;
+
+/// The state shared by the workers of a server, only locked for writing to find repos
+/// and reload, every hosted repo having its own lock
+type SharedState = web::Data<RwLock<ServerState>>;
+
+/// What the server tells about a hosted repo, leaving out its files and where it is on
+/// the server, the commits being sent by the fetch endpoint
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct RepoSummary {
+    pub metadata: RepoMetadata,
+    pub refs: Refs,
+}
 
 /// Repo hosting commands wrapper
-pub struct Server;
+pub struct Server {
+    state: SharedState,
+}
+
+/// A repo found under the root dir, only read on its first request
+#[derive(Debug)]
+struct HostedRepo {
+    path: PathBuf,
+    repo: OnceCell<RwLock<Repo>>,
+}
+
+impl HostedRepo {
+    fn new(path: PathBuf) -> Arc<Self> {
+        Arc::new(HostedRepo {
+            path,
+            repo: OnceCell::new(),
+        })
+    }
+
+    /// The lock of the repo, which is read from its files on the first call
+    async fn repo(&self) -> Result<&RwLock<Repo>, Error> {
+        self.repo
+            .get_or_try_init(|| async {
+                if !self.path.join(FURSION_DIR).is_dir() {
+                    return Err(Error::HostError(HostErrorKind::RepoNotFound));
+                }
+                Ok(RwLock::new(Repo::read(&self.path)?))
+            })
+            .await
+    }
+
+    /// The repo locked for writing, read again from its files first since they may have
+    /// been changed outside of the server, by a local push or a commit, and saving the
+    /// cached repo would undo those changes
+    async fn write(&self) -> Result<RwLockWriteGuard<'_, Repo>, Error> {
+        let mut repo = self.repo().await?.write().await;
+        *repo = Repo::read(&self.path)?;
+        Ok(repo)
+    }
+}
 
 #[derive(Debug, Default)]
 struct ServerState {
-    pub repos: Vec<Repo>,
+    /// Dir holding the users and the hosted repos, as `{author}/{repo}`
+    pub root: PathBuf,
+    /// The hosted repos by author and name
+    pub repos: BTreeMap<(String, String), Arc<HostedRepo>>,
+    /// Shared so that credentials are checked without holding the state lock
+    pub users: Arc<UserStore>,
+}
+
+impl ServerState {
+    /// Finds the repos under the root dir and reads the users, the repos which were read
+    /// are read again on their next request
+    fn discover(&mut self) -> Result<(), Error> {
+        let mut repos = BTreeMap::new();
+        for author in fs::read_dir(&self.root)? {
+            let author = author?;
+            let Some(author_name) = author.file_name().to_str().map(str::to_owned) else {
+                continue;
+            };
+            if author_name.starts_with('.') || !author.file_type()?.is_dir() {
+                continue;
+            }
+            for repo in fs::read_dir(author.path())? {
+                let path = repo?.path();
+                let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+                    continue;
+                };
+                if name.starts_with('.') || !path.join(FURSION_DIR).is_dir() {
+                    continue;
+                }
+                let key = (author_name.clone(), name.to_owned());
+                repos.insert(key, HostedRepo::new(path));
+            }
+        }
+        self.repos = repos;
+        self.users = Arc::new(UserStore::read(&self.root)?);
+        Ok(())
+    }
+
+    /// A hosted repo, repos made under the root dir since the last discovery being
+    /// found as well
+    fn find_repo(&mut self, author: &str, name: &str) -> Result<Arc<HostedRepo>, Error> {
+        match self.repos.entry((author.to_owned(), name.to_owned())) {
+            Entry::Occupied(entry) => Ok(entry.get().clone()),
+            Entry::Vacant(entry) => {
+                let path = self.root.join(author).join(name);
+                if author.starts_with('.')
+                    || name.starts_with('.')
+                    || !path.join(FURSION_DIR).is_dir()
+                {
+                    return Err(Error::HostError(HostErrorKind::RepoNotFound));
+                }
+                Ok(entry.insert(HostedRepo::new(path)).clone())
+            }
+        }
+    }
+}
+
+/// The user sending a request, from the credentials of its `Authorization` header,
+/// [`None`] for anonymous requests, the slow password hashing being run on the blocking
+/// thread pool
+async fn authenticate(users: Arc<UserStore>, req: &HttpRequest) -> Result<Option<String>, Error> {
+    let Some(header) = req.headers().get(AUTHORIZATION) else {
+        return Ok(None);
+    };
+    let header = header
+        .to_str()
+        .map_err(|_| Error::AuthError(AuthErrorReason::InvalidCredentials))?;
+    let credentials = Credentials::from_header(header)?;
+    let user = web::block(move || users.authenticate(&credentials))
+        .await
+        .map_err(|_| Error::HostError(HostErrorKind::TaskFailed))??;
+    Ok(Some(user))
+}
+
+/// The repo a request is for along with the permission of its sender, refused if it is
+/// below `needed`, repos a user or an anonymous sender can't read being reported as not
+/// found
+async fn authorize(
+    state: &SharedState,
+    req: &HttpRequest,
+    author: &str,
+    name: &str,
+    needed: Permission,
+) -> Result<(Arc<HostedRepo>, Permission), Error> {
+    let (users, found) = {
+        let state = state.read().await;
+        let found = state
+            .repos
+            .get(&(author.to_owned(), name.to_owned()))
+            .cloned();
+        (state.users.clone(), found)
+    };
+    let user = authenticate(users.clone(), req).await?;
+    let hosted = match found {
+        Some(hosted) => hosted,
+        None => state.write().await.find_repo(author, name)?,
+    };
+    let access = hosted.repo().await?.read().await.access_control()?;
+    let permission = access.permission(user.as_deref(), author, &users);
+    match permission {
+        Some(permission) if permission >= needed => Ok((hosted, permission)),
+        None => Err(Error::HostError(HostErrorKind::RepoNotFound)),
+        Some(_) if user.is_none() => Err(Error::AuthError(AuthErrorReason::MissingCredentials)),
+        Some(_) => Err(Error::AuthError(AuthErrorReason::PermissionDenied(
+            needed.name().to_owned(),
+        ))),
+    }
 }
 
 #[get("/{author}/{repo}")]
 async fn get_repo(
-    author: web::Path<String>,
-    repo: web::Path<String>,
+    state: SharedState,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> actix_web::Result<impl Responder> {
+    let (author, repo) = path.into_inner();
+    let (hosted, _) = authorize(&state, &req, &author, &repo, Permission::Read).await?;
+
+    let repo = hosted.repo().await?.read().await;
+    Ok(web::Json(RepoSummary {
+        metadata: repo.metadata.clone(),
+        refs: repo.refs.clone(),
+    }))
+}
+
+#[get("/{author}/{repo}/refs")]
+async fn get_refs(
+    state: SharedState,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
 ) -> actix_web::Result<impl Responder> {
-    let server_state = SERVER_STATE
-        .try_lock()
-        .ok_or(Error::HostError(HostErrorKind::StateMutexThreadLocked))?;
+    let (author, repo) = path.into_inner();
+    let (hosted, _) = authorize(&state, &req, &author, &repo, Permission::Read).await?;
+
+    let refs = hosted.repo().await?.read().await.refs.clone();
+    Ok(web::Json(refs))
+}
 
-    let repo_obj = server_state
-        .repos
-        .iter()
-        .find(|r| r.metadata.name == *repo && r.metadata.author == *author)
-        .ok_or(Error::HostError(HostErrorKind::RepoNotFound))
-        .and_then(|r| Ok((*r).clone()))?;
+/// Streams the commits a client wants and doesn't have as a pack
+#[post("/{author}/{repo}/fetch")]
+async fn send_pack(
+    state: SharedState,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    request: web::Json<FetchRequest>,
+) -> actix_web::Result<HttpResponse> {
+    let (author, repo) = path.into_inner();
+    let (hosted, _) = authorize(&state, &req, &author, &repo, Permission::Read).await?;
 
-    Ok(web::Json(repo_obj))
+    let pack = hosted.repo().await?.read().await.pack_missing(&request)?;
+    let chunks = std::iter::once(Pack::header(pack.commits.len()))
+        .chain(pack.commits.into_iter().map(|commit| Pack::entry(&commit)))
+        .map(|chunk| Ok::<_, Infallible>(web::Bytes::from(chunk)));
+    Ok(HttpResponse::Ok()
+        .content_type(Pack::CONTENT_TYPE)
+        .streaming(futures::stream::iter(chunks)))
+}
+
+/// Answers which of the commits a client is about to push are missing
+#[post("/{author}/{repo}/push/negotiate")]
+async fn negotiate_push(
+    state: SharedState,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    ids: web::Json<Vec<CommitId>>,
+) -> actix_web::Result<impl Responder> {
+    let (author, repo) = path.into_inner();
+    let (hosted, _) = authorize(&state, &req, &author, &repo, Permission::Write).await?;
+
+    let missing = hosted.repo().await?.read().await.missing_commits(&ids);
+    Ok(web::Json(missing))
+}
+
+#[post("/{author}/{repo}/push")]
+async fn receive_push(
+    state: SharedState,
+    req: HttpRequest,
+    path: web::Path<(String, String)>,
+    request: web::Json<PushRequest>,
+) -> actix_web::Result<impl Responder> {
+    let (author, repo) = path.into_inner();
+    let (hosted, permission) = authorize(&state, &req, &author, &repo, Permission::Write).await?;
+
+    let mut repo = hosted.write().await?;
+    let access = repo.access_control()?;
+    let report = repo.receive_push_guarded(request.into_inner(), &|branch, rewrites| {
+        access.allows_branch_update(branch, rewrites, permission)
+    })?;
+    Ok(web::Json(report))
+}
+
+/// Adds the shared state and the endpoints of a server to an app
+fn routes(state: SharedState, config: &mut web::ServiceConfig) {
+    config
+        .app_data(state)
+        .app_data(web::JsonConfig::default().limit(MAX_PAYLOAD_SIZE))
+        .service(get_repo)
+        .service(get_refs)
+        .service(send_pack)
+        .service(negotiate_push)
+        .service(receive_push);
 }
 
 impl Server {
-    pub async fn new() -> Result<Self, Error> {
-        HttpServer::new(|| App::new().service(get_repo))
-            .bind(("127.0.0.1", 54510))?
-            .run()
-            .await?;
+    /// Address served by default, on this machine only
+    pub const DEFAULT_ADDRESS: &'static str = "127.0.0.1:54510";
+
+    /// Finds the repos under `root`, laid out as `{author}/{repo}`, and the users of its
+    /// [`UserStore`], the author of a repo being its admin, see
+    /// [`AccessControl`](crate::access::AccessControl)
+    pub fn new(root: &Path) -> Result<Self, Error> {
+        let mut state = ServerState {
+            root: root.to_owned(),
+            ..Default::default()
+        };
+        state.discover()?;
+        Ok(Server {
+            state: web::Data::new(RwLock::new(state)),
+        })
+    }
 
-        Ok(Server)
+    /// Adds the endpoints of the server to an app, to serve it along with other services
+    pub fn configure(&self, config: &mut web::ServiceConfig) {
+        routes(self.state.clone(), config);
     }
 
-    async fn reload(&mut self, repos: Vec<Repo>) -> Result<(), Error> {
+    /// Serves the hosted repos on `address`, like [`Server::DEFAULT_ADDRESS`], until the
+    /// server is stopped
+    pub async fn run(&self, address: &str) -> Result<(), Error> {
+        let state = self.state.clone();
+        HttpServer::new(move || {
+            let state = state.clone();
+            App::new().configure(move |config| routes(state, config))
+        })
+        .bind(address)?
+        .run()
+        .await?;
         Ok(())
     }
 
-    fn host_repo(self, repo: Repo){
-        SERVER_STATE.lock().unwrap()
+    /// Finds the repos under the root dir again, picking up the new ones and dropping the
+    /// removed ones, and reads the users again, the repos are read again from their files
+    /// on their next request
+    pub async fn reload(&self) -> Result<(), Error> {
+        self.state.write().await.discover()
+    }
+
+    /// Author and name of the hosted repos
+    pub async fn repos(&self) -> Vec<(String, String)> {
+        self.state.read().await.repos.keys().cloned().collect()
     }
 }
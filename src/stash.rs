@@ -0,0 +1,252 @@
+use std::{fmt, fs, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commit::{self, Commit, CommitId, FileChanges},
+    error::{Error, StashErrorReason},
+    merge::{self, MergeLabels},
+    refs::Head,
+    repo::{Repo, FURSION_DIR},
+    tree::{self, Tree},
+};
+
+/// Uncommitted work set aside
+#[derive(Debug, Deserialize, Serialize, Clone)]
+pub struct StashEntry {
+    /// The commit HEAD pointed to when the work was stashed, [`None`] before the first commit
+    pub base: Option<CommitId>,
+    /// Seconds since the unix epoch at which the work was stashed
+    pub timestamp: u64,
+    pub message: String,
+    /// The stated changes, relative to the base
+    pub stated: Vec<FileChanges>,
+    /// The changes of the working directory which were not stated, relative to the stated state
+    pub unstated: Vec<FileChanges>,
+}
+
+impl StashEntry {
+    const INDEX_DELIMITER: &'static str = "\nEND_FURSION_STASH_INDEX\n";
+    const NO_COMMIT: &'static str = "-";
+
+    /// Content of the files which were stated
+    pub fn index_tree(&self, base_tree: &Tree) -> Tree {
+        let mut tree = base_tree.clone();
+        tree::apply_changes(&mut tree, &self.stated);
+        tree
+    }
+
+    /// Content of the files of the working directory
+    pub fn working_tree(&self, base_tree: &Tree) -> Tree {
+        let mut tree = self.index_tree(base_tree);
+        tree::apply_changes(&mut tree, &self.unstated);
+        tree
+    }
+}
+
+impl fmt::Display for StashEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}|{}\n{}{}{}{}{}",
+            self.base
+                .map_or(Self::NO_COMMIT.to_owned(), |id| id.to_hex()),
+            self.timestamp,
+            self.message,
+            Commit::MESSAGE_DELIMITER,
+            FileChanges::list_to_string(&self.stated),
+            Self::INDEX_DELIMITER,
+            FileChanges::list_to_string(&self.unstated)
+        )
+    }
+}
+
+impl FromStr for StashEntry {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        let malformed = || Error::StashError(StashErrorReason::StashMalformed(s.to_owned()));
+        let (header, rest) = s.split_once('\n').ok_or_else(malformed)?;
+        let (base, timestamp) = header.split_once('|').ok_or_else(malformed)?;
+        let (message, changes) = rest
+            .split_once(Commit::MESSAGE_DELIMITER)
+            .ok_or_else(malformed)?;
+        let (stated, unstated) = changes
+            .split_once(Self::INDEX_DELIMITER)
+            .ok_or_else(malformed)?;
+
+        Ok(StashEntry {
+            base: match base {
+                Self::NO_COMMIT => None,
+                base => Some(CommitId::from_hex(base)?),
+            },
+            timestamp: timestamp.parse().map_err(|_| malformed())?,
+            message: message.to_owned(),
+            stated: FileChanges::list_from_str(stated)?,
+            unstated: FileChanges::list_from_str(unstated)?,
+        })
+    }
+}
+
+/// Stack of stashed work, the newest entry first
+pub struct Stash;
+
+impl Stash {
+    pub const FILE_NAME: &'static str = "stash";
+    pub const DELIMITER: &'static str = "\nEND_FURSION_STASH\n";
+
+    pub fn read(fursion_dir_path: &Path) -> Result<Vec<StashEntry>, Error> {
+        let path = fursion_dir_path.join(Self::FILE_NAME);
+        if !Path::exists(&path) {
+            return Ok(Vec::new());
+        }
+        let data = fs::read(path)?;
+        let s = std::str::from_utf8(&data)?;
+        if s.is_empty() {
+            return Ok(Vec::new());
+        }
+        s.split(Self::DELIMITER).map(StashEntry::from_str).collect()
+    }
+
+    pub fn save(fursion_dir_path: &Path, entries: &[StashEntry]) -> Result<(), Error> {
+        let data = entries
+            .iter()
+            .map(StashEntry::to_string)
+            .collect::<Vec<_>>()
+            .join(Self::DELIMITER);
+        fs::write(fursion_dir_path.join(Self::FILE_NAME), data)?;
+        Ok(())
+    }
+}
+
+impl Repo {
+    /// Sets the stated changes and the changes of the working directory aside,
+    /// leaving the working directory as it is at HEAD
+    pub fn stash_save(&mut self, message: Option<&str>) -> Result<(), Error> {
+        let head_tree = self.head_tree()?;
+        let index = self.index_tree()?;
+        let working = self.working_tree()?;
+        let unstated = tree::diff_trees(&index, &working);
+        if self.stated_changes.is_empty() && unstated.is_empty() {
+            return Err(Error::StashError(StashErrorReason::NothingToStash));
+        }
+
+        let place = match &self.refs.head {
+            Head::Branch(name) => name.clone(),
+            Head::Detached(_) => "(no branch)".to_owned(),
+        };
+        let message = match message {
+            Some(message) => format!("On {}: {}", place, message),
+            None => match self.head() {
+                Some(id) => format!(
+                    "WIP on {}: {} {}",
+                    place,
+                    id.to_hex(),
+                    self.history.find(&id)?.summary()
+                ),
+                None => format!("WIP on {}", place),
+            },
+        };
+        let entry = StashEntry {
+            base: self.head(),
+            timestamp: commit::now(),
+            message,
+            stated: self.stated_changes.clone(),
+            unstated,
+        };
+
+        let fursion_dir = self.path.join(FURSION_DIR);
+        let mut entries = Stash::read(&fursion_dir)?;
+        entries.insert(0, entry);
+        Stash::save(&fursion_dir, &entries)?;
+
+        tree::write_working_tree(&self.path, &working, &head_tree)?;
+        self.reread_files()?;
+        self.stated_changes.clear();
+        self.save_stated_changes()?;
+        self.record_operation(&format!("stash: {}", entries[0].message))?;
+        Ok(())
+    }
+
+    /// Every stashed entry, newest first
+    pub fn stash_list(&self) -> Result<Vec<StashEntry>, Error> {
+        Stash::read(&self.path.join(FURSION_DIR))
+    }
+
+    /// A stashed entry, 0 being the newest
+    pub fn stash_get(&self, index: usize) -> Result<StashEntry, Error> {
+        self.stash_list()?
+            .into_iter()
+            .nth(index)
+            .ok_or(Error::StashError(StashErrorReason::StashEntryNotFound(
+                index,
+            )))
+    }
+
+    /// Content of the files at the base of a stashed entry
+    pub fn stash_base_tree(&self, entry: &StashEntry) -> Result<Tree, Error> {
+        match entry.base {
+            Some(id) => self.tree_at(&id),
+            None => Ok(Tree::new()),
+        }
+    }
+
+    /// Brings back the changes of a stashed entry on top of the current state with
+    /// a three-way merge, the stated changes are only brought back if they merge cleanly,
+    /// conflicting files are written with conflict markers and fail with [`Error::MergeConflicts`]
+    pub fn stash_apply(&mut self, index: usize) -> Result<(), Error> {
+        let entry = self.stash_get(index)?;
+        let base_tree = self.stash_base_tree(&entry)?;
+        let labels = MergeLabels {
+            ours: "Updated upstream",
+            theirs: "Stashed changes",
+        };
+
+        let working = self.working_tree()?;
+        let merged_working = merge::merge_trees(
+            &base_tree,
+            &working,
+            &entry.working_tree(&base_tree),
+            labels,
+        );
+        let merged_index = merge::merge_trees(
+            &base_tree,
+            &self.index_tree()?,
+            &entry.index_tree(&base_tree),
+            labels,
+        );
+
+        tree::write_working_tree(&self.path, &working, &merged_working.tree)?;
+        self.reread_files()?;
+        if merged_index.is_clean() {
+            self.stated_changes = tree::diff_trees(&self.head_tree()?, &merged_index.tree);
+            self.save_stated_changes()?;
+        }
+        self.record_operation(&format!("stash apply: {}", entry.message))?;
+
+        if !merged_working.is_clean() {
+            return Err(Error::MergeConflicts(merged_working.conflicts));
+        }
+        Ok(())
+    }
+
+    /// Removes a stashed entry, 0 being the newest
+    pub fn stash_drop(&mut self, index: usize) -> Result<StashEntry, Error> {
+        let fursion_dir = self.path.join(FURSION_DIR);
+        let mut entries = Stash::read(&fursion_dir)?;
+        if index >= entries.len() {
+            return Err(Error::StashError(StashErrorReason::StashEntryNotFound(
+                index,
+            )));
+        }
+        let entry = entries.remove(index);
+        Stash::save(&fursion_dir, &entries)?;
+        Ok(entry)
+    }
+
+    /// Applies a stashed entry and drops it, it is kept if there are conflicts
+    pub fn stash_pop(&mut self, index: usize) -> Result<StashEntry, Error> {
+        self.stash_apply(index)?;
+        self.stash_drop(index)
+    }
+}
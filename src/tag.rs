@@ -0,0 +1,212 @@
+use std::{collections::BTreeMap, fmt, fs, path::Path, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    commit::{self, CommitId},
+    error::{Error, RefErrorReason},
+    refs::Refs,
+    repo::Repo,
+};
+
+/// What a tag points to
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub enum Tag {
+    /// A plain name for a commit
+    Lightweight(CommitId),
+    /// A name for a commit along with who made it, when and why
+    Annotated(AnnotatedTag),
+}
+
+/// Tag object carrying a message, usually used for releases
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq)]
+pub struct AnnotatedTag {
+    pub target: CommitId,
+    pub tagger: String,
+    /// Seconds since the unix epoch at which the tag was made
+    pub timestamp: u64,
+    pub message: String,
+}
+
+impl Tag {
+    pub const DIR_NAME: &'static str = "tags";
+
+    /// The commit the tag points to
+    pub fn target(&self) -> CommitId {
+        match self {
+            Tag::Lightweight(id) => *id,
+            Tag::Annotated(tag) => tag.target,
+        }
+    }
+}
+
+impl fmt::Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Tag::Lightweight(id) => f.write_str(&id.to_hex()),
+            Tag::Annotated(tag) => write!(
+                f,
+                "object {}\ntagger {}\ndate {}\n\n{}",
+                tag.target.to_hex(),
+                tag.tagger,
+                tag.timestamp,
+                tag.message
+            ),
+        }
+    }
+}
+
+impl FromStr for Tag {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Error> {
+        if !s.starts_with("object ") {
+            return Ok(Tag::Lightweight(CommitId::from_hex(s.trim())?));
+        }
+
+        let malformed = || Error::RefError(RefErrorReason::TagMalformed(s.to_owned()));
+        let (header, message) = s.split_once("\n\n").unwrap_or((s, ""));
+        let mut target = None;
+        let mut tagger = String::new();
+        let mut timestamp = 0;
+        for line in header.lines() {
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "object" => target = Some(CommitId::from_hex(value)?),
+                "tagger" => tagger = value.to_owned(),
+                "date" => timestamp = value.parse().map_err(|_| malformed())?,
+                _ => return Err(malformed()),
+            }
+        }
+
+        Ok(Tag::Annotated(AnnotatedTag {
+            target: target.ok_or_else(malformed)?,
+            tagger,
+            timestamp,
+            message: message.to_owned(),
+        }))
+    }
+}
+
+/// Reads every tag file under a directory
+pub(crate) fn read_tag_dir(dir: &Path) -> Result<BTreeMap<String, Tag>, Error> {
+    let mut tags = BTreeMap::new();
+    if Path::exists(dir) {
+        read_tag_dir_into(dir, "", &mut tags)?;
+    }
+    Ok(tags)
+}
+
+fn read_tag_dir_into(
+    dir: &Path,
+    prefix: &str,
+    tags: &mut BTreeMap<String, Tag>,
+) -> Result<(), Error> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = format!("{}{}", prefix, entry.file_name().to_string_lossy());
+        if entry.file_type()?.is_dir() {
+            read_tag_dir_into(&entry.path(), &format!("{}/", name), tags)?;
+        } else {
+            let data = fs::read(entry.path())?;
+            tags.insert(name, Tag::from_str(std::str::from_utf8(&data)?)?);
+        }
+    }
+    Ok(())
+}
+
+/// Writes every tag as a file under a directory, removing the ones which no longer exist
+pub(crate) fn write_tag_dir(dir: &Path, tags: &BTreeMap<String, Tag>) -> Result<(), Error> {
+    if Path::exists(dir) {
+        fs::remove_dir_all(dir)?;
+    }
+    fs::create_dir_all(dir)?;
+    for (name, tag) in tags {
+        let path = dir.join(name);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, tag.to_string())?;
+    }
+    Ok(())
+}
+
+impl Repo {
+    /// Tags a commit, HEAD if [`None`], the tag is annotated if a message is given,
+    /// `force` replaces an existing tag of the same name
+    pub fn create_tag(
+        &mut self,
+        name: &str,
+        target: Option<CommitId>,
+        message: Option<&str>,
+        force: bool,
+    ) -> Result<(), Error> {
+        Refs::check_name(name)?;
+        if !force && self.refs.tags.contains_key(name) {
+            return Err(Error::RefError(RefErrorReason::TagAlreadyExists(
+                name.to_owned(),
+            )));
+        }
+        let target = match target {
+            Some(id) => self.history.find(&id)?.id,
+            None => self.head_or_err()?,
+        };
+
+        let tag = match message {
+            Some(message) => Tag::Annotated(AnnotatedTag {
+                target,
+                tagger: self.metadata.author.clone(),
+                timestamp: commit::now(),
+                message: message.to_owned(),
+            }),
+            None => Tag::Lightweight(target),
+        };
+        self.refs.tags.insert(name.to_owned(), tag);
+        self.save_refs()?;
+        self.record_operation(&format!("create tag {}", name))?;
+        Ok(())
+    }
+
+    pub fn delete_tag(&mut self, name: &str) -> Result<Tag, Error> {
+        let tag =
+            self.refs
+                .tags
+                .remove(name)
+                .ok_or(Error::RefError(RefErrorReason::TagNotFound(
+                    name.to_owned(),
+                )))?;
+        self.save_refs()?;
+        self.record_operation(&format!("delete tag {}", name))?;
+        Ok(tag)
+    }
+
+    pub fn tag(&self, name: &str) -> Result<&Tag, Error> {
+        self.refs
+            .tags
+            .get(name)
+            .ok_or(Error::RefError(RefErrorReason::TagNotFound(
+                name.to_owned(),
+            )))
+    }
+
+    /// Every tag, sorted by name
+    pub fn list_tags(&self) -> impl Iterator<Item = (&String, &Tag)> {
+        self.refs.tags.iter()
+    }
+
+    /// The commit a tag points to
+    pub fn resolve_tag(&self, name: &str) -> Result<CommitId, Error> {
+        self.tag(name).map(Tag::target)
+    }
+
+    /// Fetches every remote, which copies the tags unknown locally, the existing tags
+    /// are kept, gives back the names of the new tags
+    pub async fn fetch_tags(&mut self) -> Result<Vec<String>, Error> {
+        Ok(self
+            .fetch()
+            .await?
+            .into_iter()
+            .flat_map(|report| report.tags)
+            .collect())
+    }
+}
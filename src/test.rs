@@ -1,33 +1,2037 @@
-use std::{fs, path::Path};
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::{atomic::Ordering, Arc},
+};
+
+use futures::future::BoxFuture;
 
 use crate::{
+    access::{AccessControl, Permission, ProtectedBranch, Visibility},
+    auth::UserStore,
+    bisect::{BisectMark, BisectStatus},
+    blame::BlameOptions,
+    bundle::Bundle,
     commit::{Commit, CommitId, FileChanges},
-    repo::{self, Repo},
+    config::{PullMode, Upstream},
+    credential::{CredentialFile, Credentials},
+    diff::{self, DiffMode, DiffOptions},
+    error::{
+        AuthErrorReason, BisectErrorReason, BundleErrorReason, Error, FetchErrorReason,
+        PullErrorReason, PushErrorReason, RefErrorReason, RemoteConfigErrorReason, RepoErrorReason,
+    },
+    fetch::FetchRequest,
+    log::{self, LogFormat, LogQuery},
+    merge::{self, MergeLabels},
+    pack::Pack,
+    pull::PullOutcome,
+    push::{BranchUpdate, PushOptions, PushReport, PushRequest, RefUpdateStatus},
+    rebase::{RebaseOptions, RebaseStatus, TodoItem},
+    refs::{Head, Refs},
+    remote::{Refspec, Remote},
+    repo::{self, Repo, RepoHistory},
+    reset::ResetMode,
+    search::Pattern,
     server::Server,
+    tag::Tag,
+    transport::{self, Transport},
 };
 
+/// Creates an empty directory in the system temp dir
+fn temp_dir(name: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("fursion-{}-{:08x}", name, rand::random::<u32>()));
+    fs::create_dir_all(&path).unwrap();
+    path
+}
+
+/// Inits a repo in a temp dir with some author
+fn temp_repo(name: &str) -> Repo {
+    let mut repo = Repo::init(&temp_dir(name)).unwrap();
+    repo.metadata.author = "Tester".to_owned();
+    repo.save_metadata().unwrap();
+    repo
+}
+
 #[test]
+#[ignore = "reads a repo at a path of a Windows machine"]
 fn read() {
     let repo = Repo::read(Path::new("C:\\Users\\adrie\\Desktop\\Some Folder")).unwrap();
     panic!("{:?}", repo.remotes);
 }
 
 #[test]
+#[ignore = "reinitializes a repo at a path of a Windows machine"]
 fn main() {
     let path = Path::new("C:\\Users\\adrie\\Desktop\\Some Folder");
     fs::remove_dir_all(path.join(repo::FURSION_DIR)).unwrap();
-    let repo = Repo::init(&path).unwrap();
+    let repo = Repo::init(path).unwrap();
     println!("{:?}", repo);
 }
 
 #[test]
 /// Tests if commit id to/from hex conversion works
 fn commit_id_hex() {
-    let commit = Commit::new("test", FileChanges::default());
+    let commit = Commit::new("test", "Tester", Vec::new(), Vec::new());
     assert_eq!(commit.id, CommitId::from_hex(&commit.id.to_hex()).unwrap());
+    assert_eq!(CommitId::from_hex("00a0ff").unwrap().to_hex(), "00A0FF");
+    assert!(CommitId::from_hex("ABC").is_err());
+    assert!(CommitId::from_hex("ABCDEF01").is_err());
+    assert!(CommitId::from_hex("ABCDEé").is_err());
 }
 
 #[actix_web::test]
+/// Tests serving the endpoints of a server without binding a port
 async fn host() {
-    Server::new().await.unwrap();
+    use actix_web::{
+        http::StatusCode,
+        test::{self as actix_test, TestRequest},
+        App,
+    };
+
+    let server = Server::new(&temp_dir("host")).unwrap();
+    let app =
+        actix_test::init_service(App::new().configure(|config| server.configure(config))).await;
+    let request = TestRequest::get().uri("/alice/project").to_request();
+    let response = actix_test::call_service(&app, request).await;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+}
+
+#[test]
+/// Tests if a line diff applied on the old text gives back the new text
+fn diff_apply() {
+    let old = "first line\nsecond line\nthird line\n";
+    let new = "first line\nsecond line edited\nthird line\nfourth line\n";
+    let changes = diff::diff_text("file.txt", old, new);
+    assert_eq!(changes.changes().len(), 2);
+    assert_eq!(changes.apply(old), new);
+
+    let parsed = FileChanges::from_str(&changes.to_string()).unwrap();
+    assert_eq!(parsed.path(), "file.txt");
+    assert_eq!(parsed.apply(old), new);
+}
+
+#[test]
+/// Tests the intra-line highlighting of the word and char diff modes
+fn word_diff() {
+    let segments = diff::inline_diff("the quick fox", "the slow fox", DiffMode::Word);
+    assert_eq!(
+        diff::render_segments(&segments, false),
+        "the [-quick-]{+slow+} fox"
+    );
+
+    let options = DiffOptions {
+        mode: DiffMode::Char,
+        context: 0,
+        color: false,
+    };
+    assert_eq!(
+        diff::render("color = 1\n", "colour = 1\n", &options),
+        "@@ -1,1 +1,1 @@\ncolo{+u+}r = 1\n"
+    );
+}
+
+#[test]
+/// Tests if the edit scripts are as short as possible and rebuild the new sequence
+fn diff_minimal() {
+    for _ in 0..200 {
+        let old: Vec<u8> = (0..rand::random::<usize>() % 40)
+            .map(|_| rand::random::<u8>() % 4)
+            .collect();
+        let new: Vec<u8> = (0..rand::random::<usize>() % 40)
+            .map(|_| rand::random::<u8>() % 4)
+            .collect();
+
+        let mut lcs = vec![vec![0; new.len() + 1]; old.len() + 1];
+        for i in (0..old.len()).rev() {
+            for j in (0..new.len()).rev() {
+                lcs[i][j] = if old[i] == new[j] {
+                    lcs[i + 1][j + 1] + 1
+                } else {
+                    lcs[i + 1][j].max(lcs[i][j + 1])
+                };
+            }
+        }
+
+        let (mut rebuilt, mut equal, mut old_pos) = (Vec::new(), 0, 0);
+        for op in diff::diff_slices(&old, &new) {
+            match op {
+                diff::DiffOp::Equal {
+                    old: at,
+                    new: _,
+                    len,
+                } => {
+                    assert_eq!(at, old_pos);
+                    rebuilt.extend_from_slice(&old[at..at + len]);
+                    equal += len;
+                    old_pos += len;
+                }
+                diff::DiffOp::Delete { old: at, len } => {
+                    assert_eq!(at, old_pos);
+                    old_pos += len;
+                }
+                diff::DiffOp::Insert { new: at, len } => {
+                    assert_eq!(at, rebuilt.len());
+                    rebuilt.extend_from_slice(&new[at..at + len]);
+                }
+            }
+        }
+        assert_eq!(old_pos, old.len());
+        assert_eq!(rebuilt, new);
+        assert_eq!(equal, lcs[0][0]);
+    }
+
+    let options = DiffOptions::default();
+    assert_eq!(
+        diff::render("a\nb", "a\nc\n", &options),
+        "@@ -1,2 +1,2 @@\n a\n-b\n\\ No newline at end of file\n+c\n"
+    );
+}
+
+#[test]
+/// Tests rebuilding the trees of a long history, walking it backward then forward
+fn tree_at_long_history() {
+    let mut repo = temp_repo("tree-at");
+    let mut ids = Vec::new();
+    for i in 0..80 {
+        fs::write(repo.path.join("a.txt"), format!("{}\n", i)).unwrap();
+        fs::write(repo.path.join(format!("{}.txt", i % 7)), "x\n").unwrap();
+        ids.push(repo.commit(&format!("commit {}", i)).unwrap());
+    }
+
+    for (i, id) in ids.iter().enumerate().rev().chain(ids.iter().enumerate()) {
+        let tree = repo.tree_at(id).unwrap();
+        assert_eq!(tree["a.txt"], format!("{}\n", i));
+        assert_eq!(tree.len(), 1 + (i + 1).min(7));
+    }
+
+    // The commits are found by id in a history read back from JSON
+    let json = serde_json::to_string(&repo.history).unwrap();
+    let history: RepoHistory = serde_json::from_str(&json).unwrap();
+    assert!(ids.iter().all(|id| history.get(id) == repo.history.get(id)));
+    assert_eq!(history.len(), ids.len());
+}
+
+#[test]
+/// Tests committing, branching and checking out a branch
+fn branch_checkout() {
+    let mut repo = temp_repo("branch");
+    fs::write(repo.path.join("a.txt"), "one\n").unwrap();
+    let first = repo.commit("add a").unwrap();
+    fs::create_dir_all(repo.path.join("docs")).unwrap();
+    fs::write(repo.path.join("docs/b.txt"), "two\n").unwrap();
+    let second = repo.commit("add docs").unwrap();
+
+    let mut repo = Repo::read(&repo.path).unwrap();
+    assert_eq!(repo.head(), Some(second));
+    assert_eq!(repo.history.find(&second).unwrap().parents, vec![first]);
+    assert_eq!(repo.tree_at(&first).unwrap().len(), 1);
+    assert_eq!(repo.head_tree().unwrap()["docs/b.txt"], "two\n");
+
+    repo.create_branch("old", Some(first)).unwrap();
+    assert!(repo.create_branch("old", None).is_err());
+    repo.checkout("old").unwrap();
+    assert!(!repo.path.join("docs/b.txt").exists());
+    assert_eq!(repo.head(), Some(first));
+    assert_eq!(repo.refs.current_branch(), Some("old"));
+
+    // Files which aren't text are neither tracked nor overwritten
+    let binary = [0xFF, 0xFE, 0x00, 0x80];
+    fs::write(repo.path.join("image.bin"), binary).unwrap();
+    assert!(matches!(
+        repo.commit("add image"),
+        Err(Error::RepoReadFailed(RepoErrorReason::FileIsNotText(_)))
+    ));
+    assert!(repo.checkout("main").is_err());
+    assert_eq!(fs::read(repo.path.join("image.bin")).unwrap(), binary);
+    assert_eq!(repo.head(), Some(first));
+}
+
+#[test]
+/// Tests walking the history with filters
+fn log_query() {
+    let mut repo = temp_repo("log");
+    fs::write(repo.path.join("a.txt"), "one\n").unwrap();
+    let first = repo.commit("add a").unwrap();
+    fs::create_dir_all(repo.path.join("docs")).unwrap();
+    fs::write(repo.path.join("docs/b.txt"), "two\n").unwrap();
+    let second = repo.commit("add docs").unwrap();
+
+    let mut repo = Repo::read(&repo.path).unwrap();
+
+    let ids = |commits: Vec<&Commit>| commits.iter().map(|c| c.id).collect::<Vec<_>>();
+    assert_eq!(
+        ids(repo.log(&[], &LogQuery::new()).unwrap()),
+        vec![second, first]
+    );
+    assert_eq!(
+        ids(repo.log(&[], &LogQuery::new().path("docs")).unwrap()),
+        vec![second]
+    );
+    assert_eq!(
+        ids(repo.log(&[], &LogQuery::new().grep("add a")).unwrap()),
+        vec![first]
+    );
+    assert_eq!(
+        ids(repo.log(&[], &LogQuery::new().max_count(1)).unwrap()),
+        vec![second]
+    );
+    assert!(repo
+        .log(&[], &LogQuery::new().author("Someone"))
+        .unwrap()
+        .is_empty());
+
+    repo.create_branch("old", Some(first)).unwrap();
+    repo.checkout("old").unwrap();
+    assert!(!repo.path.join("docs/b.txt").exists());
+    assert_eq!(ids(repo.log(&[], &LogQuery::new()).unwrap()), vec![first]);
+}
+
+#[test]
+/// Tests the ascii graph of a history with a merge
+fn log_graph() {
+    let mut repo = temp_repo("graph");
+    let mut commit = |message: &str, parents: Vec<CommitId>, timestamp: u64| {
+        let mut commit = Commit::new(message, "Tester", parents, Vec::new());
+        commit.timestamp = timestamp;
+        let id = commit.id;
+        repo.history.push(commit);
+        id
+    };
+    let a = commit("a", vec![], 1);
+    let b = commit("b", vec![a], 2);
+    let c = commit("c", vec![a], 3);
+    let d = commit("d", vec![b, c], 4);
+    repo.refs.branches.insert("main".to_owned(), d);
+
+    let commits = repo.log(&[], &LogQuery::new()).unwrap();
+    let graph = log::render(&repo, &commits, LogFormat::OneLine, true);
+    let expected = format!(
+        "* {} (HEAD -> main) d\n|\\\n| * {} c\n* | {} b\n|/\n* {} a\n",
+        d.to_hex(),
+        c.to_hex(),
+        b.to_hex(),
+        a.to_hex()
+    );
+    assert_eq!(graph, expected);
+}
+
+#[test]
+/// Tests that each line is blamed on the commit which last changed it, through a rename
+fn blame() {
+    let mut repo = temp_repo("blame");
+    fs::write(repo.path.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+    let first = repo.commit("first").unwrap();
+    fs::write(repo.path.join("a.txt"), "one\n2\nthree\nfour\n").unwrap();
+    let second = repo.commit("second").unwrap();
+    fs::rename(repo.path.join("a.txt"), repo.path.join("b.txt")).unwrap();
+    let third = repo.commit("rename").unwrap();
+
+    let origins = |options: &BlameOptions| {
+        let blame = repo.blame("b.txt", None, options).unwrap();
+        blame
+            .lines
+            .iter()
+            .map(|l| l.origin.commit)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(origins(&BlameOptions::default()), vec![third; 4]);
+
+    let options = BlameOptions {
+        follow_renames: true,
+    };
+    assert_eq!(origins(&options), vec![first, second, first, second]);
+
+    let blame = repo.blame("b.txt", None, &options).unwrap();
+    assert_eq!(blame.lines[3].content, "four");
+    assert_eq!(blame.lines[3].origin.path, "a.txt");
+    assert_eq!(blame.lines[3].origin.line_number, 4);
+    assert!(repo.blame("a.txt", None, &options).is_err());
+}
+
+#[test]
+/// Tests creating, resolving, persisting and deleting tags
+fn tags() {
+    let mut repo = temp_repo("tags");
+    fs::write(repo.path.join("a.txt"), "a\n").unwrap();
+    let first = repo.commit("first").unwrap();
+    repo.create_tag("v1", None, None, false).unwrap();
+    fs::write(repo.path.join("a.txt"), "b\n").unwrap();
+    let second = repo.commit("second").unwrap();
+    repo.create_tag("v2", None, Some("Release 2\n\nWith notes"), false)
+        .unwrap();
+    assert!(repo.create_tag("v2", Some(first), None, false).is_err());
+
+    let mut repo = Repo::read(&repo.path).unwrap();
+    assert_eq!(repo.resolve_tag("v1").unwrap(), first);
+    assert_eq!(repo.resolve("v2").unwrap(), second);
+    match repo.tag("v2").unwrap() {
+        Tag::Annotated(tag) => {
+            assert_eq!(tag.tagger, "Tester");
+            assert_eq!(tag.message, "Release 2\n\nWith notes");
+        }
+        Tag::Lightweight(_) => panic!("v2 should be annotated"),
+    }
+    assert_eq!(repo.decorations(&first), "tag: v1");
+
+    repo.delete_tag("v1").unwrap();
+    assert_eq!(repo.list_tags().count(), 1);
+    assert!(repo.resolve("v1").is_err());
+}
+
+#[test]
+/// Tests resolving revision expressions and ranges
+fn revisions() {
+    let mut repo = temp_repo("revisions");
+    let mut commit = |id: &str, parents: Vec<CommitId>, timestamp: u64| {
+        let mut commit = Commit::new(id, "Tester", parents, Vec::new());
+        commit.id = CommitId::from_hex(id).unwrap();
+        commit.timestamp = timestamp;
+        repo.history.push(commit);
+        CommitId::from_hex(id).unwrap()
+    };
+    let a = commit("A00001", vec![], 1);
+    let b = commit("B00001", vec![a], 2);
+    let c = commit("B00002", vec![a], 3);
+    let d = commit("D00001", vec![b, c], 4);
+    repo.refs.branches.insert("main".to_owned(), d);
+    repo.refs.branches.insert("side".to_owned(), c);
+
+    assert_eq!(repo.resolve("HEAD").unwrap(), d);
+    assert_eq!(repo.resolve("@").unwrap(), d);
+    assert_eq!(repo.resolve("main~1").unwrap(), b);
+    assert_eq!(repo.resolve("HEAD^2").unwrap(), c);
+    assert_eq!(repo.resolve("HEAD^2~").unwrap(), a);
+    assert_eq!(repo.resolve("main~2").unwrap(), a);
+    assert_eq!(repo.resolve("HEAD^0").unwrap(), d);
+    assert_eq!(repo.resolve("d000").unwrap(), d);
+    assert_eq!(repo.resolve("HEAD@{0}").unwrap(), d);
+
+    let error = |rev: &str| format!("{:?}", repo.resolve(rev).unwrap_err());
+    assert!(error("B000").starts_with("RefError(AmbiguousRevision"));
+    assert!(error("nope").starts_with("RefError(UnknownRevision"));
+    assert!(error("A00").starts_with("RefError(UnknownRevision"));
+    assert!(error("HEAD~3").starts_with("RefError(ParentNotFound"));
+    assert!(error("HEAD^3").starts_with("RefError(ParentNotFound"));
+    assert!(error("HEAD~x").starts_with("RefError(InvalidRevision"));
+    assert!(error("HEAD@{x}").starts_with("RefError(InvalidRevision"));
+
+    let ids = |revs: &[&str]| {
+        let commits = repo.log(revs, &LogQuery::new()).unwrap();
+        commits.iter().map(|c| c.id).collect::<Vec<_>>()
+    };
+    assert_eq!(ids(&["side..main"]), vec![d, b]);
+    assert_eq!(ids(&["main~1...side"]), vec![c, b]);
+    assert_eq!(ids(&["main", "^side"]), vec![d, b]);
+}
+
+#[test]
+/// Tests that commits, branches and checkouts are logged and resolvable through `@{n}`
+fn reflog() {
+    let mut repo = temp_repo("reflog");
+    fs::write(repo.path.join("a.txt"), "a\n").unwrap();
+    let first = repo.commit("first").unwrap();
+    repo.create_branch("side", None).unwrap();
+    fs::write(repo.path.join("a.txt"), "b\n").unwrap();
+    let second = repo.commit("second").unwrap();
+    repo.checkout("side").unwrap();
+
+    let repo = Repo::read(&repo.path).unwrap();
+    let messages = |name: &str| {
+        let entries = repo.reflog(name).unwrap();
+        entries.into_iter().map(|e| e.message).collect::<Vec<_>>()
+    };
+    assert_eq!(
+        messages("HEAD"),
+        vec![
+            "checkout: moving from main to side",
+            "commit: second",
+            "commit (initial): first"
+        ]
+    );
+    assert_eq!(
+        messages("main"),
+        vec!["commit: second", "commit (initial): first"]
+    );
+    assert_eq!(messages("side"), vec!["branch: Created"]);
+    assert_eq!(repo.reflog("HEAD").unwrap()[0].who, "Tester");
+
+    assert_eq!(repo.resolve("HEAD@{0}").unwrap(), first);
+    assert_eq!(repo.resolve("HEAD@{1}").unwrap(), second);
+    assert_eq!(repo.resolve("main@{1}").unwrap(), first);
+    assert_eq!(repo.resolve("@{2}").unwrap(), first);
+    let error = format!("{:?}", repo.resolve("main@{2}").unwrap_err());
+    assert!(error.starts_with("RefError(ReflogEntryNotFound"));
+}
+
+#[test]
+/// Tests undoing, redoing and restoring operations
+fn operations() {
+    let mut repo = temp_repo("operations");
+    fs::write(repo.path.join("a.txt"), "a\n").unwrap();
+    let first = repo.commit("first").unwrap();
+    fs::write(repo.path.join("a.txt"), "b\n").unwrap();
+    repo.stage(&[]).unwrap();
+    let second = repo.commit("second").unwrap();
+    repo.create_branch("old", Some(first)).unwrap();
+    repo.checkout("old").unwrap();
+    assert_eq!(fs::read_to_string(repo.path.join("a.txt")).unwrap(), "a\n");
+
+    repo.undo().unwrap();
+    assert_eq!(repo.refs.current_branch(), Some("main"));
+    assert_eq!(fs::read_to_string(repo.path.join("a.txt")).unwrap(), "b\n");
+    repo.undo().unwrap();
+    assert!(!repo.refs.branches.contains_key("old"));
+    repo.undo().unwrap();
+    assert_eq!(repo.head(), Some(first));
+    assert_eq!(repo.stated_changes.len(), 1);
+
+    repo.redo().unwrap();
+    assert_eq!(repo.head(), Some(second));
+    assert!(repo.stated_changes.is_empty());
+    repo.redo().unwrap();
+    assert!(repo.refs.branches.contains_key("old"));
+    repo.redo().unwrap();
+    assert_eq!(repo.refs.current_branch(), Some("old"));
+    assert!(repo.redo().is_err());
+
+    let repo_path = repo.path.clone();
+    let operations = repo.operations().unwrap();
+    assert_eq!(
+        operations.last().unwrap().description,
+        "initialize repository"
+    );
+    let initial = operations.last().unwrap().id;
+    repo.restore_operation(initial).unwrap();
+    assert_eq!(repo.head(), None);
+    assert!(!repo_path.join("a.txt").exists());
+
+    let mut repo = Repo::read(&repo_path).unwrap();
+    repo.undo().unwrap();
+    assert_eq!(repo.head(), Some(first));
+    assert_eq!(fs::read_to_string(repo_path.join("a.txt")).unwrap(), "a\n");
+    assert!(repo.reflog("HEAD").unwrap()[0]
+        .message
+        .starts_with("restore: operation"));
+}
+
+#[test]
+/// Tests the three-way merge of texts, with and without conflicts
+fn merge_text() {
+    let labels = MergeLabels {
+        ours: "ours",
+        theirs: "theirs",
+    };
+    let base = "one\ntwo\nthree\nfour\n";
+    let (merged, conflicted) = merge::merge_text(
+        base,
+        "1\ntwo\nthree\nfour\n",
+        "one\ntwo\nthree\n4\n",
+        labels,
+    );
+    assert!(!conflicted);
+    assert_eq!(merged, "1\ntwo\nthree\n4\n");
+
+    let (merged, conflicted) = merge::merge_text(
+        base,
+        "one\n2\nthree\nfour\n",
+        "one\nTWO\nthree\nfour\n",
+        labels,
+    );
+    assert!(conflicted);
+    assert_eq!(
+        merged,
+        "one\n<<<<<<< ours\n2\n=======\nTWO\n>>>>>>> theirs\nthree\nfour\n"
+    );
+}
+
+#[test]
+/// Tests stashing work and bringing it back after HEAD moved on
+fn stash() {
+    let mut repo = temp_repo("stash");
+    fs::write(repo.path.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+    repo.commit("first").unwrap();
+
+    fs::write(repo.path.join("a.txt"), "one\ntwo\nthree\nfour\n").unwrap();
+    fs::write(repo.path.join("b.txt"), "new\n").unwrap();
+    repo.stage(&["b.txt"]).unwrap();
+    repo.stash_save(Some("work")).unwrap();
+    assert!(!repo.has_uncommitted_changes().unwrap());
+    assert!(!repo.path.join("b.txt").exists());
+    assert_eq!(repo.stash_list().unwrap()[0].message, "On main: work");
+
+    fs::write(repo.path.join("a.txt"), "zero\none\ntwo\nthree\n").unwrap();
+    repo.commit("second").unwrap();
+    repo.stash_pop(0).unwrap();
+    assert_eq!(
+        fs::read_to_string(repo.path.join("a.txt")).unwrap(),
+        "zero\none\ntwo\nthree\nfour\n"
+    );
+    assert_eq!(repo.stated_changes.len(), 1);
+    assert_eq!(repo.stated_changes[0].path(), "b.txt");
+    assert!(repo.stash_list().unwrap().is_empty());
+
+    repo.stash_save(None).unwrap();
+    fs::write(repo.path.join("a.txt"), "zero\none\ntwo\nTHREE\n").unwrap();
+    repo.commit("third").unwrap();
+    let error = repo.stash_pop(0).unwrap_err();
+    assert!(matches!(error, Error::MergeConflicts(paths) if paths == ["a.txt"]));
+    assert!(fs::read_to_string(repo.path.join("a.txt"))
+        .unwrap()
+        .contains("<<<<<<< Updated upstream\nTHREE\n"));
+    assert_eq!(repo.stash_list().unwrap().len(), 1);
+}
+
+#[test]
+/// Tests inverting file changes, cherry-picking and reverting commits
+fn cherry_pick_revert() {
+    let old = "one\ntwo\nthree\n";
+    let changes = diff::diff_text("a.txt", old, "one\n2\nthree\nfour\n");
+    let inverse = changes.invert(Some(old));
+    assert_eq!(inverse.apply(&changes.apply(old)), old);
+
+    let mut repo = temp_repo("cherry-pick");
+    fs::write(repo.path.join("a.txt"), old).unwrap();
+    let first = repo.commit("first").unwrap();
+    repo.create_branch("side", None).unwrap();
+    fs::write(repo.path.join("a.txt"), "zero\none\ntwo\nthree\n").unwrap();
+    let zero = repo.commit("add zero").unwrap();
+    fs::write(repo.path.join("a.txt"), "zero\none\ntwo\nthree\nfour\n").unwrap();
+    repo.commit("add four").unwrap();
+
+    repo.checkout("side").unwrap();
+    let picked = repo.cherry_pick(zero).unwrap();
+    assert_eq!(repo.history.find(&picked).unwrap().message, "add zero");
+    assert_eq!(
+        repo.head_tree().unwrap()["a.txt"],
+        "zero\none\ntwo\nthree\n"
+    );
+
+    repo.checkout("main").unwrap();
+    repo.revert(zero).unwrap();
+    assert_eq!(
+        fs::read_to_string(repo.path.join("a.txt")).unwrap(),
+        "one\ntwo\nthree\nfour\n"
+    );
+    assert!(repo
+        .history
+        .find(&repo.head().unwrap())
+        .unwrap()
+        .message
+        .starts_with("Revert \"add zero\""));
+
+    fs::write(repo.path.join("a.txt"), "ONE\ntwo\nthree\nfour\n").unwrap();
+    repo.commit("upper").unwrap();
+    repo.checkout("side").unwrap();
+    fs::write(repo.path.join("a.txt"), "zero\nuno\ntwo\nthree\n").unwrap();
+    let spanish = repo.commit("spanish").unwrap();
+    repo.checkout("main").unwrap();
+    let head = repo.head();
+    let error = repo.cherry_pick(spanish).unwrap_err();
+    assert!(matches!(error, Error::MergeConflicts(paths) if paths == ["a.txt"]));
+    assert_eq!(
+        repo.operations().unwrap()[0].description,
+        format!("cherry-pick (conflicts): {}", spanish.to_hex())
+    );
+    repo.abort_pick().unwrap();
+    assert_eq!(
+        repo.operations().unwrap()[0].description,
+        "cherry-pick (abort)"
+    );
+    assert_eq!(repo.head(), head);
+    assert!(!repo.has_uncommitted_changes().unwrap());
+
+    let error = repo.cherry_pick(spanish).unwrap_err();
+    assert!(matches!(error, Error::MergeConflicts(paths) if paths == ["a.txt"]));
+    assert!(repo.continue_pick().is_err());
+    fs::write(repo.path.join("a.txt"), "UNO\ntwo\nthree\nfour\n").unwrap();
+    let resolved = repo.continue_pick().unwrap();
+    assert_eq!(repo.history.find(&resolved).unwrap().message, "spanish");
+    assert!(repo.pending_pick().unwrap().is_none());
+    assert_eq!(repo.tree_at(&first).unwrap()["a.txt"], old);
+}
+
+#[test]
+/// Tests rebasing a branch, with a todo plan and with conflicts
+fn rebase() {
+    let mut repo = temp_repo("rebase");
+    fs::write(repo.path.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+    let base = repo.commit("base").unwrap();
+    fs::write(repo.path.join("a.txt"), "zero\none\ntwo\nthree\n").unwrap();
+    let upstream = repo.commit("add zero").unwrap();
+
+    repo.create_branch("topic", Some(base)).unwrap();
+    repo.checkout("topic").unwrap();
+    fs::write(repo.path.join("a.txt"), "one\ntwo\nthree\nfour\n").unwrap();
+    let four = repo.commit("add four").unwrap();
+    fs::write(repo.path.join("b.txt"), "b\n").unwrap();
+    let b = repo.commit("add b").unwrap();
+    fs::write(repo.path.join("c.txt"), "c\n").unwrap();
+    let c = repo.commit("add c\n\nWith a body.").unwrap();
+
+    let todo = TodoItem::list_from_str(&format!(
+        "pick {}\n# comment\nfixup {}\nreword {} Add c file\n",
+        four.to_hex(),
+        b.to_hex(),
+        c.to_hex()
+    ))
+    .unwrap();
+    let options = RebaseOptions {
+        onto: None,
+        todo: Some(todo),
+    };
+    let status = repo.rebase(upstream, &options).unwrap();
+    assert_eq!(status, RebaseStatus::Finished(repo.head()));
+    assert_eq!(repo.refs.current_branch(), Some("topic"));
+    let messages = repo
+        .log(&["main..topic"], &LogQuery::new())
+        .unwrap()
+        .iter()
+        .map(|c| c.message.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(messages, vec!["Add c file\n\nWith a body.", "add four"]);
+    assert_eq!(
+        fs::read_to_string(repo.path.join("a.txt")).unwrap(),
+        "zero\none\ntwo\nthree\nfour\n"
+    );
+    assert!(repo.path.join("b.txt").exists());
+
+    fs::write(repo.path.join("a.txt"), "ZERO\none\ntwo\nthree\nfour\n").unwrap();
+    repo.commit("upper zero").unwrap();
+    repo.checkout("main").unwrap();
+    fs::write(repo.path.join("a.txt"), "0\none\ntwo\nthree\n").unwrap();
+    let main = repo.commit("digit zero").unwrap();
+    repo.checkout("topic").unwrap();
+    let topic = repo.head().unwrap();
+
+    let error = repo.rebase(main, &RebaseOptions::default()).unwrap_err();
+    assert!(matches!(error, Error::MergeConflicts(_)));
+    let repo_path = repo.path.clone();
+    let mut repo = Repo::read(&repo_path).unwrap();
+    assert!(repo.rebase_state().unwrap().is_some());
+    repo.rebase_abort().unwrap();
+    assert_eq!(repo.head(), Some(topic));
+    assert_eq!(repo.refs.current_branch(), Some("topic"));
+
+    assert!(repo.rebase(main, &RebaseOptions::default()).is_err());
+    fs::write(repo.path.join("a.txt"), "0\none\ntwo\nthree\nfour\n").unwrap();
+    repo.rebase_continue().unwrap();
+    assert_eq!(repo.refs.current_branch(), Some("topic"));
+    assert!(repo.history.is_ancestor(&main, &repo.head().unwrap()));
+    assert!(repo.rebase_state().unwrap().is_none());
+}
+
+#[test]
+/// Tests the soft, mixed and hard resets and amending the last commit
+fn reset_amend() {
+    let mut repo = temp_repo("reset");
+    fs::write(repo.path.join("a.txt"), "one\n").unwrap();
+    let first = repo.commit("first").unwrap();
+    fs::write(repo.path.join("a.txt"), "two\n").unwrap();
+    repo.commit("second").unwrap();
+
+    fs::write(repo.path.join("b.txt"), "b\n").unwrap();
+    let amended = repo.commit_amend(Some("second, with b")).unwrap();
+    let commit = repo.history.find(&amended).unwrap();
+    assert_eq!(commit.parents, vec![first]);
+    assert_eq!(commit.message, "second, with b");
+    assert_eq!(repo.tree_at(&amended).unwrap().len(), 2);
+
+    repo.reset(first, ResetMode::Soft).unwrap();
+    assert_eq!(repo.head(), Some(first));
+    assert_eq!(repo.stated_changes.len(), 2);
+    repo.reset(amended, ResetMode::Mixed).unwrap();
+    assert!(repo.stated_changes.is_empty());
+    repo.reset(first, ResetMode::Mixed).unwrap();
+    assert!(repo.stated_changes.is_empty());
+    assert_eq!(
+        fs::read_to_string(repo.path.join("a.txt")).unwrap(),
+        "two\n"
+    );
+
+    fs::write(repo.path.join("untracked.txt"), "u\n").unwrap();
+    repo.stage(&["a.txt"]).unwrap();
+    repo.reset(first, ResetMode::Hard).unwrap();
+    assert_eq!(
+        fs::read_to_string(repo.path.join("a.txt")).unwrap(),
+        "one\n"
+    );
+    assert!(repo.path.join("untracked.txt").exists());
+    assert!(repo.path.join("b.txt").exists());
+    assert_eq!(repo.resolve("main@{2}").unwrap(), amended);
+}
+
+#[test]
+/// Tests finding the first bad commit by answering and by running a command
+fn bisect() {
+    let mut repo = temp_repo("bisect");
+    let mut ids = Vec::new();
+    for i in 0..8 {
+        let content = if i >= 5 { "bug\n" } else { "ok\n" };
+        fs::write(repo.path.join("a.txt"), format!("{}{}\n", content, i)).unwrap();
+        ids.push(repo.commit(&format!("commit {}", i)).unwrap());
+    }
+
+    let mut status = repo.bisect_start(Some(ids[7]), &[ids[0]]).unwrap();
+    let repo_path = repo.path.clone();
+    let mut steps = 0;
+    while let BisectStatus::Testing { commit, .. } = status {
+        steps += 1;
+        let mut repo = Repo::read(&repo_path).unwrap();
+        assert_eq!(repo.head(), Some(commit));
+        let content = fs::read_to_string(repo_path.join("a.txt")).unwrap();
+        let mark = if content.starts_with("bug") {
+            BisectMark::Bad
+        } else {
+            BisectMark::Good
+        };
+        status = repo.bisect_mark(mark, None).unwrap();
+    }
+    assert_eq!(status, BisectStatus::Found(ids[5]));
+    assert!(steps <= 3);
+
+    let mut repo = Repo::read(&repo_path).unwrap();
+    repo.bisect_reset().unwrap();
+    assert_eq!(repo.head(), Some(ids[7]));
+    assert_eq!(repo.refs.current_branch(), Some("main"));
+
+    // A bad commit which a good one descends from can't be bisected
+    for good in [ids[5], ids[2]] {
+        assert!(matches!(
+            repo.bisect_start(Some(ids[2]), &[good]),
+            Err(Error::BisectError(BisectErrorReason::BadIsAncestorOfGood(
+                _
+            )))
+        ));
+    }
+    assert!(repo.bisect_state().unwrap().is_none());
+    repo.bisect_start(None, &[ids[4]]).unwrap();
+    assert!(matches!(
+        repo.bisect_mark(BisectMark::Bad, Some(ids[1])),
+        Err(Error::BisectError(BisectErrorReason::BadIsAncestorOfGood(
+            _
+        )))
+    ));
+    assert_eq!(repo.bisect_state().unwrap().unwrap().bad, None);
+    repo.bisect_reset().unwrap();
+
+    if cfg!(unix) {
+        repo.bisect_start(Some(ids[7]), &[ids[0]]).unwrap();
+        let args = ["-c", "grep -q ok a.txt"].map(String::from);
+        let status = repo.bisect_run("sh", &args).unwrap();
+        assert_eq!(status, BisectStatus::Found(ids[5]));
+        repo.bisect_reset().unwrap();
+    }
+}
+
+#[test]
+/// Tests searching files at a revision and the commits adding or removing a string
+fn grep_pickaxe() {
+    let mut repo = temp_repo("grep-pickaxe");
+    fs::write(repo.path.join("config.toml"), "name = \"demo\"\n").unwrap();
+    fs::write(repo.path.join("main.rs"), "fn main() {}\n").unwrap();
+    let first = repo.commit("first").unwrap();
+    fs::write(
+        repo.path.join("config.toml"),
+        "name = \"demo\"\ntimeout = 30\n",
+    )
+    .unwrap();
+    let second = repo.commit("add timeout").unwrap();
+    fs::write(
+        repo.path.join("config.toml"),
+        "name = \"demo\"\ntimeout = 60\n",
+    )
+    .unwrap();
+    let third = repo.commit("raise timeout").unwrap();
+    fs::write(repo.path.join("config.toml"), "name = \"demo\"\n").unwrap();
+    let fourth = repo.commit("drop timeout").unwrap();
+
+    let pattern = Pattern::text("timeout");
+    assert!(repo.grep(Some(first), &pattern, &[]).unwrap().is_empty());
+    let found = repo.grep(Some(third), &pattern, &[]).unwrap();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].path, "config.toml");
+    assert_eq!(found[0].line_number, 2);
+    assert_eq!(found[0].line, "timeout = 60");
+    let pattern = Pattern::regex(r"name|main").unwrap();
+    assert_eq!(repo.grep(None, &pattern, &[]).unwrap().len(), 2);
+    let paths = ["main.rs".to_owned()];
+    assert_eq!(repo.grep(None, &pattern, &paths).unwrap().len(), 1);
+    assert!(Pattern::regex("(").is_err());
+
+    let ids = |pattern: &Pattern| {
+        repo.pickaxe(&[], &LogQuery::new(), pattern)
+            .unwrap()
+            .iter()
+            .map(|found| found.commit.id)
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(ids(&Pattern::text("timeout")), vec![fourth, second]);
+    // Replacing one occurrence by another one doesn't count
+    assert_eq!(
+        ids(&Pattern::regex(r"= \d+").unwrap()),
+        vec![fourth, second]
+    );
+    assert_eq!(ids(&Pattern::text_ignore_case("FN MAIN")), vec![first]);
+    let limited = repo
+        .pickaxe(
+            &[],
+            &LogQuery::new().max_count(1),
+            &Pattern::text("timeout"),
+        )
+        .unwrap();
+    assert_eq!(limited.len(), 1);
+    assert_eq!(limited[0].paths, vec!["config.toml".to_owned()]);
+    // The parent of a commit left out by the query is rebuilt on its own
+    let filtered = repo
+        .pickaxe(
+            &[],
+            &LogQuery::new().grep("drop"),
+            &Pattern::text("timeout"),
+        )
+        .unwrap();
+    assert_eq!(filtered.len(), 1);
+    assert_eq!(filtered[0].commit.id, fourth);
+}
+
+#[test]
+/// Tests that a clone holds the commits and refs of the source, at its own path
+fn clone() {
+    let mut source = temp_repo("clone-source");
+    fs::create_dir_all(source.path.join("src")).unwrap();
+    fs::write(source.path.join("src/lib.rs"), "pub mod a;\n").unwrap();
+    let first = source.commit("first").unwrap();
+    source.create_branch("feature", None).unwrap();
+    fs::write(source.path.join("README"), "hello\n").unwrap();
+    let second = source.commit("second").unwrap();
+
+    let remote = Remote::new("origin", "http://127.0.0.1:54510/Tester/clone-source");
+    let dest = temp_dir("clone-dest").join("copy");
+    let repo = Repo::clone_fetched(&source, &remote, &dest).unwrap();
+    assert_eq!(repo.path, dest);
+    assert_eq!(repo.metadata.name, "copy");
+    assert_eq!(repo.head(), Some(second));
+    assert_eq!(repo.refs.branches["feature"], first);
+    assert_eq!(
+        fs::read_to_string(dest.join("src/lib.rs")).unwrap(),
+        "pub mod a;\n"
+    );
+    assert!(!repo.has_uncommitted_changes().unwrap());
+
+    let repo = Repo::read(&dest).unwrap();
+    assert_eq!(repo.remotes.len(), 1);
+    assert_eq!(repo.remotes[0], remote);
+    assert_eq!(repo.refs.current_branch(), Some("main"));
+    assert_eq!(repo.reflog("main").unwrap()[0].new, Some(second));
+    assert!(repo.files.iter().all(|file| file.path.starts_with(&dest)));
+
+    let result = Repo::clone_fetched(&source, &remote, &dest);
+    assert!(matches!(result, Err(Error::RepoInitFailed(_))));
+
+    // Refs named to write outside the refs dir fail the clone before anything is made
+    let dest = temp_dir("clone-dest").join("invalid");
+    source.refs.branches.insert("../evil".to_owned(), first);
+    let result = Repo::clone_fetched(&source, &remote, &dest);
+    assert!(matches!(
+        result,
+        Err(Error::RefError(RefErrorReason::InvalidRefName(_)))
+    ));
+    source.refs.branches.remove("../evil");
+    source.refs.head = Head::Branch("../evil".to_owned());
+    let result = Repo::clone_fetched(&source, &remote, &dest);
+    assert!(matches!(
+        result,
+        Err(Error::RefError(RefErrorReason::InvalidRefName(_)))
+    ));
+    assert!(!dest.exists());
+}
+
+/// Trims a push request to the commits the server lacks, like the push negotiation does
+fn negotiate(server: &Repo, mut request: PushRequest) -> PushRequest {
+    let ids = request.commits.iter().map(|c| c.id).collect::<Vec<_>>();
+    let missing = server.missing_commits(&ids);
+    request
+        .commits
+        .retain(|commit| missing.contains(&commit.id));
+    request
+}
+
+#[test]
+/// Tests pushing commits and tags, and the checks made on the moved branches
+fn push() {
+    let mut server = temp_repo("push-server");
+    fs::write(server.path.join("a.txt"), "a\n").unwrap();
+    let first = server.commit("first").unwrap();
+    let remote = Remote::new("origin", "http://127.0.0.1:54510/Tester/push-server");
+    let mut client = Repo::clone_fetched(&server, &remote, &temp_dir("push-client")).unwrap();
+
+    fs::write(client.path.join("a.txt"), "a\nb\n").unwrap();
+    client.commit("second").unwrap();
+    fs::write(client.path.join("b.txt"), "b\n").unwrap();
+    let third = client.commit("third").unwrap();
+    client.create_tag("v1", None, None, false).unwrap();
+
+    let options = PushOptions {
+        tags: true,
+        branches: vec!["main".to_owned()],
+        ..Default::default()
+    };
+    let request = negotiate(
+        &server,
+        client.push_request(&server.refs, &options).unwrap(),
+    );
+    assert_eq!(request.commits.len(), 2);
+    let report = server.receive_push(request).unwrap();
+    assert_eq!(report.received, 2);
+    assert!(!report.is_rejected());
+    assert_eq!(report.updates[0].old, Some(first));
+    assert_eq!(report.updates[1].name, "tags/v1");
+    assert_eq!(server.head(), Some(third));
+    assert_eq!(server.resolve_tag("v1").unwrap(), third);
+    // The checked out branch of the server brings its files along
+    assert_eq!(
+        fs::read_to_string(server.path.join("b.txt")).unwrap(),
+        "b\n"
+    );
+    assert!(!server.has_uncommitted_changes().unwrap());
+    assert_eq!(server.reflog("main").unwrap()[0].message, "push");
+
+    // Pushing again changes nothing
+    let request = negotiate(
+        &server,
+        client.push_request(&server.refs, &options).unwrap(),
+    );
+    assert!(request.commits.is_empty());
+    let report = server.receive_push(request).unwrap();
+    assert_eq!(report.updates[0].status, RefUpdateStatus::UpToDate);
+
+    // Rewritten history is only pushed by force
+    client.reset(first, ResetMode::Hard).unwrap();
+    fs::write(client.path.join("c.txt"), "c\n").unwrap();
+    let rewritten = client.commit("rewritten").unwrap();
+    let options = PushOptions::default();
+    let stale_refs = server.refs.clone();
+    let request = negotiate(
+        &server,
+        client.push_request(&server.refs, &options).unwrap(),
+    );
+    let report = server.receive_push(request).unwrap();
+    assert_eq!(
+        report.updates[0].status,
+        RefUpdateStatus::RejectedNonFastForward
+    );
+    assert_eq!(server.head(), Some(third));
+    let options = PushOptions {
+        force: true,
+        ..Default::default()
+    };
+    let request = negotiate(
+        &server,
+        client.push_request(&server.refs, &options).unwrap(),
+    );
+    let report = server.receive_push(request).unwrap();
+    assert_eq!(report.updates[0].status, RefUpdateStatus::ForcedUpdate);
+    assert_eq!(server.head(), Some(rewritten));
+    assert!(!server.path.join("b.txt").exists());
+
+    // The branch moved since the refs were listed
+    client.create_branch("feature", None).unwrap();
+    let options = PushOptions {
+        delete: vec!["feature".to_owned()],
+        branches: vec!["main".to_owned()],
+        ..Default::default()
+    };
+    let mut request = client.push_request(&stale_refs, &options).unwrap();
+    request.branches[0].new = Some(first);
+    let report = server.receive_push(request).unwrap();
+    assert_eq!(report.updates[0].status, RefUpdateStatus::RejectedStale);
+
+    let options = PushOptions {
+        branches: vec!["feature".to_owned()],
+        ..Default::default()
+    };
+    let request = negotiate(
+        &server,
+        client.push_request(&server.refs, &options).unwrap(),
+    );
+    server.receive_push(request).unwrap();
+    assert_eq!(server.refs.branches["feature"], rewritten);
+    let options = PushOptions {
+        delete: vec!["feature".to_owned()],
+        ..Default::default()
+    };
+    let request = client.push_request(&server.refs, &options).unwrap();
+    let report = server.receive_push(request).unwrap();
+    assert_eq!(report.updates[0].status, RefUpdateStatus::Updated);
+    assert!(!server.refs.branches.contains_key("feature"));
+
+    // Invalid names are rejected without stopping the other updates
+    let mut request = client
+        .push_request(
+            &server.refs,
+            &PushOptions {
+                branches: vec!["main".to_owned()],
+                ..Default::default()
+            },
+        )
+        .unwrap();
+    let valid = request.branches[0].clone();
+    request.branches = vec![
+        BranchUpdate {
+            name: "bad..name".to_owned(),
+            old: None,
+            new: Some(first),
+        },
+        BranchUpdate {
+            name: "kept".to_owned(),
+            old: None,
+            new: valid.new,
+        },
+    ];
+    request
+        .tags
+        .insert("bad tag".to_owned(), Tag::Lightweight(first));
+    let report = server.receive_push(request).unwrap();
+    assert_eq!(
+        report.updates[0].status,
+        RefUpdateStatus::RejectedInvalidName
+    );
+    assert_eq!(report.updates[1].status, RefUpdateStatus::Updated);
+    assert_eq!(
+        report.updates[2].status,
+        RefUpdateStatus::RejectedInvalidName
+    );
+    let saved = Repo::read(&server.path).unwrap();
+    assert_eq!(saved.refs.branches["kept"], rewritten);
+    assert!(!saved.refs.branches.contains_key("bad..name"));
+
+    // Every commit must be sent or already known
+    fs::write(client.path.join("d.txt"), "d\n").unwrap();
+    client.commit("unsent").unwrap();
+    let mut request = client
+        .push_request(&server.refs, &PushOptions::default())
+        .unwrap();
+    request.commits.clear();
+    assert!(matches!(
+        server.receive_push(request),
+        Err(Error::PushError(_))
+    ));
+
+    let mut forged = server.history.find(&first).unwrap().clone();
+    forged.message = "forged".to_owned();
+    let request = PushRequest {
+        commits: vec![forged],
+        ..Default::default()
+    };
+    assert!(matches!(
+        server.receive_push(request),
+        Err(Error::PushError(PushErrorReason::CommitIdCollision(_)))
+    ));
+}
+
+#[test]
+/// Tests that a fetch only transfers the missing commits and moves remote-tracking branches
+fn fetch() {
+    let mut server = temp_repo("fetch-server");
+    fs::write(server.path.join("a.txt"), "a\n").unwrap();
+    let first = server.commit("first").unwrap();
+    server.create_branch("feature", None).unwrap();
+    fs::write(server.path.join("a.txt"), "a\nb\n").unwrap();
+    let second = server.commit("second").unwrap();
+    let remote = Remote::new("origin", "http://127.0.0.1:54510/Tester/fetch-server");
+    let mut client = Repo::clone_fetched(&server, &remote, &temp_dir("fetch-client")).unwrap();
+    assert_eq!(client.resolve("origin/main").unwrap(), second);
+    assert_eq!(client.resolve("origin/feature").unwrap(), first);
+
+    fs::write(server.path.join("a.txt"), "a\nb\nc\n").unwrap();
+    let third = server.commit("third").unwrap();
+    server.delete_branch("feature").unwrap();
+    server.create_branch("topic", None).unwrap();
+    server
+        .create_tag("v2", None, Some("release"), false)
+        .unwrap();
+
+    let request = FetchRequest::new(&server.refs, client.refs.tips());
+    assert_eq!(request.wants, vec![third]);
+    let pack = server.pack_missing(&request).unwrap();
+    assert_eq!(pack.commits.len(), 1);
+    let pack = Pack::from_str(&pack.to_string()).unwrap();
+    let report = client
+        .store_fetch(&remote, &server.refs, pack.clone())
+        .unwrap();
+    assert_eq!(report.received, 1);
+    // Commits read back from a pack are the known ones
+    let again = client.store_fetch(&remote, &server.refs, pack).unwrap();
+    assert_eq!(again.received, 0);
+    assert_eq!(report.tags, vec!["v2".to_owned()]);
+    let updated = report
+        .updates
+        .iter()
+        .map(|update| (update.name.as_str(), update.new))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        updated,
+        vec![
+            ("origin/feature", None),
+            ("origin/main", Some(third)),
+            ("origin/topic", Some(third))
+        ]
+    );
+    // The local branches don't move
+    assert_eq!(client.head(), Some(second));
+    let mut client = Repo::read(&client.path).unwrap();
+    assert_eq!(client.resolve("origin/main").unwrap(), third);
+    assert_eq!(client.resolve_tag("v2").unwrap(), third);
+    assert!(client.history.contains(&third));
+
+    server.reset(first, ResetMode::Hard).unwrap();
+    let request = FetchRequest::new(&server.refs, client.refs.tips());
+    assert!(request.wants.is_empty());
+    let report = client
+        .store_fetch(&remote, &server.refs, Pack::default())
+        .unwrap();
+    assert_eq!(report.received, 0);
+    assert_eq!(report.updates[0].status, RefUpdateStatus::ForcedUpdate);
+
+    // A different commit with a known id isn't taken for the known one
+    let mut forged = server.history.find(&third).unwrap().clone();
+    forged.message = "forged".to_owned();
+    let pack = Pack {
+        commits: vec![forged],
+    };
+    assert!(matches!(
+        client.store_fetch(&remote, &server.refs, pack),
+        Err(Error::FetchError(FetchErrorReason::CommitIdCollision(_)))
+    ));
+
+    let text = server
+        .pack_missing(&FetchRequest::new(&server.refs, Vec::new()))
+        .unwrap()
+        .to_string();
+    let truncated = &text[..text.len() - 3];
+    assert!(matches!(
+        Pack::from_str(truncated),
+        Err(Error::FetchError(_))
+    ));
+    // A count no pack could hold fails like any other missing entry
+    let (header, entries) = text.split_once('\n').unwrap();
+    let (prefix, _) = header.rsplit_once(' ').unwrap();
+    let oversized = format!("{} {}\n{}", prefix, usize::MAX, entries);
+    assert!(matches!(
+        Pack::from_str(&oversized),
+        Err(Error::FetchError(FetchErrorReason::PackMalformed(_)))
+    ));
+}
+
+#[test]
+/// Tests that a fetch rejects the refs whose names would write files outside their dir
+fn fetch_invalid_names() {
+    let mut server = temp_repo("fetch-names-server");
+    fs::write(server.path.join("a.txt"), "a\n").unwrap();
+    let first = server.commit("first").unwrap();
+    let remote = Remote::new("origin", "http://127.0.0.1:54510/Tester/fetch-names-server");
+    let mut client =
+        Repo::clone_fetched(&server, &remote, &temp_dir("fetch-names-client")).unwrap();
+
+    let mut refs = server.refs.clone();
+    refs.branches.insert("../evil".to_owned(), first);
+    refs.tags
+        .insert("../evil".to_owned(), Tag::Lightweight(first));
+    let report = client.store_fetch(&remote, &refs, Pack::default()).unwrap();
+    let rejected = report
+        .updates
+        .iter()
+        .filter(|update| update.status == RefUpdateStatus::RejectedInvalidName)
+        .map(|update| update.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(rejected, vec!["origin/../evil", "tags/../evil"]);
+    assert!(report.tags.is_empty());
+
+    let refs_path = client.path.join(repo::FURSION_DIR).join(Refs::DIR_NAME);
+    let names = |dir: &Path| {
+        fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect::<Vec<_>>()
+    };
+    let mut dirs = names(&refs_path);
+    dirs.sort();
+    assert_eq!(dirs, vec!["heads", "remotes", "tags"]);
+    assert_eq!(
+        names(&refs_path.join(Refs::REMOTES_DIR_NAME)),
+        vec!["origin"]
+    );
+    assert!(names(&refs_path.join(Tag::DIR_NAME)).is_empty());
+    let client = Repo::read(&client.path).unwrap();
+    assert_eq!(
+        client.refs.remotes.keys().collect::<Vec<_>>(),
+        vec!["origin/main"]
+    );
+}
+
+/// Stores what `server` has that `client` doesn't as a fetch of `origin`
+fn fetch_local(client: &mut Repo, server: &Repo) {
+    let request = FetchRequest::new(&server.refs, client.refs.tips());
+    let pack = server.pack_missing(&request).unwrap();
+    let remote = client.remote("origin").unwrap().clone();
+    client.store_fetch(&remote, &server.refs, pack).unwrap();
+}
+
+#[test]
+fn pull() {
+    let mut server = temp_repo("pull-server");
+    fs::write(server.path.join("a.txt"), "one\ntwo\nthree\n").unwrap();
+    let first = server.commit("first").unwrap();
+    let remote = Remote::new("origin", "http://127.0.0.1:54510/Tester/pull-server");
+    let mut client = Repo::clone_fetched(&server, &remote, &temp_dir("pull-client")).unwrap();
+    client.metadata.author = "Tester".to_owned();
+    let upstream = client.upstream("main").unwrap();
+    assert_eq!(upstream.tracking_name(), "origin/main");
+
+    fs::write(server.path.join("a.txt"), "one\ntwo\nthree\nfour\n").unwrap();
+    let second = server.commit("second").unwrap();
+    fetch_local(&mut client, &server);
+    assert_eq!(
+        client
+            .pull_fetched(&upstream, PullMode::FastForwardOnly)
+            .unwrap(),
+        PullOutcome::FastForward {
+            from: Some(first),
+            to: second
+        }
+    );
+    assert_eq!(
+        fs::read_to_string(client.path.join("a.txt")).unwrap(),
+        "one\ntwo\nthree\nfour\n"
+    );
+    assert_eq!(
+        client.pull_fetched(&upstream, PullMode::Merge).unwrap(),
+        PullOutcome::UpToDate
+    );
+
+    // Diverged histories
+    fs::write(server.path.join("b.txt"), "b\n").unwrap();
+    let third = server.commit("third").unwrap();
+    fs::write(client.path.join("c.txt"), "c\n").unwrap();
+    let local = client.commit("local").unwrap();
+    fetch_local(&mut client, &server);
+    assert!(matches!(
+        client.pull_fetched(&upstream, PullMode::FastForwardOnly),
+        Err(Error::PullError(PullErrorReason::NotFastForward(_)))
+    ));
+    let Ok(PullOutcome::Merged(merge)) = client.pull_fetched(&upstream, PullMode::Merge) else {
+        panic!("expected a merge commit");
+    };
+    assert_eq!(
+        client.history.find(&merge).unwrap().parents,
+        vec![local, third]
+    );
+    assert!(client.path.join("b.txt").exists() && client.path.join("c.txt").exists());
+
+    fs::write(server.path.join("b.txt"), "b\nb\n").unwrap();
+    let fourth = server.commit("fourth").unwrap();
+    fs::write(client.path.join("c.txt"), "c\nc\n").unwrap();
+    client.commit("local again").unwrap();
+    fetch_local(&mut client, &server);
+    let Ok(PullOutcome::Rebased(Some(rebased))) = client.pull_fetched(&upstream, PullMode::Rebase)
+    else {
+        panic!("expected a rebase");
+    };
+    assert!(client.history.is_ancestor(&fourth, &rebased));
+    assert_eq!(client.refs.current_branch(), Some("main"));
+
+    // Conflicts stop the merge until resolved
+    fs::write(server.path.join("a.txt"), "one\n2\nthree\nfour\n").unwrap();
+    let fifth = server.commit("fifth").unwrap();
+    fs::write(client.path.join("a.txt"), "one\nTWO\nthree\nfour\n").unwrap();
+    let ours = client.commit("ours").unwrap();
+    fetch_local(&mut client, &server);
+    assert_eq!(
+        client.pull_fetched(&upstream, PullMode::Merge).unwrap(),
+        PullOutcome::Conflicts(vec!["a.txt".to_owned()])
+    );
+    assert_eq!(
+        client.operations().unwrap()[0].description,
+        format!("merge (conflicts): {}", fifth.to_hex())
+    );
+    assert!(matches!(
+        client.pull_fetched(&upstream, PullMode::Merge),
+        Err(Error::InProgress(_))
+    ));
+    client.merge_abort().unwrap();
+    assert_eq!(client.operations().unwrap()[0].description, "merge (abort)");
+    assert_eq!(client.head(), Some(ours));
+    assert!(!client.has_uncommitted_changes().unwrap());
+
+    client.pull_fetched(&upstream, PullMode::Merge).unwrap();
+    assert!(matches!(
+        client.merge_continue(),
+        Err(Error::MergeConflicts(_))
+    ));
+    fs::write(client.path.join("a.txt"), "one\n2\nthree\nfour\n").unwrap();
+    let merge = client.merge_continue().unwrap();
+    assert_eq!(
+        client.history.find(&merge).unwrap().parents,
+        vec![ours, fifth]
+    );
+    assert!(client.pending_merge().unwrap().is_none());
+
+    client
+        .set_upstream(
+            "main",
+            Upstream {
+                remote: "origin".to_owned(),
+                branch: "missing".to_owned(),
+            },
+        )
+        .unwrap();
+    let upstream = client.upstream("main").unwrap();
+    assert!(matches!(
+        client.pull_fetched(&upstream, PullMode::Merge),
+        Err(Error::PullError(PullErrorReason::UpstreamNotFetched(_)))
+    ));
+}
+
+#[test]
+fn remotes() {
+    let mut repo = temp_repo("remotes");
+    repo.add_remote("origin", "http://127.0.0.1:54510/Tester/a")
+        .unwrap();
+    repo.add_remote("backup", "http://127.0.0.1:54510/Tester/b")
+        .unwrap();
+    assert!(matches!(
+        repo.add_remote("origin", "http://elsewhere"),
+        Err(Error::RemoteConfigError(
+            RemoteConfigErrorReason::AlreadyExists(_)
+        ))
+    ));
+    assert!(matches!(
+        repo.add_remote("a/b", "http://elsewhere"),
+        Err(Error::RemoteConfigError(
+            RemoteConfigErrorReason::InvalidName(_)
+        ))
+    ));
+    repo.set_remote_url("backup", "http://127.0.0.1:54510/Tester/c", true)
+        .unwrap();
+
+    fs::write(repo.path.join("a.txt"), "a\n").unwrap();
+    let first = repo.commit("first").unwrap();
+    let backup = repo.remote("backup").unwrap().clone();
+    repo.store_fetch(&backup, &repo.refs.clone(), Pack::default())
+        .unwrap();
+    repo.set_upstream(
+        "main",
+        Upstream {
+            remote: "backup".to_owned(),
+            branch: "main".to_owned(),
+        },
+    )
+    .unwrap();
+    repo.rename_remote("backup", "mirror").unwrap();
+    repo.save_all().unwrap();
+
+    // Everything survives a save and a read
+    let mut repo = Repo::read(&repo.path).unwrap();
+    let names = repo
+        .remotes
+        .iter()
+        .map(|remote| remote.name.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["origin", "mirror"]);
+    let mirror = repo.remote("mirror").unwrap();
+    assert_eq!(mirror.url, "http://127.0.0.1:54510/Tester/b");
+    assert_eq!(mirror.push_url(), "http://127.0.0.1:54510/Tester/c");
+    assert_eq!(mirror.refspecs()[0].to_string(), "+*:mirror/*");
+    assert_eq!(repo.resolve("mirror/main").unwrap(), first);
+    assert_eq!(repo.upstream("main").unwrap().remote, "mirror");
+
+    repo.remove_remote("mirror").unwrap();
+    assert!(repo.resolve("mirror/main").is_err());
+    assert_eq!(repo.upstream("main").unwrap().remote, "origin");
+    assert!(repo.remote("mirror").is_err());
+
+    // Only the branches of the refspecs are fetched, without force only fast-forwards
+    let spec = Refspec::from_str("main:upstream/trunk").unwrap();
+    assert_eq!(
+        spec.tracking_name("main").as_deref(),
+        Some("upstream/trunk")
+    );
+    assert_eq!(spec.tracking_name("other"), None);
+    assert!(Refspec::from_str("*:origin").is_err());
+    repo.set_remote_refspecs("origin", vec![spec]).unwrap();
+    let origin = repo.remote("origin").unwrap().clone();
+    repo.create_branch("feature", None).unwrap();
+    repo.store_fetch(&origin, &repo.refs.clone(), Pack::default())
+        .unwrap();
+    assert_eq!(repo.resolve("upstream/trunk").unwrap(), first);
+    assert!(repo.resolve("origin/feature").is_err());
+
+    fs::write(repo.path.join("a.txt"), "b\n").unwrap();
+    repo.commit("second").unwrap();
+    repo.reset(first, ResetMode::Hard).unwrap();
+    let mut moved = repo.refs.clone();
+    moved
+        .branches
+        .insert("main".to_owned(), repo.resolve("HEAD@{1}").unwrap());
+    repo.store_fetch(&origin, &moved, Pack::default()).unwrap();
+    let second = repo.resolve("upstream/trunk").unwrap();
+    assert_ne!(second, first);
+    let report = repo
+        .store_fetch(&origin, &repo.refs.clone(), Pack::default())
+        .unwrap();
+    assert_eq!(
+        report.updates[0].status,
+        RefUpdateStatus::RejectedNonFastForward
+    );
+    assert_eq!(repo.resolve("upstream/trunk").unwrap(), second);
+
+    // The older format of a url per line
+    fs::write(
+        repo.path.join(repo::FURSION_DIR).join(Remote::FILE_NAME),
+        "http://one\nhttp://two\n",
+    )
+    .unwrap();
+    let repo = Repo::read(&repo.path).unwrap();
+    assert_eq!(repo.remote("origin2").unwrap().url, "http://two");
+}
+
+#[test]
+fn local_remote() {
+    let mut server = temp_repo("local-server");
+    fs::write(server.path.join("a.txt"), "a\n").unwrap();
+    let first = server.commit("first").unwrap();
+    server.create_branch("feature", None).unwrap();
+
+    let url = server.path.to_string_lossy().into_owned();
+    let remote = Remote::new("origin", &url);
+    let dest = temp_dir("local-client").join("client");
+    let mut client = futures::executor::block_on(Repo::clone_from(&remote, &dest)).unwrap();
+    client.metadata.author = "Tester".to_owned();
+    assert_eq!(client.head(), Some(first));
+    assert_eq!(client.resolve("origin/feature").unwrap(), first);
+    assert_eq!(fs::read_to_string(dest.join("a.txt")).unwrap(), "a\n");
+
+    // Pushing to the checked out branch of the other repo updates its files
+    fs::write(client.path.join("a.txt"), "a\nb\n").unwrap();
+    let second = client.commit("second").unwrap();
+    let report =
+        futures::executor::block_on(client.push(&remote, &PushOptions::default())).unwrap();
+    assert_eq!(report.received, 1);
+    let mut server = Repo::read(&server.path).unwrap();
+    assert_eq!(server.head(), Some(second));
+    assert_eq!(
+        fs::read_to_string(server.path.join("a.txt")).unwrap(),
+        "a\nb\n"
+    );
+
+    // A file:// url works the same
+    fs::write(server.path.join("a.txt"), "a\nb\nc\n").unwrap();
+    let third = server.commit("third").unwrap();
+    client
+        .set_remote_url("origin", &format!("file://{}", url), false)
+        .unwrap();
+    let report = futures::executor::block_on(client.pull()).unwrap();
+    assert_eq!(report.fetch.received, 1);
+    assert_eq!(
+        report.outcome,
+        PullOutcome::FastForward {
+            from: Some(second),
+            to: third
+        }
+    );
+    assert_eq!(
+        fs::read_to_string(client.path.join("a.txt")).unwrap(),
+        "a\nb\nc\n"
+    );
+
+    let missing = Remote::new("origin", &format!("{}-missing", url));
+    assert!(futures::executor::block_on(missing.list_refs()).is_err());
+}
+
+/// Serves a repo held in memory, counting the calls
+struct MemoryTransport {
+    repo: std::sync::Mutex<Repo>,
+    calls: std::sync::atomic::AtomicUsize,
+}
+
+impl Transport for MemoryTransport {
+    fn list_refs<'a>(&'a self, _url: &'a str) -> BoxFuture<'a, Result<Refs, Error>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move { Ok(self.repo.lock().unwrap().refs.clone()) })
+    }
+
+    fn fetch_objects<'a>(
+        &'a self,
+        _url: &'a str,
+        request: &'a FetchRequest,
+    ) -> BoxFuture<'a, Result<Pack, Error>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move { self.repo.lock().unwrap().pack_missing(request) })
+    }
+
+    fn push_objects<'a>(
+        &'a self,
+        _url: &'a str,
+        request: &'a PushRequest,
+    ) -> BoxFuture<'a, Result<PushReport, Error>> {
+        self.calls.fetch_add(1, Ordering::SeqCst);
+        Box::pin(async move { self.repo.lock().unwrap().receive_push(request.clone()) })
+    }
+}
+
+#[test]
+fn transports() {
+    let mut server = temp_repo("transport-server");
+    fs::write(server.path.join("a.txt"), "a\n").unwrap();
+    let first = server.commit("first").unwrap();
+    let transport = Arc::new(MemoryTransport {
+        repo: std::sync::Mutex::new(server),
+        calls: Default::default(),
+    });
+    transport::register_transport("mem", transport.clone());
+
+    let remote = Remote::new("origin", "mem://server");
+    let dest = temp_dir("transport-client").join("client");
+    let mut client = futures::executor::block_on(Repo::clone_from(&remote, &dest)).unwrap();
+    client.metadata.author = "Tester".to_owned();
+    assert_eq!(client.head(), Some(first));
+    assert_eq!(transport.calls.load(Ordering::SeqCst), 2);
+
+    fs::write(client.path.join("a.txt"), "a\nb\n").unwrap();
+    let second = client.commit("second").unwrap();
+    let report =
+        futures::executor::block_on(client.push(&remote, &PushOptions::default())).unwrap();
+    assert_eq!(report.updates[0].status, RefUpdateStatus::Updated);
+    assert_eq!(transport.repo.lock().unwrap().head(), Some(second));
+    assert_eq!(transport.calls.load(Ordering::SeqCst), 4);
+
+    assert!(transport::transport_for("http://host/a/b").is_ok());
+    assert!(transport::transport_for("/some/path").is_ok());
+    assert!(matches!(
+        transport::transport_for("gopher://host/a/b"),
+        Err(Error::RemoteConfigError(
+            RemoteConfigErrorReason::UnsupportedScheme(_)
+        ))
+    ));
+}
+
+#[test]
+fn bundle() {
+    let mut source = temp_repo("bundle-source");
+    fs::write(source.path.join("a.txt"), "a\n").unwrap();
+    let first = source.commit("first").unwrap();
+    source.create_tag("v1", None, None, false).unwrap();
+    fs::write(source.path.join("a.txt"), "a\nb\n").unwrap();
+    let second = source.commit("second").unwrap();
+    assert!(matches!(
+        source.create_bundle(&["HEAD"]),
+        Err(Error::BundleError(BundleErrorReason::Empty))
+    ));
+
+    let dir = temp_dir("bundle-files");
+    let full = source.create_bundle(&["main", "v1"]).unwrap();
+    assert!(full.prerequisites.is_empty());
+    assert_eq!(full.pack.commits.len(), 2);
+    full.write(&dir.join("full.bundle")).unwrap();
+    let read = Bundle::read(&dir.join("full.bundle")).unwrap();
+    assert_eq!(read.refs.branches, full.refs.branches);
+    assert_eq!(read.refs.tags, full.refs.tags);
+    let mut forged = full.clone();
+    forged
+        .refs
+        .tags
+        .insert("../evil".to_owned(), Tag::Lightweight(first));
+    assert!(matches!(
+        forged.to_text().unwrap().parse::<Bundle>(),
+        Err(Error::BundleError(BundleErrorReason::Malformed(_)))
+    ));
+    let mut forged = full.clone();
+    forged.refs.head = Head::Branch("../evil".to_owned());
+    assert!(matches!(
+        forged.to_text().unwrap().parse::<Bundle>(),
+        Err(Error::BundleError(BundleErrorReason::Malformed(_)))
+    ));
+
+    // A bundle is cloned and fetched like a repo but not pushed to
+    let url = dir.join("full.bundle").to_string_lossy().into_owned();
+    let remote = Remote::new("origin", &url);
+    let dest = temp_dir("bundle-clone").join("clone");
+    let mut clone = futures::executor::block_on(Repo::clone_from(&remote, &dest)).unwrap();
+    clone.metadata.author = "Tester".to_owned();
+    assert_eq!(clone.head(), Some(second));
+    assert_eq!(clone.resolve_tag("v1").unwrap(), first);
+    assert!(matches!(
+        futures::executor::block_on(clone.push(&remote, &PushOptions::default())),
+        Err(Error::BundleError(BundleErrorReason::ReadOnly))
+    ));
+
+    fs::write(source.path.join("a.txt"), "a\nb\nc\n").unwrap();
+    let third = source.commit("third").unwrap();
+    let incremental = source.create_bundle(&["v1..main"]).unwrap();
+    assert_eq!(incremental.prerequisites, vec![first]);
+    assert_eq!(incremental.pack.commits.len(), 2);
+    incremental.write(&dir.join("full.bundle")).unwrap();
+    clone.verify_bundle(&incremental).unwrap();
+    let report = futures::executor::block_on(clone.fetch()).unwrap();
+    assert_eq!(report[0].received, 1);
+    assert_eq!(clone.resolve("origin/main").unwrap(), third);
+
+    let unrelated = temp_repo("bundle-unrelated");
+    assert!(matches!(
+        unrelated.verify_bundle(&incremental),
+        Err(Error::BundleError(BundleErrorReason::MissingPrerequisites(
+            _
+        )))
+    ));
+}
+
+#[test]
+fn auth() {
+    let root = temp_dir("auth");
+    let mut store = UserStore::default();
+    store.add_user("alice", Some("secret")).unwrap();
+    store.add_user("bob", None).unwrap();
+    assert!(matches!(
+        store.add_user("bob", None),
+        Err(Error::AuthError(AuthErrorReason::UserAlreadyExists(_)))
+    ));
+    let token = store.create_token("bob", "laptop").unwrap();
+    store.save(&root).unwrap();
+    let mut store = UserStore::read(&root).unwrap();
+    assert!(!fs::read_to_string(root.join(UserStore::FILE_NAME))
+        .unwrap()
+        .contains("secret"));
+
+    let basic = |user: &str, password: &str| Credentials::Basic {
+        user: user.to_owned(),
+        password: password.to_owned(),
+    };
+    assert_eq!(
+        store.authenticate(&basic("alice", "secret")).unwrap(),
+        "alice"
+    );
+    assert!(matches!(
+        store.authenticate(&basic("alice", "wrong")),
+        Err(Error::AuthError(AuthErrorReason::InvalidCredentials))
+    ));
+    assert!(store.authenticate(&basic("bob", "")).is_err());
+    let bearer = Credentials::Token(token.clone());
+    assert_eq!(store.authenticate(&bearer).unwrap(), "bob");
+    store.revoke_token("bob", "laptop").unwrap();
+    assert!(store.authenticate(&bearer).is_err());
+
+    // Credentials go through the Authorization header unchanged
+    for credentials in [bearer, basic("alice", "pass:word")] {
+        let header = credentials.to_header();
+        assert_eq!(Credentials::from_header(&header).unwrap(), credentials);
+    }
+    assert!(Credentials::from_header("Digest abc").is_err());
+    assert_eq!(
+        Credentials::from_helper_output("username=carol\npassword=pw\n"),
+        Some(basic("carol", "pw"))
+    );
+
+    let file = CredentialFile::from_str(
+        "# servers\nhttp://host token t1\nhttp://host/team basic dave pw\n",
+    )
+    .unwrap();
+    assert_eq!(
+        file.find("http://host/team/repo"),
+        Some(&basic("dave", "pw"))
+    );
+    assert_eq!(
+        file.find("http://host/other/repo"),
+        Some(&Credentials::Token("t1".to_owned()))
+    );
+    assert_eq!(file.find("http://elsewhere/repo"), None);
+    assert_eq!(
+        CredentialFile::from_str(&file.to_string()).unwrap().entries,
+        file.entries
+    );
+    assert!(CredentialFile::from_str("http://host token").is_err());
+
+    // Fields are whitespace separated and the saved file holds secrets
+    let mut file = CredentialFile::default();
+    assert!(matches!(
+        file.set("http://host", basic("erin", "p w")),
+        Err(Error::AuthError(AuthErrorReason::UnstorableCredential(field))) if field == "password"
+    ));
+    file.set("http://host", basic("erin", "pw")).unwrap();
+    let path = root.join("credentials");
+    file.save(&path).unwrap();
+    assert_eq!(CredentialFile::read(&path).unwrap().entries, file.entries);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
+
+#[test]
+/// Tests the permissions users and teams get on a repo and the protected branches
+fn access() {
+    let mut users = UserStore::default();
+    for user in ["alice", "bob", "carol"] {
+        users.add_user(user, None).unwrap();
+    }
+    users.add_team_member("devs", "bob").unwrap();
+    assert!(matches!(
+        users.remove_team_member("ops", "bob"),
+        Err(Error::AuthError(AuthErrorReason::TeamNotFound(team))) if team == "ops"
+    ));
+    assert!(matches!(
+        users.remove_team_member("devs", "carol"),
+        Err(Error::AuthError(AuthErrorReason::UserNotFound(user))) if user == "carol"
+    ));
+
+    let mut server = temp_repo("access-server");
+    let mut access = server.access_control().unwrap();
+    assert_eq!(access.visibility, Visibility::Private);
+    access.users.insert("alice".to_owned(), Permission::Read);
+    access.users.insert("bob".to_owned(), Permission::Read);
+    access.teams.insert("devs".to_owned(), Permission::Write);
+    access.protected_branches.push(ProtectedBranch {
+        pattern: "main".to_owned(),
+        allow_force_push: false,
+        allow_direct_push: true,
+    });
+    access.protected_branches.push(ProtectedBranch {
+        pattern: "release/*".to_owned(),
+        allow_force_push: false,
+        allow_direct_push: false,
+    });
+    server.save_access_control(&access).unwrap();
+    let access = server.access_control().unwrap();
+
+    let permission = |user| access.permission(user, "Tester", &users);
+    assert_eq!(permission(Some("Tester")), Some(Permission::Admin));
+    assert_eq!(permission(Some("alice")), Some(Permission::Read));
+    // The best of the user's and their teams' permissions
+    assert_eq!(permission(Some("bob")), Some(Permission::Write));
+    assert_eq!(permission(Some("carol")), None);
+    assert_eq!(permission(None), None);
+    let public = AccessControl {
+        visibility: Visibility::Public,
+        ..access.clone()
+    };
+    assert_eq!(
+        public.permission(None, "Tester", &users),
+        Some(Permission::Read)
+    );
+    assert_eq!(
+        public.permission(Some("bob"), "Tester", &users),
+        Some(Permission::Write)
+    );
+
+    assert!(access.allows_branch_update("main", false, Permission::Write));
+    assert!(!access.allows_branch_update("main", true, Permission::Admin));
+    assert!(!access.allows_branch_update("release/1.0", false, Permission::Write));
+    assert!(access.allows_branch_update("release/1.0", false, Permission::Admin));
+    assert!(access.allows_branch_update("feature", true, Permission::Write));
+
+    // Pushes through the rules of a writer
+    fs::write(server.path.join("a.txt"), "a\n").unwrap();
+    server.commit("first").unwrap();
+    let remote = Remote::new("origin", "http://127.0.0.1:54510/Tester/access-server");
+    let mut client = Repo::clone_fetched(&server, &remote, &temp_dir("access-client")).unwrap();
+    fs::write(client.path.join("b.txt"), "b\n").unwrap();
+    client.commit("rewritten").unwrap();
+    client.create_branch("release/1.0", None).unwrap();
+    let guard = |branch: &str, rewrites: bool| {
+        access.allows_branch_update(branch, rewrites, Permission::Write)
+    };
+
+    fs::write(server.path.join("c.txt"), "c\n").unwrap();
+    server.commit("diverged").unwrap();
+    let options = PushOptions {
+        force: true,
+        branches: vec!["main".to_owned(), "release/1.0".to_owned()],
+        ..Default::default()
+    };
+    let request = negotiate(
+        &server,
+        client.push_request(&server.refs, &options).unwrap(),
+    );
+    let report = server.receive_push_guarded(request, &guard).unwrap();
+    assert_eq!(report.updates[0].status, RefUpdateStatus::RejectedProtected);
+    assert_eq!(report.updates[1].status, RefUpdateStatus::RejectedProtected);
+    assert!(!server.refs.branches.contains_key("release/1.0"));
+    assert_ne!(server.head(), client.head());
+
+    // A fast-forward of a branch only protected against force pushes goes through
+    fetch_local(&mut client, &server);
+    client
+        .reset(server.head().unwrap(), ResetMode::Hard)
+        .unwrap();
+    fs::write(client.path.join("d.txt"), "d\n").unwrap();
+    let ahead = client.commit("ahead").unwrap();
+    let options = PushOptions {
+        branches: vec!["main".to_owned()],
+        ..Default::default()
+    };
+    let request = negotiate(
+        &server,
+        client.push_request(&server.refs, &options).unwrap(),
+    );
+    let report = server.receive_push_guarded(request, &guard).unwrap();
+    assert_eq!(report.updates[0].status, RefUpdateStatus::Updated);
+    assert_eq!(server.head(), Some(ahead));
+}
+
+#[actix_web::test]
+/// Tests finding the hosted repos under the root dir of a server
+async fn server_discovery() {
+    let root = temp_dir("server-root");
+    Repo::init(&root.join("alice").join("project")).unwrap();
+    fs::create_dir_all(root.join("alice").join("not-a-repo")).unwrap();
+    fs::create_dir_all(root.join(".hidden").join("repo").join(repo::FURSION_DIR)).unwrap();
+    let mut users = UserStore::default();
+    users.add_user("alice", None).unwrap();
+    users.save(&root).unwrap();
+
+    let server = Server::new(&root).unwrap();
+    let key = |author: &str, name: &str| (author.to_owned(), name.to_owned());
+    assert_eq!(server.repos().await, vec![key("alice", "project")]);
+
+    Repo::init(&root.join("bob").join("other")).unwrap();
+    server.reload().await.unwrap();
+    assert_eq!(
+        server.repos().await,
+        vec![key("alice", "project"), key("bob", "other")]
+    );
+    fs::remove_dir_all(root.join("alice")).unwrap();
+    server.reload().await.unwrap();
+    assert_eq!(server.repos().await, vec![key("bob", "other")]);
+}
+
+#[actix_web::test]
+/// Tests the routes of a server, concurrent reads and the permission checks
+async fn server_requests() {
+    use actix_web::{
+        http::{header::AUTHORIZATION, StatusCode},
+        test::{self as actix_test, TestRequest},
+        App,
+    };
+
+    let root = temp_dir("server-requests");
+    let mut repo = Repo::init(&root.join("alice").join("project")).unwrap();
+    fs::write(repo.path.join("a.txt"), "a\n").unwrap();
+    let first = repo.commit("first").unwrap();
+    let mut users = UserStore::default();
+    users.add_user("alice", Some("pw")).unwrap();
+    users.add_user("bob", Some("pw")).unwrap();
+    users.save(&root).unwrap();
+
+    let server = Server::new(&root).unwrap();
+    let app =
+        actix_test::init_service(App::new().configure(|config| server.configure(config))).await;
+    let header = |user: &str| {
+        Credentials::Basic {
+            user: user.to_owned(),
+            password: "pw".to_owned(),
+        }
+        .to_header()
+    };
+    let get = |uri: &str, user: Option<&str>| {
+        let request = TestRequest::get().uri(uri);
+        match user {
+            Some(user) => request.insert_header((AUTHORIZATION, header(user))),
+            None => request,
+        }
+        .to_request()
+    };
+
+    // Overlapping reads all succeed
+    let responses = futures::future::join_all((0..4).map(|i| {
+        let uri = if i % 2 == 0 {
+            "/alice/project"
+        } else {
+            "/alice/project/refs"
+        };
+        actix_test::call_service(&app, get(uri, Some("alice")))
+    }))
+    .await;
+    assert!(responses
+        .iter()
+        .all(|response| response.status() == StatusCode::OK));
+    let refs: Refs =
+        actix_test::call_and_read_body_json(&app, get("/alice/project/refs", Some("alice"))).await;
+    assert_eq!(refs.branches["main"], first);
+    let summary: serde_json::Value =
+        actix_test::call_and_read_body_json(&app, get("/alice/project", Some("alice"))).await;
+    assert_eq!(summary["refs"], serde_json::to_value(&refs).unwrap());
+    assert!(summary.get("path").is_none() && summary.get("files").is_none());
+
+    let status = |response: actix_web::dev::ServiceResponse| response.status();
+    // Anonymous requests can't tell a private repo from a missing one
+    assert_eq!(
+        status(actix_test::call_service(&app, get("/alice/project/refs", None)).await),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status(actix_test::call_service(&app, get("/alice/missing/refs", None)).await),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status(actix_test::call_service(&app, get("/alice/project/refs", Some("bob"))).await),
+        StatusCode::NOT_FOUND
+    );
+    assert_eq!(
+        status(actix_test::call_service(&app, get("/alice/missing/refs", Some("alice"))).await),
+        StatusCode::NOT_FOUND
+    );
+
+    // Readers can't push
+    let mut access = repo.access_control().unwrap();
+    access.users.insert("bob".to_owned(), Permission::Read);
+    repo.save_access_control(&access).unwrap();
+    let negotiate = TestRequest::post()
+        .uri("/alice/project/push/negotiate")
+        .insert_header((AUTHORIZATION, header("bob")))
+        .set_json(Vec::<CommitId>::new())
+        .to_request();
+    assert_eq!(
+        status(actix_test::call_service(&app, negotiate).await),
+        StatusCode::FORBIDDEN
+    );
+    // Anonymous senders are asked for credentials to push to a public repo
+    access.visibility = Visibility::Public;
+    repo.save_access_control(&access).unwrap();
+    let negotiate = TestRequest::post()
+        .uri("/alice/project/push/negotiate")
+        .set_json(Vec::<CommitId>::new())
+        .to_request();
+    assert_eq!(
+        status(actix_test::call_service(&app, negotiate).await),
+        StatusCode::UNAUTHORIZED
+    );
+
+    // Commits made in the hosted repo since it was read are kept by pushes
+    let remote = Remote::new("origin", "http://127.0.0.1:54510/alice/project");
+    let mut client = Repo::clone_fetched(&repo, &remote, &temp_dir("server-client")).unwrap();
+    fs::write(repo.path.join("a.txt"), "a\nb\n").unwrap();
+    let local = repo.commit("local").unwrap();
+    client.create_branch("feature", None).unwrap();
+    let options = PushOptions {
+        branches: vec!["feature".to_owned()],
+        ..Default::default()
+    };
+    let push = TestRequest::post()
+        .uri("/alice/project/push")
+        .insert_header((AUTHORIZATION, header("alice")))
+        .set_json(client.push_request(&repo.refs, &options).unwrap())
+        .to_request();
+    let report: PushReport = actix_test::call_and_read_body_json(&app, push).await;
+    assert!(!report.is_rejected());
+    let pushed = Repo::read(&repo.path).unwrap();
+    assert_eq!(pushed.refs.branches["main"], local);
+    assert_eq!(pushed.refs.branches["feature"], first);
+
+    // Repos made after the server started are found on their first request
+    Repo::init(&root.join("alice").join("later")).unwrap();
+    assert_eq!(
+        status(actix_test::call_service(&app, get("/alice/later/refs", Some("alice"))).await),
+        StatusCode::OK
+    );
 }
@@ -0,0 +1,272 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, RwLock},
+};
+
+use futures::future::BoxFuture;
+use once_cell::sync::Lazy;
+use reqwest::header::AUTHORIZATION;
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::{
+    bundle::Bundle,
+    commit::CommitId,
+    credential::{credentials_for, Credentials},
+    error::{BundleErrorReason, Error, RemoteConfigErrorReason},
+    fetch::FetchRequest,
+    pack::Pack,
+    push::{PushReport, PushRequest},
+    refs::Refs,
+    remote::Remote,
+    repo::Repo,
+};
+
+/// How the repo at a url is talked to, see [`register_transport`]
+pub trait Transport: Send + Sync {
+    /// The branches, tags and HEAD of the repo
+    fn list_refs<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Refs, Error>>;
+
+    /// The commits the repo has and the client doesn't, see [`Repo::pack_missing`]
+    fn fetch_objects<'a>(
+        &'a self,
+        url: &'a str,
+        request: &'a FetchRequest,
+    ) -> BoxFuture<'a, Result<Pack, Error>>;
+
+    /// Uploads the commits of a push and asks the repo to update its refs, the commits
+    /// the repo already has may be left out, see [`Repo::receive_push`]
+    fn push_objects<'a>(
+        &'a self,
+        url: &'a str,
+        request: &'a PushRequest,
+    ) -> BoxFuture<'a, Result<PushReport, Error>>;
+}
+
+/// Scheme of the urls without one, which are paths on this machine
+const LOCAL_SCHEME: &str = "file";
+
+static TRANSPORTS: Lazy<RwLock<HashMap<String, Arc<dyn Transport>>>> = Lazy::new(|| {
+    let mut transports: HashMap<String, Arc<dyn Transport>> = HashMap::new();
+    transports.insert("http".to_owned(), Arc::new(HttpTransport::default()));
+    transports.insert("https".to_owned(), Arc::new(HttpTransport::default()));
+    transports.insert(LOCAL_SCHEME.to_owned(), Arc::new(LocalTransport));
+    RwLock::new(transports)
+});
+
+/// Uses `transport` for the urls starting with `<scheme>://`, in place of the built-in one if any
+pub fn register_transport(scheme: &str, transport: Arc<dyn Transport>) {
+    TRANSPORTS
+        .write()
+        .unwrap()
+        .insert(scheme.to_owned(), transport);
+}
+
+/// The transport of the scheme of a url
+pub fn transport_for(url: &str) -> Result<Arc<dyn Transport>, Error> {
+    let scheme = url
+        .split_once("://")
+        .map_or(LOCAL_SCHEME, |(scheme, _)| scheme);
+    TRANSPORTS
+        .read()
+        .unwrap()
+        .get(scheme)
+        .cloned()
+        .ok_or_else(|| {
+            Error::RemoteConfigError(RemoteConfigErrorReason::UnsupportedScheme(
+                scheme.to_owned(),
+            ))
+        })
+}
+
+/// Talks to a repo hosted by a [`Server`](crate::server::Server)
+#[derive(Debug, Clone, Default)]
+pub struct HttpTransport {
+    /// Credentials sent with every request, looked up for each url if [`None`],
+    /// see [`credentials_for`]
+    pub credentials: Option<Credentials>,
+}
+
+impl HttpTransport {
+    pub fn with_credentials(credentials: Credentials) -> Self {
+        HttpTransport {
+            credentials: Some(credentials),
+        }
+    }
+
+    fn endpoint(url: &str, path: &str) -> String {
+        format!("{}/{}", url.trim_end_matches('/'), path)
+    }
+
+    /// A request to an endpoint of the repo at `url` carrying the credentials for it
+    fn request(
+        &self,
+        method: reqwest::Method,
+        url: &str,
+        path: &str,
+    ) -> Result<reqwest::RequestBuilder, Error> {
+        let request = reqwest::Client::new().request(method, Self::endpoint(url, path));
+        let credentials = match &self.credentials {
+            Some(credentials) => Some(credentials.clone()),
+            None => credentials_for(url)?,
+        };
+        Ok(match credentials {
+            Some(credentials) => request.header(AUTHORIZATION, credentials.to_header()),
+            None => request,
+        })
+    }
+
+    async fn post<B: Serialize + ?Sized, R: DeserializeOwned>(
+        &self,
+        url: &str,
+        path: &str,
+        body: &B,
+    ) -> Result<R, Error> {
+        let res = self
+            .request(reqwest::Method::POST, url, path)?
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| Error::RemoteError(e.to_string()))?;
+        Self::read_response(res).await
+    }
+
+    async fn read_response<R: DeserializeOwned>(res: reqwest::Response) -> Result<R, Error> {
+        Self::check_status(res)
+            .await?
+            .json()
+            .await
+            .map_err(|e| Error::RemoteError(e.to_string()))
+    }
+
+    async fn check_status(res: reqwest::Response) -> Result<reqwest::Response, Error> {
+        if !res.status().is_success() {
+            let status = res.status();
+            let body = res.text().await.unwrap_or_default();
+            return Err(Error::RemoteError(format!("{}: {}", status, body)));
+        }
+        Ok(res)
+    }
+}
+
+impl Transport for HttpTransport {
+    fn list_refs<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Refs, Error>> {
+        Box::pin(async move {
+            let res = self
+                .request(reqwest::Method::GET, url, "refs")?
+                .send()
+                .await
+                .map_err(|e| Error::RemoteError(e.to_string()))?;
+            Self::read_response(res).await
+        })
+    }
+
+    fn fetch_objects<'a>(
+        &'a self,
+        url: &'a str,
+        request: &'a FetchRequest,
+    ) -> BoxFuture<'a, Result<Pack, Error>> {
+        Box::pin(async move {
+            let res = self
+                .request(reqwest::Method::POST, url, "fetch")?
+                .json(request)
+                .send()
+                .await
+                .map_err(|e| Error::RemoteError(e.to_string()))?;
+            let body = Self::check_status(res)
+                .await?
+                .text()
+                .await
+                .map_err(|e| Error::RemoteError(e.to_string()))?;
+            body.parse::<Pack>()
+        })
+    }
+
+    /// Asks the server which commits it is missing first so that only those are uploaded
+    fn push_objects<'a>(
+        &'a self,
+        url: &'a str,
+        request: &'a PushRequest,
+    ) -> BoxFuture<'a, Result<PushReport, Error>> {
+        Box::pin(async move {
+            let candidates = request
+                .commits
+                .iter()
+                .map(|commit| commit.id)
+                .collect::<Vec<_>>();
+            let missing: HashSet<CommitId> = if candidates.is_empty() {
+                HashSet::new()
+            } else {
+                self.post::<_, Vec<CommitId>>(url, "push/negotiate", &candidates)
+                    .await?
+                    .into_iter()
+                    .collect()
+            };
+            let mut request = request.clone();
+            request
+                .commits
+                .retain(|commit| missing.contains(&commit.id));
+            self.post(url, "push", &request).await
+        })
+    }
+}
+
+/// Talks directly to a repo on this machine, the url being its path, plain or as a
+/// `file://` url, the path may also be the one of a [`Bundle`] which can't be pushed to
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalTransport;
+
+/// What a local url leads to
+enum LocalTarget {
+    Repo(Box<Repo>),
+    Bundle(Bundle),
+}
+
+impl LocalTransport {
+    fn open(url: &str) -> Result<LocalTarget, Error> {
+        let path = Remote::local_path(url).ok_or_else(|| {
+            Error::RemoteConfigError(RemoteConfigErrorReason::UnsupportedScheme(url.to_owned()))
+        })?;
+        if Bundle::is_bundle(&path) {
+            return Ok(LocalTarget::Bundle(Bundle::read(&path)?));
+        }
+        Ok(LocalTarget::Repo(Box::new(Repo::read(&path)?)))
+    }
+}
+
+impl Transport for LocalTransport {
+    fn list_refs<'a>(&'a self, url: &'a str) -> BoxFuture<'a, Result<Refs, Error>> {
+        Box::pin(async move {
+            Ok(match Self::open(url)? {
+                LocalTarget::Repo(repo) => repo.refs,
+                LocalTarget::Bundle(bundle) => bundle.refs,
+            })
+        })
+    }
+
+    /// A bundle sends all of its commits, the known ones being skipped by the client
+    fn fetch_objects<'a>(
+        &'a self,
+        url: &'a str,
+        request: &'a FetchRequest,
+    ) -> BoxFuture<'a, Result<Pack, Error>> {
+        Box::pin(async move {
+            match Self::open(url)? {
+                LocalTarget::Repo(repo) => repo.pack_missing(request),
+                LocalTarget::Bundle(bundle) => Ok(bundle.pack),
+            }
+        })
+    }
+
+    fn push_objects<'a>(
+        &'a self,
+        url: &'a str,
+        request: &'a PushRequest,
+    ) -> BoxFuture<'a, Result<PushReport, Error>> {
+        Box::pin(async move {
+            match Self::open(url)? {
+                LocalTarget::Repo(mut repo) => repo.receive_push(request.clone()),
+                LocalTarget::Bundle(_) => Err(Error::BundleError(BundleErrorReason::ReadOnly)),
+            }
+        })
+    }
+}
@@ -0,0 +1,171 @@
+use std::{collections::BTreeMap, fs, path::Path};
+
+use crate::{
+    commit::FileChanges,
+    diff,
+    error::{Error, RepoErrorReason},
+    repo::FURSION_DIR,
+};
+
+/// Content of every file of the repo at some point, indexed by their path relative to the repo root
+pub type Tree = BTreeMap<String, String>;
+
+/// Applies a list of file changes on a tree
+pub fn apply_changes(tree: &mut Tree, changes: &[FileChanges]) {
+    for file_changes in changes {
+        if file_changes.is_removal() {
+            tree.remove(file_changes.path());
+        } else {
+            let old = tree
+                .get(file_changes.path())
+                .map(String::as_str)
+                .unwrap_or("");
+            let new = file_changes.apply(old);
+            tree.insert(file_changes.path().to_owned(), new);
+        }
+    }
+}
+
+/// Computes the file changes needed to go from the `old` tree to the `new` one
+pub fn diff_trees(old: &Tree, new: &Tree) -> Vec<FileChanges> {
+    let mut changes = Vec::new();
+    for (path, old_content) in old {
+        match new.get(path) {
+            None => changes.push(FileChanges::removal(path.clone(), old_content)),
+            Some(new_content) if new_content != old_content => {
+                changes.push(diff::diff_text(path, old_content, new_content))
+            }
+            Some(_) => {}
+        }
+    }
+    for (path, new_content) in new {
+        if !old.contains_key(path) {
+            changes.push(diff::diff_text(path, "", new_content));
+        }
+    }
+    changes.sort_by(|a, b| a.path().cmp(b.path()));
+    changes
+}
+
+/// Whether `path` is `filter` itself or a file under the `filter` directory
+pub fn path_matches(path: &str, filter: &str) -> bool {
+    let filter = filter.trim_end_matches('/');
+    filter.is_empty()
+        || filter == "."
+        || path == filter
+        || path
+            .strip_prefix(filter)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Whether a path relative to the repo root matches one of the ignore patterns,
+/// a pattern either names a file or directory, or uses `*` as a wildcard
+pub fn is_ignored(path: &str, ignored: &[String]) -> bool {
+    ignored.iter().any(|pattern| {
+        let pattern = pattern.trim_end_matches('/');
+        if pattern.contains('/') {
+            path_matches(path, pattern.trim_start_matches('/'))
+        } else {
+            path.split('/').any(|name| glob_match(pattern, name))
+        }
+    })
+}
+
+fn glob_match(pattern: &str, name: &str) -> bool {
+    match pattern.split_once('*') {
+        None => pattern == name,
+        Some((prefix, rest)) => {
+            let Some(name) = name.strip_prefix(prefix) else {
+                return false;
+            };
+            (0..=name.len())
+                .filter(|i| name.is_char_boundary(*i))
+                .any(|i| glob_match(rest, &name[i..]))
+        }
+    }
+}
+
+/// Reads every file of the working directory which is not ignored, failing on the ones
+/// which aren't UTF-8 text rather than tracking an altered copy of them
+pub fn read_working_tree(root: &Path, ignored: &[String]) -> Result<Tree, Error> {
+    let mut tree = Tree::new();
+    read_dir_into(root, root, ignored, &mut tree)?;
+    Ok(tree)
+}
+
+fn read_dir_into(
+    root: &Path,
+    dir: &Path,
+    ignored: &[String],
+    tree: &mut Tree,
+) -> Result<(), Error> {
+    let entries = fs::read_dir(dir)
+        .map_err(|_| Error::RepoReadFailed(RepoErrorReason::PathNotFound(dir.to_owned())))?;
+
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_name() == FURSION_DIR {
+            continue;
+        }
+        let path = entry.path();
+        let relative = relative_path(root, &path);
+        if is_ignored(&relative, ignored) {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            read_dir_into(root, &path, ignored, tree)?;
+        } else {
+            let data = fs::read(&path).map_err(|e| {
+                Error::RepoReadFailed(RepoErrorReason::CantReadFile(path.clone(), e.to_string()))
+            })?;
+            let content = String::from_utf8(data)
+                .map_err(|_| Error::RepoReadFailed(RepoErrorReason::FileIsNotText(path.clone())))?;
+            tree.insert(relative, content);
+        }
+    }
+    Ok(())
+}
+
+/// The path of a file relative to the repo root, with `/` separators
+pub fn relative_path(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Updates the files of the working directory which differ between the `old` and `new` trees
+pub fn write_working_tree(root: &Path, old: &Tree, new: &Tree) -> Result<(), Error> {
+    for path in old.keys().filter(|path| !new.contains_key(*path)) {
+        let file_path = root.join(path);
+        if Path::exists(&file_path) {
+            fs::remove_file(&file_path)?;
+        }
+        remove_empty_parents(root, &file_path);
+    }
+
+    for (path, content) in new {
+        if old.get(path) == Some(content) && Path::exists(&root.join(path)) {
+            continue;
+        }
+        let file_path = root.join(path);
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&file_path, content)?;
+    }
+    Ok(())
+}
+
+fn remove_empty_parents(root: &Path, file_path: &Path) {
+    let mut dir = file_path.parent();
+    while let Some(path) = dir {
+        if path == root || fs::remove_dir(path).is_err() {
+            break;
+        }
+        dir = path.parent();
+    }
+}